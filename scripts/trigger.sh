@@ -1,10 +1,11 @@
 #!/usr/bin/env bash
 # SuperWhisper Linux trigger script for Hyprland
-# Usage: trigger.sh [command]
-# Commands: toggle (default), start, stop, cancel, status
+# Usage: trigger.sh [command] [output]
+# Commands: toggle (default), start, stop, rollover, cancel, ptt-down, ptt-up, status
+# toggle and stop accept an output mode: clipboard, primary, both (e.g. "stop primary")
 
 SOCKET="${XDG_RUNTIME_DIR:-/run/user/$(id -u)}/super-whisper.sock"
-COMMAND="${1:-toggle}"
+COMMAND="${*:-toggle}"
 
 if [[ ! -S "$SOCKET" ]]; then
     notify-send "SuperWhisper" "App not running" -u critical 2>/dev/null
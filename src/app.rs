@@ -1,13 +1,20 @@
 use cpal::Stream;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
-use tokio::sync::{watch, Mutex};
+use tokio::sync::{broadcast, mpsc, oneshot, watch, Mutex};
 use tracing::{debug, error, info, warn};
 
-use crate::audio::{AudioCapture, CaptureConfig};
-use crate::clipboard;
-use crate::config::AppConfig;
+use crate::audio::{
+    speech_bounds, AudioCapture, AudioLevel, CaptureConfig, CaptureEngine, Cue, DspChain, Duck,
+    FeedbackPlayer, Playback,
+};
+use crate::clipboard::{self, TextOutput, WaylandOutput};
+use crate::config::{self, AppConfig, MaxDurationAction, OutputMode, ProviderType};
 use crate::error::{AppError, Result};
-use crate::ipc::IpcCommand;
+use crate::history::{History, HistoryEntry};
+use crate::ipc::{CommandRequest, CommandSender, IpcCommand, IpcEvent};
+use crate::logging::LogHandle;
+use crate::notify::Notifier;
 use crate::stt::{self, AudioData, SttProvider};
 use crate::tray::TrayState;
 
@@ -24,45 +31,243 @@ pub enum AppState {
     Error,
 }
 
+/// What became of a transcription
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Outcome {
+    /// Text was delivered to the output
+    Delivered,
+    /// There was no text to deliver
+    Empty,
+    /// Nothing was above the silence threshold, so nothing was transcribed
+    Silent,
+    /// Transcription failed; already logged and notified
+    Failed,
+}
+
+/// How long the input may stay near-silent after recording starts before
+/// the user is warned (wrong device or muted microphone)
+const SILENT_INPUT_WARNING: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// Settings that can be changed while running (tray menu / IPC)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuntimeSettings {
+    pub provider: ProviderType,
+    pub language: String,
+    pub input_device: Option<String>,
+    pub auto_paste: bool,
+}
+
+impl RuntimeSettings {
+    fn from_config(config: &AppConfig) -> Self {
+        Self {
+            provider: config.general.default_provider,
+            language: config.general.language.clone(),
+            input_device: config.audio.input_device.clone(),
+            auto_paste: config.general.auto_paste,
+        }
+    }
+}
+
 /// Main application
 pub struct App {
     config: AppConfig,
     state_tx: watch::Sender<AppState>,
     state_rx: watch::Receiver<AppState>,
+    settings_tx: watch::Sender<RuntimeSettings>,
+    history: Arc<Mutex<History>>,
+    recent_tx: Arc<watch::Sender<Vec<HistoryEntry>>>,
+    level_tx: watch::Sender<AudioLevel>,
+    // Names of the input devices present
+    devices_tx: watch::Sender<Vec<String>>,
+    events_tx: broadcast::Sender<IpcEvent>,
     provider: Arc<Mutex<Option<Box<dyn SttProvider>>>>,
     audio_capture: Arc<Mutex<Option<AudioCapture>>>,
     audio_buffer: Arc<Mutex<Vec<f32>>>,
+    // Samples of the current recording lost to capture overflow
+    dropped_samples: Arc<AtomicU64>,
     // Store stream separately - it's not Send so we use a std Mutex
     #[allow(dead_code)]
     audio_stream: Arc<std::sync::Mutex<Option<Stream>>>,
     // Store the audio collection task handle so we can await it
     audio_task: Arc<Mutex<Option<tokio::task::JoinHandle<()>>>>,
+    // Persistent capture; None if the device could not be opened at startup
+    engine: std::sync::Mutex<Option<CaptureEngine>>,
+    // When the push-to-talk key went down, while it is held
+    ptt_pressed: std::sync::Mutex<Option<std::time::Instant>>,
+    // Stop scheduled after the push-to-talk tail, until it is sent
+    ptt_tail: std::sync::Mutex<Option<tokio::task::JoinHandle<()>>>,
+    feedback: Option<FeedbackPlayer>,
+    notifier: Option<Notifier>,
+    log_handle: Option<LogHandle>,
+    // Lets the recording task issue commands (e.g. stop at max_duration)
+    commands: Option<CommandSender>,
+    // Recordings waiting to be transcribed, in order
+    transcriptions: mpsc::UnboundedSender<Transcription>,
+}
+
+/// Reads the app's status from its watch channels
+#[derive(Clone)]
+pub struct StatusReporter {
+    state_rx: watch::Receiver<AppState>,
+    settings_rx: watch::Receiver<RuntimeSettings>,
+    // Device the capture engine has open; None without an engine
+    device_rx: Option<watch::Receiver<Option<String>>>,
+}
+
+impl StatusReporter {
+    /// One-line status: state, input device and provider
+    pub fn status(&self) -> String {
+        let settings = self.settings_rx.borrow().clone();
+        let device = self
+            .device_rx
+            .as_ref()
+            .and_then(|device| device.borrow().clone())
+            .or(settings.input_device)
+            .unwrap_or_else(|| "default".to_string());
+
+        format!(
+            "{} (device: {}, provider: {})",
+            format!("{:?}", *self.state_rx.borrow()).to_lowercase(),
+            device,
+            settings.provider
+        )
+    }
+}
+
+/// A recording (or rollover chunk) queued for transcription
+struct Transcription {
+    samples: Vec<f32>,
+    dropped: u64,
+    output: Option<OutputMode>,
+    settings: RuntimeSettings,
+    // Receives the outcome when the caller waits for it
+    done: Option<oneshot::Sender<Outcome>>,
+}
+
+/// Transcribes queued recordings one at a time, so transcripts are
+/// delivered in the order they were recorded
+#[derive(Clone)]
+struct Transcriber {
+    config: Arc<AppConfig>,
+    provider: Arc<Mutex<Option<Box<dyn SttProvider>>>>,
+    history: Arc<Mutex<History>>,
+    recent_tx: Arc<watch::Sender<Vec<HistoryEntry>>>,
+    notifier: Option<Notifier>,
+    output: Arc<dyn TextOutput>,
 }
 
 impl App {
     pub async fn new(config: AppConfig) -> Result<Self> {
+        let app = Self::with_output(config, Arc::new(WaylandOutput));
+        app.start_engine();
+        Ok(app)
+    }
+
+    /// Build the app without opening the input device, delivering
+    /// transcripts to `output`
+    fn with_output(config: AppConfig, output: Arc<dyn TextOutput>) -> Self {
         let (state_tx, state_rx) = watch::channel(AppState::Idle);
+        let (settings_tx, _) = watch::channel(RuntimeSettings::from_config(&config));
+
+        let history = History::load(&config.history);
+        let (recent_tx, _) = watch::channel(history.recent());
+        let (level_tx, _) = watch::channel(AudioLevel::default());
+        let (devices_tx, _) = watch::channel(Vec::new());
+        let (events_tx, _) = broadcast::channel(64);
+
+        let feedback = config
+            .general
+            .audio_feedback
+            .then(|| FeedbackPlayer::new(&config.sounds));
+
+        let notifier = if config.notifications.enabled {
+            match Notifier::new(&config.notifications) {
+                Ok(notifier) => Some(notifier),
+                Err(e) => {
+                    warn!("Desktop notifications unavailable: {}", e);
+                    None
+                }
+            }
+        } else {
+            None
+        };
 
-        Ok(Self {
+        let history = Arc::new(Mutex::new(history));
+        let recent_tx = Arc::new(recent_tx);
+        let provider = Arc::new(Mutex::new(None));
+        let transcriptions = Transcriber {
+            config: Arc::new(config.clone()),
+            provider: provider.clone(),
+            history: history.clone(),
+            recent_tx: recent_tx.clone(),
+            notifier: notifier.clone(),
+            output,
+        }
+        .spawn();
+
+        Self {
             config,
             state_tx,
             state_rx,
-            provider: Arc::new(Mutex::new(None)),
+            settings_tx,
+            history,
+            recent_tx,
+            level_tx,
+            devices_tx,
+            events_tx,
+            provider,
             audio_capture: Arc::new(Mutex::new(None)),
             audio_buffer: Arc::new(Mutex::new(Vec::new())),
+            dropped_samples: Arc::new(AtomicU64::new(0)),
             audio_stream: Arc::new(std::sync::Mutex::new(None)),
             audio_task: Arc::new(Mutex::new(None)),
-        })
+            engine: std::sync::Mutex::new(None),
+            ptt_pressed: std::sync::Mutex::new(None),
+            ptt_tail: std::sync::Mutex::new(None),
+            feedback,
+            notifier,
+            log_handle: None,
+            commands: None,
+            transcriptions,
+        }
+    }
+
+    /// Open the input device once for all recordings
+    fn start_engine(&self) {
+        let capture_config = CaptureConfig {
+            sample_rate: self.config.audio.sample_rate,
+            device_name: self.settings().input_device,
+            preferences: self.config.audio.input_devices.clone(),
+            downmix: self.config.audio.downmix,
+            downmix_channel: self.config.audio.downmix_channel,
+            buffer_size: self.config.audio.buffer_size,
+        };
+        let pre_roll = std::time::Duration::from_millis(self.config.audio.pre_roll_ms as u64);
+
+        match CaptureEngine::start(capture_config, pre_roll, self.devices_tx.clone()) {
+            Ok(engine) => *self.engine.lock().unwrap() = Some(engine),
+            Err(e) => {
+                warn!("Audio device unavailable, opening it per recording: {}", e);
+                // Without the engine nobody watches for hotplug; list once
+                self.devices_tx
+                    .send_replace(AudioCapture::list_devices().unwrap_or_default());
+            }
+        }
+    }
+
+    /// Pause the capture engine after a recording
+    fn end_capture(&self) {
+        if let Some(engine) = self.engine.lock().unwrap().as_ref() {
+            engine.end();
+        }
     }
 
     /// Initialize the STT provider
     pub async fn init_provider(&self) -> Result<()> {
-        info!(
-            "Initializing STT provider: {}",
-            self.config.general.default_provider
-        );
+        let provider_type = self.settings().provider;
+        info!("Initializing STT provider: {}", provider_type);
 
-        let provider = stt::create_provider(self.config.general.default_provider, &self.config)
+        let provider = stt::create_provider(provider_type, &self.config)
             .await
             .map_err(|e| AppError::Stt(e))?;
 
@@ -71,6 +276,33 @@ impl App {
         Ok(())
     }
 
+    /// Switch to another STT provider, keeping the current one on failure
+    async fn set_provider(&self, provider_type: ProviderType) -> Result<()> {
+        if self.settings().provider == provider_type {
+            return Ok(());
+        }
+
+        info!("Switching STT provider to {}", provider_type);
+        let provider = stt::create_provider(provider_type, &self.config)
+            .await
+            .map_err(AppError::Stt)?;
+
+        *self.provider.lock().await = Some(provider);
+        self.settings_tx.send_modify(|s| s.provider = provider_type);
+        Ok(())
+    }
+
+    /// Allow the `log-level` command to change the log filter
+    pub fn set_log_handle(&mut self, handle: LogHandle) {
+        self.log_handle = Some(handle);
+    }
+
+    /// Channel the recording task uses to stop (or roll over) when
+    /// `max_duration` is reached; usually the sender of the main command loop
+    pub fn set_command_sender(&mut self, commands: CommandSender) {
+        self.commands = Some(commands);
+    }
+
     /// Get a receiver for state changes
     pub fn state_receiver(&self) -> watch::Receiver<AppState> {
         self.state_rx.clone()
@@ -81,13 +313,63 @@ impl App {
         *self.state_rx.borrow()
     }
 
+    /// One-line status: state, input device and provider
+    pub fn status(&self) -> String {
+        self.status_reporter().status()
+    }
+
+    /// Answers `status` from the current state, without going through the
+    /// command loop (which may be busy transcribing)
+    pub fn status_reporter(&self) -> StatusReporter {
+        StatusReporter {
+            state_rx: self.state_rx.clone(),
+            settings_rx: self.settings_tx.subscribe(),
+            device_rx: self
+                .engine
+                .lock()
+                .unwrap()
+                .as_ref()
+                .map(CaptureEngine::device_receiver),
+        }
+    }
+
+    /// Get a receiver for runtime settings changes
+    pub fn settings_receiver(&self) -> watch::Receiver<RuntimeSettings> {
+        self.settings_tx.subscribe()
+    }
+
+    /// Get the current runtime settings
+    pub fn settings(&self) -> RuntimeSettings {
+        self.settings_tx.borrow().clone()
+    }
+
+    /// Get a receiver for the input level while recording
+    pub fn level_receiver(&self) -> watch::Receiver<AudioLevel> {
+        self.level_tx.subscribe()
+    }
+
+    /// Get a receiver for the names of the input devices present
+    pub fn devices_receiver(&self) -> watch::Receiver<Vec<String>> {
+        self.devices_tx.subscribe()
+    }
+
+    /// Sender for events streamed to IPC subscribers
+    pub fn event_sender(&self) -> broadcast::Sender<IpcEvent> {
+        self.events_tx.clone()
+    }
+
+    /// Get a receiver for the recent transcripts (newest first)
+    pub fn recent_receiver(&self) -> watch::Receiver<Vec<HistoryEntry>> {
+        self.recent_tx.subscribe()
+    }
+
     /// Handle an IPC command
     pub async fn handle_command(&self, cmd: IpcCommand) -> Result<()> {
         match cmd {
-            IpcCommand::Toggle => {
+            IpcCommand::Toggle { output } => {
                 match self.state() {
                     AppState::Idle => self.start_recording().await?,
-                    AppState::Recording => self.stop_and_transcribe().await?,
+                    AppState::Recording => self.stop_and_transcribe(output).await?,
                     _ => {
                         debug!("Ignoring toggle command in {:?} state", self.state());
                     }
@@ -98,16 +380,77 @@ impl App {
                     self.start_recording().await?;
                 }
             }
-            IpcCommand::Stop => {
+            IpcCommand::Stop { output } => {
                 if self.state() == AppState::Recording {
-                    self.stop_and_transcribe().await?;
+                    self.stop_and_transcribe(output).await?;
                 }
             }
+            IpcCommand::Rollover => {
+                self.rollover().await?;
+            }
             IpcCommand::Cancel => {
                 self.cancel().await?;
             }
+            IpcCommand::PttDown => {
+                self.ptt_down().await?;
+            }
+            IpcCommand::PttUp => {
+                self.ptt_up().await?;
+            }
             IpcCommand::Status => {
-                info!("Current state: {:?}", self.state());
+                info!("Current state: {}", self.status());
+            }
+            IpcCommand::LogLevel(level) => match &self.log_handle {
+                Some(handle) => {
+                    handle.set_level(&level)?;
+                    info!("Log level changed to {}", level);
+                }
+                None => {
+                    return Err(AppError::Other(
+                        "Log level cannot be changed at runtime".into(),
+                    ))
+                }
+            },
+            IpcCommand::Provider(provider) => {
+                self.set_provider(provider).await?;
+            }
+            IpcCommand::Language(language) => {
+                info!("Language set to {}", language);
+                self.settings_tx.send_modify(|s| s.language = language);
+            }
+            IpcCommand::Device(device) => {
+                info!("Input device set to {}", device.as_deref().unwrap_or("default"));
+                self.settings_tx.send_modify(|s| s.input_device = device);
+                if let Some(engine) = self.engine.lock().unwrap().as_ref() {
+                    engine.set_device(self.settings().input_device);
+                }
+            }
+            IpcCommand::AutoPaste(enabled) => {
+                info!("Auto-paste {}", if enabled { "enabled" } else { "disabled" });
+                self.settings_tx.send_modify(|s| s.auto_paste = enabled);
+            }
+            IpcCommand::Copy(index) => {
+                let text = self.recent_text(index).await?;
+                clipboard::set_clipboard(&text).await?;
+            }
+            IpcCommand::Paste(index) => {
+                let text = self.recent_text(index).await?;
+                clipboard::paste_text(&text).await?;
+            }
+            IpcCommand::OpenConfig => {
+                let path = config::config_path();
+                if !path.exists() {
+                    config::save_config(&self.config)?;
+                }
+                open_path(&path)?;
+            }
+            IpcCommand::OpenHistory => {
+                let path = self.history.lock().await.path().cloned();
+                match path {
+                    Some(path) if path.exists() => open_path(&path)?,
+                    Some(_) => info!("No transcripts recorded yet"),
+                    None => warn!("Transcript history is disabled"),
+                }
             }
             IpcCommand::Shutdown => {
                 info!("Shutdown requested");
@@ -120,6 +463,8 @@ impl App {
     /// Start recording audio
     async fn start_recording(&self) -> Result<()> {
         info!("Starting recording");
+        // A stop left over from the previous recording's push-to-talk tail
+        self.abort_ptt_tail();
 
         // Forcibly abort any lingering audio task from a previous session
         if let Some(task) = self.audio_task.lock().await.take() {
@@ -129,32 +474,76 @@ impl App {
 
         // Clear audio buffer
         self.audio_buffer.lock().await.clear();
+        self.dropped_samples.store(0, Ordering::Relaxed);
 
-        // Create audio capture
-        let capture_config = CaptureConfig {
-            sample_rate: self.config.audio.sample_rate,
-            device_name: self.config.audio.input_device.clone(),
-            ..Default::default()
-        };
+        // Resume the persistent capture, or open the device now
+        let started = self.engine.lock().unwrap().as_ref().map(|e| e.begin());
+        let mut rx = match started {
+            Some((pre_roll, rx)) => {
+                if !pre_roll.is_empty() {
+                    debug!(
+                        "Prepending {} ms of pre-roll",
+                        pre_roll.len() as u64 * 1000 / self.config.audio.sample_rate as u64
+                    );
+                    self.audio_buffer.lock().await.extend(pre_roll);
+                }
+                rx
+            }
+            None => {
+                let capture_config = CaptureConfig {
+                    sample_rate: self.config.audio.sample_rate,
+                    device_name: self.settings().input_device,
+                    preferences: self.config.audio.input_devices.clone(),
+                    downmix: self.config.audio.downmix,
+                    downmix_channel: self.config.audio.downmix_channel,
+                    buffer_size: self.config.audio.buffer_size,
+                };
 
-        let capture = AudioCapture::new(capture_config).map_err(AppError::Audio)?;
-        let (stream, mut rx) = capture.start().map_err(AppError::Audio)?;
+                let capture = AudioCapture::new(capture_config).map_err(AppError::Audio)?;
+                let (stream, rx) = capture.start().map_err(AppError::Audio)?;
 
-        // Store capture and stream (keeps them alive)
-        *self.audio_capture.lock().await = Some(capture);
-        *self.audio_stream.lock().unwrap() = Some(stream);
+                // Store capture and stream (keeps them alive)
+                *self.audio_capture.lock().await = Some(capture);
+                *self.audio_stream.lock().unwrap() = Some(stream);
+                rx
+            }
+        };
+
+        // Capture of the recording proper starts now, after the pre-roll
+        let started = std::time::Instant::now();
+        let pre_roll_len = self.audio_buffer.lock().await.len();
 
         // Update state
-        self.set_state(AppState::Recording);
+        let start_cue = self.set_state(AppState::Recording);
 
         // Spawn task to collect audio samples (rx is Send, stream is not)
         let buffer = self.audio_buffer.clone();
         let mut state_rx = self.state_rx.clone();
         let max_duration = self.config.audio.max_duration;
+        let on_max_duration = self.config.audio.on_max_duration;
+        let commands = self.commands.clone();
+
+        // Drop the start cue from the recording once it has played
+        let mut duck = start_cue.filter(|_| self.config.sounds.duck).map(|playback| {
+            Duck::new(playback, started, self.config.audio.sample_rate, pre_roll_len)
+        });
+
+        let level_tx = self.level_tx.clone();
+        let events_tx = self.events_tx.clone();
+        let notifier = self.notifier.clone();
+        let silence_threshold = self.config.audio.silence_threshold;
+        let dropped_samples = self.dropped_samples.clone();
+        let sample_rate = self.config.audio.sample_rate;
 
         let task = tokio::spawn(async move {
             let start = std::time::Instant::now();
+            // Start of the current chunk; only differs from `start` after a rollover
+            let mut chunk_start = start;
             let max_duration = std::time::Duration::from_secs(max_duration as u64);
+            // Samples the ring had dropped at the last check
+            let mut seen_dropped = 0;
+            // Loudest peak so far, until the silent input check has run
+            let mut initial_peak = Some(0.0f32);
 
             loop {
                 // Check if we should stop
@@ -164,9 +553,46 @@ impl App {
                 }
 
                 // Check timeout
-                if start.elapsed() > max_duration {
-                    warn!("Max recording duration reached");
-                    break;
+                if chunk_start.elapsed() > max_duration {
+                    let Some(commands) = &commands else {
+                        warn!("Max recording duration reached, no longer collecting audio");
+                        break;
+                    };
+
+                    let command = match on_max_duration {
+                        MaxDurationAction::Stop => {
+                            let message = format!(
+                                "Maximum recording length ({}s) reached, transcribing",
+                                max_duration.as_secs()
+                            );
+                            warn!("{}", message);
+                            if let Some(notifier) = &notifier {
+                                notifier.warning(message.clone());
+                            }
+                            let _ = events_tx.send(IpcEvent::Warning { message });
+                            IpcCommand::Stop { output: None }
+                        }
+                        MaxDurationAction::Rollover => {
+                            let message = format!(
+                                "Maximum recording length ({}s) reached, transcribing and still recording",
+                                max_duration.as_secs()
+                            );
+                            info!("{}", message);
+                            if let Some(notifier) = &notifier {
+                                notifier.warning(message.clone());
+                            }
+                            let _ = events_tx.send(IpcEvent::Warning { message });
+                            // The buffer is handed off; its cue is long gone
+                            duck = None;
+                            chunk_start = std::time::Instant::now();
+                            IpcCommand::Rollover
+                        }
+                    };
+
+                    let stop = command != IpcCommand::Rollover;
+                    if commands.send(CommandRequest::new(command)).await.is_err() || stop {
+                        break;
+                    }
                 }
 
                 tokio::select! {
@@ -185,7 +611,58 @@ impl App {
                     ) => {
                         match result {
                             Ok(Some(samples)) => {
-                                buffer.lock().await.extend(samples);
+                                {
+                                    let mut buffer = buffer.lock().await;
+                                    buffer.extend(&samples);
+                                    if duck.as_mut().is_some_and(|duck| duck.apply(&mut buffer)) {
+                                        duck = None;
+                                    }
+                                }
+
+                                // The capture ring overflowed: this collector fell behind
+                                let dropped = rx.dropped();
+                                if dropped > seen_dropped {
+                                    let message = format!(
+                                        "Audio dropped ({} ms lost), the transcript may have gaps",
+                                        (dropped - seen_dropped) * 1000 / sample_rate as u64
+                                    );
+                                    warn!("{}", message);
+                                    dropped_samples.fetch_add(dropped - seen_dropped, Ordering::Relaxed);
+                                    // Notify once per recording
+                                    let first = seen_dropped == 0;
+                                    seen_dropped = dropped;
+                                    if first {
+                                        if let Some(notifier) = &notifier {
+                                            notifier.warning(message.clone());
+                                        }
+                                    }
+                                    let _ = events_tx.send(IpcEvent::Warning { message });
+                                }
+
+                                let level = AudioLevel::measure(&samples);
+                                level_tx.send_replace(level);
+                                let _ = events_tx.send(IpcEvent::Level {
+                                    rms: level.rms,
+                                    peak: level.peak,
+                                });
+
+                                if let Some(peak) = initial_peak.as_mut() {
+                                    *peak = peak.max(level.peak);
+                                    if start.elapsed() >= SILENT_INPUT_WARNING {
+                                        if *peak < silence_threshold {
+                                            let message = format!(
+                                                "No input detected in the first {}s, check the microphone",
+                                                SILENT_INPUT_WARNING.as_secs()
+                                            );
+                                            warn!("{} (peak {:.4})", message, peak);
+                                            if let Some(notifier) = &notifier {
+                                                notifier.warning(message.clone());
+                                            }
+                                            let _ = events_tx.send(IpcEvent::Warning { message });
+                                        }
+                                        initial_peak = None;
+                                    }
+                                }
                             }
                             Ok(None) => {
                                 // Channel closed
@@ -208,8 +685,9 @@ impl App {
         Ok(())
     }
 
-    /// Stop recording and transcribe
-    async fn stop_and_transcribe(&self) -> Result<()> {
+    /// Stop recording and transcribe, writing the result to `output`
+    /// (or the configured output when `None`)
+    async fn stop_and_transcribe(&self, output: Option<OutputMode>) -> Result<()> {
         info!("Stopping recording and transcribing");
 
         // Update state first to signal the audio collection task to stop
@@ -222,6 +700,7 @@ impl App {
 
         // Drop the stream to close the channel sender
         *self.audio_stream.lock().unwrap() = None;
+        self.end_capture();
 
         // Abort the audio collection task - we already have the audio in the buffer
         if let Some(task) = self.audio_task.lock().await.take() {
@@ -230,7 +709,7 @@ impl App {
         }
 
         // Get audio data
-        let samples = self.audio_buffer.lock().await.clone();
+        let samples = std::mem::take(&mut *self.audio_buffer.lock().await);
 
         if samples.is_empty() {
             warn!("No audio recorded");
@@ -238,49 +717,28 @@ impl App {
             return Ok(());
         }
 
-        let audio = AudioData::new(samples, self.config.audio.sample_rate);
-        info!(
-            "Recorded {:.2}s of audio",
-            audio.duration().as_secs_f32()
-        );
-
-        // Transcribe
-        let provider = self.provider.lock().await;
-        let provider = provider
-            .as_ref()
-            .ok_or_else(|| AppError::Other("Provider not initialized".into()))?;
-
-        let language = if self.config.general.language == "auto" {
-            None
-        } else {
-            Some(self.config.general.language.as_str())
-        };
-
-        match provider.transcribe(&audio, language).await {
-            Ok(result) => {
-                info!(
-                    "Transcription: \"{}\" ({:?})",
-                    result.text, result.processing_time
-                );
-
-                if !result.text.is_empty() {
-                    // Paste if enabled
-                    if self.config.general.auto_paste {
-                        if let Err(e) = clipboard::paste_text(&result.text).await {
-                            error!("Failed to paste: {}", e);
-                            // Still copy to clipboard at least
-                            let _ = clipboard::set_clipboard(&result.text).await;
-                        }
-                    } else {
-                        // Just copy to clipboard
-                        let _ = clipboard::set_clipboard(&result.text).await;
-                    }
-                }
-
+        // Waits behind any rollover chunks still being transcribed
+        let dropped = self.dropped_samples.load(Ordering::Relaxed);
+        let (done_tx, done_rx) = oneshot::channel();
+        self.queue_transcription(samples, dropped, output, Some(done_tx));
+        let outcome = done_rx.await.unwrap_or_else(|_| {
+            error!("Transcription queue stopped");
+            Outcome::Failed
+        });
+        match outcome {
+            Outcome::Delivered => {
                 self.set_state(AppState::Idle);
+                self.play_cue(Cue::Success);
             }
-            Err(e) => {
-                error!("Transcription failed: {}", e);
+            Outcome::Empty => {
+                self.set_state(AppState::Idle);
+            }
+            // The recording was discarded, which sounds like a cancel
+            Outcome::Silent => {
+                self.set_state(AppState::Idle);
+                self.play_cue(Cue::Cancelled);
+            }
+            Outcome::Failed => {
                 self.set_state(AppState::Error);
                 // Recover to idle after a moment
                 tokio::time::sleep(std::time::Duration::from_secs(2)).await;
@@ -291,9 +749,46 @@ impl App {
         Ok(())
     }
 
+    /// Transcribe what has been recorded so far and keep recording
+    async fn rollover(&self) -> Result<()> {
+        if self.state() != AppState::Recording {
+            debug!("Ignoring rollover in {:?} state", self.state());
+            return Ok(());
+        }
+
+        let samples = std::mem::take(&mut *self.audio_buffer.lock().await);
+        if samples.is_empty() {
+            return Ok(());
+        }
+
+        info!("Rolling over into a new recording chunk");
+        let dropped = self.dropped_samples.swap(0, Ordering::Relaxed);
+        self.queue_transcription(samples, dropped, None, None);
+        Ok(())
+    }
+
+    /// Queue a recording for transcription; `done` receives the outcome
+    fn queue_transcription(
+        &self,
+        samples: Vec<f32>,
+        dropped: u64,
+        output: Option<OutputMode>,
+        done: Option<oneshot::Sender<Outcome>>,
+    ) {
+        let _ = self.transcriptions.send(Transcription {
+            samples,
+            dropped,
+            output,
+            settings: self.settings(),
+            done,
+        });
+    }
+
     /// Cancel current operation
     async fn cancel(&self) -> Result<()> {
         info!("Cancelling operation");
+        *self.ptt_pressed.lock().unwrap() = None;
+        self.abort_ptt_tail();
 
         // Set state first to signal the audio collection task to stop
         self.set_state(AppState::Idle);
@@ -303,6 +798,7 @@ impl App {
             capture.stop();
         }
         *self.audio_stream.lock().unwrap() = None;
+        self.end_capture();
 
         // Abort the audio collection task
         if let Some(task) = self.audio_task.lock().await.take() {
@@ -316,12 +812,277 @@ impl App {
         Ok(())
     }
 
-    fn set_state(&self, state: AppState) {
-        debug!("State: {:?} -> {:?}", *self.state_tx.borrow(), state);
+    /// Push-to-talk press; key repeat and presses while busy are ignored
+    async fn ptt_down(&self) -> Result<()> {
+        if self.state() != AppState::Idle || self.ptt_pressed.lock().unwrap().is_some() {
+            debug!("Ignoring ptt-down in {:?} state", self.state());
+            return Ok(());
+        }
+
+        self.start_recording().await?;
+        *self.ptt_pressed.lock().unwrap() = Some(std::time::Instant::now());
+        Ok(())
+    }
+
+    /// Push-to-talk release: cancel accidental taps, otherwise capture a short
+    /// tail so the last word is not clipped, then transcribe
+    async fn ptt_up(&self) -> Result<()> {
+        let pressed = match self.ptt_pressed.lock().unwrap().take() {
+            Some(pressed) => pressed,
+            None => {
+                debug!("Ignoring ptt-up without ptt-down");
+                return Ok(());
+            }
+        };
+
+        if self.state() != AppState::Recording {
+            return Ok(());
+        }
+
+        let held = pressed.elapsed();
+        let min_hold = std::time::Duration::from_millis(self.config.hotkey.ptt_min_hold_ms);
+        if held < min_hold {
+            info!("Push-to-talk released after {:?}, cancelling", held);
+            return self.cancel().await;
+        }
+
+        let tail = std::time::Duration::from_millis(self.config.hotkey.ptt_tail_ms);
+        match &self.commands {
+            // Stop through the command loop, so it handles cancel meanwhile
+            Some(commands) if !tail.is_zero() => {
+                let commands = commands.clone();
+                let task = tokio::spawn(async move {
+                    tokio::time::sleep(tail).await;
+                    let stop = IpcCommand::Stop { output: None };
+                    let _ = commands.send(CommandRequest::new(stop)).await;
+                });
+                *self.ptt_tail.lock().unwrap() = Some(task);
+                Ok(())
+            }
+            _ => {
+                tokio::time::sleep(tail).await;
+                // Recording may have hit max_duration meanwhile
+                if self.state() == AppState::Recording {
+                    self.stop_and_transcribe(None).await?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Drop the stop scheduled after a push-to-talk release, if pending
+    fn abort_ptt_tail(&self) {
+        if let Some(task) = self.ptt_tail.lock().unwrap().take() {
+            task.abort();
+        }
+    }
+
+    /// Text of a recent transcript (0 = newest)
+    async fn recent_text(&self, index: usize) -> Result<String> {
+        self.history
+            .lock()
+            .await
+            .get(index)
+            .map(|entry| entry.text.clone())
+            .ok_or_else(|| AppError::Other(format!("No recent transcript #{}", index)))
+    }
+
+    /// Show a desktop notification for a failure (if enabled); `summary`
+    /// says what failed
+    pub fn notify_error(&self, summary: &'static str, message: impl Into<String>) {
+        if let Some(notifier) = &self.notifier {
+            notifier.error(summary, message);
+        }
+    }
+
+    /// Play a feedback cue (if enabled)
+    fn play_cue(&self, cue: Cue) -> Option<Playback> {
+        self.feedback.as_ref().map(|feedback| feedback.play(cue))
+    }
+
+    /// Change state and play its cue; returns the cue's playback
+    fn set_state(&self, state: AppState) -> Option<Playback> {
+        let old = *self.state_tx.borrow();
+        debug!("State: {:?} -> {:?}", old, state);
         let _ = self.state_tx.send(state);
+        let _ = self.events_tx.send(IpcEvent::State {
+            state: format!("{:?}", state).to_lowercase(),
+        });
+
+        if state != AppState::Recording {
+            self.level_tx.send_replace(AudioLevel::default());
+        }
+
+        // Success is played by the caller, only when text was delivered
+        let cue = match (old, state) {
+            (AppState::Idle, AppState::Recording) => Cue::Start,
+            (AppState::Recording, AppState::Processing) => Cue::Stop,
+            (AppState::Recording | AppState::Processing, AppState::Error) => Cue::Error,
+            (AppState::Recording, AppState::Idle) => Cue::Cancelled,
+            _ => return None,
+        };
+        self.play_cue(cue)
     }
 }
 
+impl Transcriber {
+    /// Start transcribing queued recordings
+    fn spawn(self) -> mpsc::UnboundedSender<Transcription> {
+        let (tx, mut rx) = mpsc::unbounded_channel::<Transcription>();
+        tokio::spawn(async move {
+            while let Some(job) = rx.recv().await {
+                let outcome = self
+                    .transcribe(job.samples, job.dropped, job.output, &job.settings)
+                    .await
+                    .unwrap_or_else(|e| {
+                        error!("Transcription failed: {}", e);
+                        self.notify_error("Transcription failed", e.to_string());
+                        Outcome::Failed
+                    });
+                if let Some(done) = job.done {
+                    let _ = done.send(outcome);
+                }
+            }
+        });
+        tx
+    }
+
+    /// Transcribe `samples` and deliver the transcript to `output` (or the
+    /// configured output when `None`)
+    async fn transcribe(
+        &self,
+        mut samples: Vec<f32>,
+        dropped: u64,
+        output: Option<OutputMode>,
+        settings: &RuntimeSettings,
+    ) -> Result<Outcome> {
+        let sample_rate = self.config.audio.sample_rate;
+        let Some(speech) =
+            speech_bounds(&samples, sample_rate, self.config.audio.silence_threshold)
+        else {
+            info!(
+                "No audio above the silence threshold in {:.2}s, skipping transcription",
+                samples.len() as f32 / sample_rate as f32
+            );
+            return Ok(Outcome::Silent);
+        };
+
+        // Whisper hallucinates on silence and cloud providers bill for it
+        let trimmed = samples.len() - speech.len();
+        if trimmed > 0 {
+            info!(
+                "Trimmed {:.2}s of leading and {:.2}s of trailing silence",
+                speech.start as f32 / sample_rate as f32,
+                (samples.len() - speech.end) as f32 / sample_rate as f32
+            );
+            samples.truncate(speech.end);
+            samples.drain(..speech.start);
+        }
+
+        let dsp = DspChain::from_config(&self.config.audio);
+        if dsp.is_enabled() {
+            samples = tokio::task::spawn_blocking(move || {
+                dsp.process(&mut samples, sample_rate);
+                samples
+            })
+            .await
+            .map_err(|e| AppError::Other(e.to_string()))?;
+        }
+
+        let audio = AudioData::new(samples, self.config.audio.sample_rate);
+        info!(
+            "Recorded {:.2}s of audio",
+            audio.duration().as_secs_f32()
+        );
+
+        // Transcribe
+        let provider = self.provider.lock().await;
+        let provider = provider
+            .as_ref()
+            .ok_or_else(|| AppError::Other("Provider not initialized".into()))?;
+
+        let language = if settings.language == "auto" {
+            None
+        } else {
+            Some(settings.language.as_str())
+        };
+
+        if dropped > 0 {
+            warn!(
+                "Recording has gaps: {} samples ({} ms) were dropped",
+                dropped,
+                dropped * 1000 / self.config.audio.sample_rate as u64
+            );
+        }
+
+        match provider.transcribe(&audio, language).await {
+            Ok(result) => {
+                let result = result.with_dropped_samples(dropped);
+                info!(
+                    "Transcription: \"{}\" ({:?})",
+                    result.text, result.processing_time
+                );
+
+                if result.text.is_empty() {
+                    return Ok(Outcome::Empty);
+                }
+
+                let output = output.unwrap_or(self.config.general.output);
+                let mut outcome = Outcome::Delivered;
+                if let Err(e) = self
+                    .output
+                    .output_text(&result.text, output, settings.auto_paste)
+                    .await
+                {
+                    error!("Failed to copy transcript to {}: {}", output, e);
+                    self.notify_error("Output failed", format!("Failed to copy transcript: {}", e));
+                    outcome = Outcome::Failed;
+                }
+
+                if let Some(notifier) = &self.notifier {
+                    notifier.transcript(result.text.clone());
+                }
+
+                let entry = HistoryEntry::new(
+                    result.text.clone(),
+                    provider.name(),
+                    audio.duration().as_millis() as u64,
+                )
+                .with_gaps(result.has_gaps())
+                .with_suspect(result.is_suspect());
+                let mut history = self.history.lock().await;
+                history.push(entry);
+                self.recent_tx.send_replace(history.recent());
+
+                Ok(outcome)
+            }
+            Err(e) => {
+                error!("Transcription failed: {}", e);
+                self.notify_error("Transcription failed", e.to_string());
+                Ok(Outcome::Failed)
+            }
+        }
+    }
+
+    fn notify_error(&self, summary: &'static str, message: impl Into<String>) {
+        if let Some(notifier) = &self.notifier {
+            notifier.error(summary, message);
+        }
+    }
+}
+
+/// Open a file with the desktop's default application
+fn open_path(path: &std::path::Path) -> Result<()> {
+    info!("Opening {:?}", path);
+    tokio::process::Command::new("xdg-open")
+        .arg(path)
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .map_err(|e| AppError::Other(format!("Failed to run xdg-open: {}", e)))?;
+    Ok(())
+}
+
 impl AppState {
     pub fn to_tray_state(self) -> TrayState {
         match self {
@@ -332,3 +1093,267 @@ impl AppState {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::{ClipboardError, SttError};
+    use crate::stt::{SttResult, TranscriptionResult};
+    use async_trait::async_trait;
+    use std::time::Duration;
+    use tokio::sync::Semaphore;
+
+    /// Answers with the audio's length, one recording per permit of `gate`
+    struct GatedProvider {
+        gate: Arc<Semaphore>,
+    }
+
+    #[async_trait]
+    impl SttProvider for GatedProvider {
+        fn name(&self) -> &'static str {
+            "gated"
+        }
+
+        fn is_local(&self) -> bool {
+            true
+        }
+
+        async fn transcribe(
+            &self,
+            audio: &AudioData,
+            _language: Option<&str>,
+        ) -> SttResult<TranscriptionResult> {
+            self.gate.acquire().await.unwrap().forget();
+            Ok(TranscriptionResult::new(format!(
+                "{} ms",
+                audio.duration().as_millis()
+            )))
+        }
+
+        async fn health_check(&self) -> SttResult<()> {
+            Ok(())
+        }
+    }
+
+    /// Fails every recording
+    struct FailingProvider;
+
+    #[async_trait]
+    impl SttProvider for FailingProvider {
+        fn name(&self) -> &'static str {
+            "failing"
+        }
+
+        fn is_local(&self) -> bool {
+            true
+        }
+
+        async fn transcribe(
+            &self,
+            _audio: &AudioData,
+            _language: Option<&str>,
+        ) -> SttResult<TranscriptionResult> {
+            Err(SttError::TranscriptionError("model crashed".into()))
+        }
+
+        async fn health_check(&self) -> SttResult<()> {
+            Ok(())
+        }
+    }
+
+    /// Records delivered transcripts instead of touching the clipboard
+    #[derive(Default)]
+    struct Delivered(std::sync::Mutex<Vec<String>>);
+
+    #[async_trait]
+    impl TextOutput for Delivered {
+        async fn output_text(
+            &self,
+            text: &str,
+            _output: OutputMode,
+            _auto_paste: bool,
+        ) -> std::result::Result<(), ClipboardError> {
+            self.0.lock().unwrap().push(text.to_string());
+            Ok(())
+        }
+    }
+
+    async fn test_app(gate: Arc<Semaphore>, output: Arc<Delivered>) -> App {
+        let mut config = AppConfig::default();
+        config.general.audio_feedback = false;
+        config.general.auto_paste = false;
+        config.notifications.enabled = false;
+        config.history.enabled = false;
+
+        let app = App::with_output(config, output);
+        *app.provider.lock().await = Some(Box::new(GatedProvider { gate }));
+        app
+    }
+
+    /// `ms` of a tone loud enough not to be trimmed as silence
+    fn tone(ms: usize) -> Vec<f32> {
+        (0..ms * 16)
+            .map(|i| 0.5 * (i as f32 * 440.0 * std::f32::consts::TAU / 16000.0).sin())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn rollover_then_stop_delivers_chunks_in_order() {
+        let gate = Arc::new(Semaphore::new(0));
+        let delivered = Arc::new(Delivered::default());
+        let app = test_app(gate.clone(), delivered.clone()).await;
+        app.set_state(AppState::Recording);
+
+        // The first chunk cannot be transcribed yet; rollover must not wait
+        *app.audio_buffer.lock().await = tone(1000);
+        tokio::time::timeout(
+            Duration::from_secs(10),
+            app.handle_command(IpcCommand::Rollover),
+        )
+        .await
+        .expect("rollover waited for the transcription")
+        .unwrap();
+        assert_eq!(app.state(), AppState::Recording);
+        assert!(app.audio_buffer.lock().await.is_empty());
+        assert!(delivered.0.lock().unwrap().is_empty());
+
+        // Stopping queues the second chunk behind the first
+        *app.audio_buffer.lock().await = tone(200);
+        gate.add_permits(2);
+        app.handle_command(IpcCommand::Stop { output: None })
+            .await
+            .unwrap();
+        assert_eq!(app.state(), AppState::Idle);
+
+        assert_eq!(*delivered.0.lock().unwrap(), ["1000 ms", "200 ms"]);
+        let texts: Vec<String> = app
+            .recent_receiver()
+            .borrow()
+            .iter()
+            .rev()
+            .map(|entry| entry.text.clone())
+            .collect();
+        assert_eq!(texts, ["1000 ms", "200 ms"]);
+    }
+
+    #[tokio::test]
+    async fn silent_recordings_are_not_transcribed() {
+        let delivered = Arc::new(Delivered::default());
+        let app = test_app(Arc::new(Semaphore::new(1)), delivered.clone()).await;
+
+        let (done_tx, done_rx) = oneshot::channel();
+        let mut samples = vec![0.0; 16000];
+        samples.extend(vec![0.005; 16000]);
+        app.queue_transcription(samples, 0, None, Some(done_tx));
+
+        let outcome = done_rx.await.unwrap();
+        assert_eq!(outcome, Outcome::Silent);
+        assert!(delivered.0.lock().unwrap().is_empty());
+        assert!(app.recent_receiver().borrow().is_empty());
+    }
+
+    #[tokio::test]
+    async fn failed_transcriptions_recover_to_idle() {
+        // A failing provider, and no provider at all
+        let providers: [Option<Box<dyn SttProvider>>; 2] = [Some(Box::new(FailingProvider)), None];
+        for provider in providers {
+            let delivered = Arc::new(Delivered::default());
+            let app = test_app(Arc::new(Semaphore::new(0)), delivered.clone()).await;
+            *app.provider.lock().await = provider;
+            app.set_state(AppState::Recording);
+            *app.audio_buffer.lock().await = tone(200);
+
+            // The returned guard must not outlive the wait: it blocks `set_state`
+            let mut states = app.state_receiver();
+            let saw_error = async {
+                states
+                    .wait_for(|state| *state == AppState::Error)
+                    .await
+                    .is_ok()
+            };
+            let (result, saw_error) = tokio::join!(
+                app.handle_command(IpcCommand::Stop { output: None }),
+                saw_error,
+            );
+            result.unwrap();
+            assert!(saw_error);
+            assert_eq!(app.state(), AppState::Idle);
+            assert!(delivered.0.lock().unwrap().is_empty());
+        }
+    }
+
+    /// Put `app` in the middle of a push-to-talk recording of `ms`
+    async fn hold_ptt(app: &App, ms: usize) {
+        app.set_state(AppState::Recording);
+        *app.audio_buffer.lock().await = tone(ms);
+        *app.ptt_pressed.lock().unwrap() = Some(std::time::Instant::now() - Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn ptt_ignores_stray_presses_and_releases() {
+        let delivered = Arc::new(Delivered::default());
+        let mut app = test_app(Arc::new(Semaphore::new(1)), delivered.clone()).await;
+        let (tx, mut rx) = mpsc::channel(4);
+        app.set_command_sender(tx);
+
+        // Release before press
+        app.handle_command(IpcCommand::PttUp).await.unwrap();
+        assert_eq!(app.state(), AppState::Idle);
+
+        // A second press (or key repeat) keeps the first press time
+        hold_ptt(&app, 500).await;
+        let pressed = *app.ptt_pressed.lock().unwrap();
+        app.handle_command(IpcCommand::PttDown).await.unwrap();
+        assert_eq!(*app.ptt_pressed.lock().unwrap(), pressed);
+        assert_eq!(app.state(), AppState::Recording);
+
+        // Release after cancel
+        app.handle_command(IpcCommand::Cancel).await.unwrap();
+        app.handle_command(IpcCommand::PttUp).await.unwrap();
+        assert_eq!(app.state(), AppState::Idle);
+        assert!(rx.try_recv().is_err());
+        assert!(delivered.0.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn ptt_release_stops_through_the_command_loop() {
+        let delivered = Arc::new(Delivered::default());
+        let mut app = test_app(Arc::new(Semaphore::new(1)), delivered.clone()).await;
+        app.config.hotkey.ptt_tail_ms = 10;
+        let (tx, mut rx) = mpsc::channel(4);
+        app.set_command_sender(tx);
+        hold_ptt(&app, 500).await;
+
+        // The tail is recorded while the loop stays free for other commands
+        app.handle_command(IpcCommand::PttUp).await.unwrap();
+        assert_eq!(app.state(), AppState::Recording);
+
+        let request = tokio::time::timeout(Duration::from_secs(10), rx.recv())
+            .await
+            .expect("no stop after the tail")
+            .unwrap();
+        assert_eq!(request.command, IpcCommand::Stop { output: None });
+        app.handle_command(request.command).await.unwrap();
+        assert_eq!(app.state(), AppState::Idle);
+        assert_eq!(*delivered.0.lock().unwrap(), ["500 ms"]);
+    }
+
+    #[tokio::test]
+    async fn cancel_during_the_ptt_tail_drops_the_stop() {
+        let delivered = Arc::new(Delivered::default());
+        let mut app = test_app(Arc::new(Semaphore::new(1)), delivered.clone()).await;
+        app.config.hotkey.ptt_tail_ms = 10;
+        let (tx, mut rx) = mpsc::channel(4);
+        app.set_command_sender(tx);
+        hold_ptt(&app, 500).await;
+
+        app.handle_command(IpcCommand::PttUp).await.unwrap();
+        app.handle_command(IpcCommand::Cancel).await.unwrap();
+        assert_eq!(app.state(), AppState::Idle);
+
+        // Well past the tail, the scheduled stop never arrives
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert!(rx.try_recv().is_err());
+        assert!(delivered.0.lock().unwrap().is_empty());
+    }
+}
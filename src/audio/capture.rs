@@ -1,10 +1,15 @@
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
-use cpal::{Device, SampleFormat, Stream, StreamConfig};
+use cpal::{
+    Device, SampleFormat, SampleRate, Stream, StreamConfig, SupportedBufferSize,
+    SupportedStreamConfig,
+};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use tokio::sync::mpsc;
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 
+use crate::audio::device;
+use crate::audio::ring::{audio_ring, AudioReceiver, AudioSender, RING_SECONDS};
+use crate::config::DownmixMode;
 use crate::error::AudioError;
 
 /// Configuration for audio capture
@@ -14,8 +19,14 @@ pub struct CaptureConfig {
     pub sample_rate: u32,
     /// Input device name (None = default)
     pub device_name: Option<String>,
-    /// Channel buffer size
-    pub buffer_size: usize,
+    /// Ordered device preferences used when `device_name` is unset or missing
+    pub preferences: Vec<String>,
+    /// Device buffer size in frames (0 = backend default)
+    pub buffer_size: u32,
+    /// How multi-channel input is reduced to mono
+    pub downmix: DownmixMode,
+    /// Channel kept with `DownmixMode::Channel`
+    pub downmix_channel: u16,
 }
 
 impl Default for CaptureConfig {
@@ -23,7 +34,10 @@ impl Default for CaptureConfig {
         Self {
             sample_rate: 16000,
             device_name: None,
-            buffer_size: 4096,
+            preferences: Vec::new(),
+            buffer_size: 0,
+            downmix: DownmixMode::Average,
+            downmix_channel: 0,
         }
     }
 }
@@ -31,7 +45,11 @@ impl Default for CaptureConfig {
 /// Audio capture manager
 pub struct AudioCapture {
     device: Device,
+    name: String,
     stream_config: StreamConfig,
+    sample_format: SampleFormat,
+    // Channel kept when downmixing; None averages all channels
+    channel: Option<usize>,
     target_sample_rate: u32,
     is_recording: Arc<AtomicBool>,
 }
@@ -42,39 +60,61 @@ impl AudioCapture {
         let host = cpal::default_host();
 
         // Find the input device
-        let device = if let Some(ref name) = config.device_name {
-            host.input_devices()
-                .map_err(|e| AudioError::DeviceError(e.to_string()))?
-                .find(|d| d.name().map(|n| n == *name).unwrap_or(false))
-                .ok_or_else(|| AudioError::DeviceError(format!("Device '{}' not found", name)))?
-        } else {
-            host.default_input_device()
-                .ok_or(AudioError::NoInputDevice)?
-        };
+        let device =
+            device::find_input_device(&host, config.device_name.as_deref(), &config.preferences)?;
+        let name = device.name().unwrap_or_else(|_| "unknown".to_string());
 
-        info!("Using audio input device: {:?}", device.name());
+        info!("Using audio input device: {}", name);
 
-        // Get supported config
-        let supported_config = device
-            .default_input_config()
-            .map_err(|e| AudioError::ConfigError(e.to_string()))?;
+        let supported_config = select_config(&device, config.sample_rate)?;
+        debug!("Selected input config: {:?}", supported_config);
 
-        debug!("Supported config: {:?}", supported_config);
+        let buffer_size = match (config.buffer_size, supported_config.buffer_size()) {
+            (0, _) => cpal::BufferSize::Default,
+            (frames, SupportedBufferSize::Range { min, max }) => {
+                let clamped = frames.clamp(*min, *max);
+                if clamped != frames {
+                    warn!(
+                        "Buffer size {} outside the supported range {}-{}, using {}",
+                        frames, min, max, clamped
+                    );
+                }
+                cpal::BufferSize::Fixed(clamped)
+            }
+            (frames, SupportedBufferSize::Unknown) => cpal::BufferSize::Fixed(frames),
+        };
 
+        // Open at the native channel count; many devices reject mono.
+        // Input is downmixed to mono for whisper in the callback.
         let stream_config = StreamConfig {
-            channels: 1, // Mono for whisper
+            channels: supported_config.channels(),
             sample_rate: supported_config.sample_rate(),
-            buffer_size: cpal::BufferSize::Default,
+            buffer_size,
         };
 
+        let channel = downmix_channel(&config, stream_config.channels);
+
         Ok(Self {
             device,
+            name,
+            sample_format: supported_config.sample_format(),
             stream_config,
+            channel,
             target_sample_rate: config.sample_rate,
             is_recording: Arc::new(AtomicBool::new(false)),
         })
     }
 
+    /// Name of the opened input device
+    pub fn device_name(&self) -> &str {
+        &self.name
+    }
+
+    /// Rate the device is opened at; the stream delivers audio at this rate
+    pub fn device_sample_rate(&self) -> u32 {
+        self.stream_config.sample_rate.0
+    }
+
     /// List available input devices
     pub fn list_devices() -> Result<Vec<String>, AudioError> {
         let host = cpal::default_host();
@@ -87,86 +127,80 @@ impl AudioCapture {
     }
 
     /// Start recording and return a receiver for audio samples
-    pub fn start(&self) -> Result<(Stream, mpsc::Receiver<Vec<f32>>), AudioError> {
-        let (tx, rx) = mpsc::channel::<Vec<f32>>(32);
-        let is_recording = self.is_recording.clone();
-        is_recording.store(true, Ordering::SeqCst);
-
-        let source_sample_rate = self.stream_config.sample_rate.0;
-        let target_sample_rate = self.target_sample_rate;
+    pub fn start(&self) -> Result<(Stream, AudioReceiver), AudioError> {
+        let device_rate = self.device_sample_rate();
+        let (tx, rx) = audio_ring(device_rate as usize * RING_SECONDS);
 
-        let err_fn = |err| error!("Audio stream error: {}", err);
-
-        let stream = match self.device.default_input_config().unwrap().sample_format() {
-            SampleFormat::F32 => self.build_stream::<f32>(
-                tx,
-                is_recording,
-                source_sample_rate,
-                target_sample_rate,
-                err_fn,
-            )?,
-            SampleFormat::I16 => self.build_stream::<i16>(
-                tx,
-                is_recording,
-                source_sample_rate,
-                target_sample_rate,
-                err_fn,
-            )?,
-            SampleFormat::U16 => self.build_stream::<u16>(
-                tx,
-                is_recording,
-                source_sample_rate,
-                target_sample_rate,
-                err_fn,
-            )?,
-            _ => return Err(AudioError::ConfigError("Unsupported sample format".into())),
-        };
+        let stream = self.open_stream(tx, |err| error!("Audio stream error: {}", err))?;
 
         stream
             .play()
             .map_err(|e| AudioError::StreamError(e.to_string()))?;
 
         info!("Audio recording started");
-        Ok((stream, rx))
+        Ok((stream, rx.resampled(device_rate, self.target_sample_rate)))
+    }
+
+    /// Build an input stream that pushes mono audio at the device rate into
+    /// `sender`; the receiving end resamples it. The stream is not started;
+    /// call `play()` on it.
+    pub fn open_stream(
+        &self,
+        sender: AudioSender,
+        on_error: impl Fn(cpal::StreamError) + Send + 'static,
+    ) -> Result<Stream, AudioError> {
+        let is_recording = self.is_recording.clone();
+        is_recording.store(true, Ordering::SeqCst);
+
+        macro_rules! build {
+            ($t:ty) => {
+                self.build_stream::<$t>(sender, is_recording, on_error)?
+            };
+        }
+
+        let stream = match self.sample_format {
+            SampleFormat::F32 => build!(f32),
+            SampleFormat::F64 => build!(f64),
+            SampleFormat::I8 => build!(i8),
+            SampleFormat::I16 => build!(i16),
+            SampleFormat::I32 => build!(i32),
+            SampleFormat::I64 => build!(i64),
+            SampleFormat::U8 => build!(u8),
+            SampleFormat::U16 => build!(u16),
+            SampleFormat::U32 => build!(u32),
+            SampleFormat::U64 => build!(u64),
+            format => {
+                return Err(AudioError::ConfigError(format!(
+                    "Unsupported sample format {}",
+                    format
+                )))
+            }
+        };
+
+        Ok(stream)
     }
 
     fn build_stream<T>(
         &self,
-        tx: mpsc::Sender<Vec<f32>>,
+        sender: AudioSender,
         is_recording: Arc<AtomicBool>,
-        source_rate: u32,
-        target_rate: u32,
         err_fn: impl Fn(cpal::StreamError) + Send + 'static,
     ) -> Result<Stream, AudioError>
     where
         T: cpal::Sample + cpal::SizedSample + Send + 'static,
         f32: cpal::FromSample<T>,
     {
+        let mut on_data = capture_callback::<T>(
+            sender,
+            is_recording,
+            self.stream_config.channels as usize,
+            self.channel,
+        );
         let stream = self
             .device
             .build_input_stream(
                 &self.stream_config,
-                move |data: &[T], _: &cpal::InputCallbackInfo| {
-                    if !is_recording.load(Ordering::SeqCst) {
-                        return;
-                    }
-
-                    // Convert to f32
-                    let samples: Vec<f32> = data
-                        .iter()
-                        .map(|s| cpal::Sample::from_sample(*s))
-                        .collect();
-
-                    // Resample if needed
-                    let samples = if source_rate != target_rate {
-                        resample(&samples, source_rate, target_rate)
-                    } else {
-                        samples
-                    };
-
-                    // Send samples (non-blocking)
-                    let _ = tx.try_send(samples);
-                },
+                move |data: &[T], _: &cpal::InputCallbackInfo| on_data(data),
                 err_fn,
                 None,
             )
@@ -187,9 +221,107 @@ impl AudioCapture {
     }
 }
 
+/// Channel to keep when downmixing, or None to average all channels
+fn downmix_channel(config: &CaptureConfig, channels: u16) -> Option<usize> {
+    match config.downmix {
+        DownmixMode::Average => None,
+        DownmixMode::Channel if config.downmix_channel < channels => {
+            Some(config.downmix_channel as usize)
+        }
+        DownmixMode::Channel => {
+            warn!(
+                "Input device has {} channel(s), cannot record channel {}; averaging instead",
+                channels, config.downmix_channel
+            );
+            None
+        }
+    }
+}
+
+/// Body of the stream's data callback, which runs on the real-time thread:
+/// it only converts samples and writes the ring
+fn capture_callback<T>(
+    mut sender: AudioSender,
+    is_recording: Arc<AtomicBool>,
+    channels: usize,
+    channel: Option<usize>,
+) -> impl FnMut(&[T]) + Send + 'static
+where
+    T: cpal::Sample + Send + 'static,
+    f32: cpal::FromSample<T>,
+{
+    move |data| {
+        if is_recording.load(Ordering::SeqCst) {
+            sender.push_iter(downmix(data, channels, channel));
+        }
+    }
+}
+
+/// Pick the input config closest to `target_rate`.
+///
+/// A config that supports the target rate directly avoids resampling; among
+/// equals the device's default channel count and sample format win. Falls back
+/// to the default config when the device cannot enumerate its configs.
+fn select_config(device: &Device, target_rate: u32) -> Result<SupportedStreamConfig, AudioError> {
+    let default = device
+        .default_input_config()
+        .map_err(|e| AudioError::ConfigError(e.to_string()))?;
+
+    let ranges = match device.supported_input_configs() {
+        Ok(ranges) => ranges,
+        Err(e) => {
+            debug!("Cannot enumerate input configs ({}), using the default", e);
+            return Ok(default);
+        }
+    };
+
+    let best = ranges
+        .map(|range| {
+            let rate = target_rate.clamp(range.min_sample_rate().0, range.max_sample_rate().0);
+            range.with_sample_rate(SampleRate(rate))
+        })
+        // Prefer rates at or above the target (no lost bandwidth), then the
+        // closest one, then the default layout
+        .min_by_key(|config| {
+            let rate = config.sample_rate().0;
+            (
+                rate < target_rate,
+                rate.abs_diff(target_rate),
+                config.channels() != default.channels(),
+                config.sample_format() != default.sample_format(),
+            )
+        });
+
+    Ok(best.unwrap_or(default))
+}
+
+/// Convert interleaved samples to f32 and reduce them to mono, either by
+/// averaging each frame or by keeping one channel. Does not allocate.
+pub(crate) fn downmix<T>(
+    data: &[T],
+    channels: usize,
+    channel: Option<usize>,
+) -> impl Iterator<Item = f32> + '_
+where
+    T: cpal::Sample,
+    f32: cpal::FromSample<T>,
+{
+    let channels = channels.max(1);
+    data.chunks_exact(channels).map(move |frame| match channel {
+        Some(channel) => cpal::Sample::from_sample(frame[channel]),
+        None => {
+            frame
+                .iter()
+                .map(|&s| <f32 as cpal::Sample>::from_sample(s))
+                .sum::<f32>()
+                / channels as f32
+        }
+    })
+}
+
 /// Simple linear interpolation resampling
 /// For better quality, use rubato crate
-fn resample(samples: &[f32], source_rate: u32, target_rate: u32) -> Vec<f32> {
+pub(crate) fn resample(samples: &[f32], source_rate: u32, target_rate: u32) -> Vec<f32> {
     if source_rate == target_rate {
         return samples.to_vec();
     }
@@ -216,3 +348,172 @@ fn resample(samples: &[f32], source_rate: u32, target_rate: u32) -> Vec<f32> {
 
     output
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cpal::FromSample;
+
+    /// Interleave per-channel signals into frames
+    fn interleave(signals: &[Vec<f32>]) -> Vec<f32> {
+        (0..signals[0].len())
+            .flat_map(|i| signals.iter().map(move |signal| signal[i]))
+            .collect()
+    }
+
+    /// A distinct level per channel, changing every frame
+    fn channel_signals(channels: usize, frames: usize) -> Vec<Vec<f32>> {
+        (0..channels)
+            .map(|c| {
+                (0..frames)
+                    .map(|i| (c as f32 + 1.0) / 8.0 * if i % 2 == 0 { 1.0 } else { -0.5 })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn assert_close(what: &str, actual: &[f32], expected: &[f32], tolerance: f32) {
+        assert_eq!(actual.len(), expected.len(), "{}", what);
+        for (i, (a, e)) in actual.iter().zip(expected).enumerate() {
+            assert!(
+                (a - e).abs() <= tolerance,
+                "{} sample {}: {} != {}",
+                what,
+                i,
+                a,
+                e
+            );
+        }
+    }
+
+    /// Run interleaved f32 frames, converted to `T`, through the capture
+    /// callback and return what reaches the ring
+    fn capture<T>(data: &[f32], channels: usize, channel: Option<usize>) -> Vec<f32>
+    where
+        T: cpal::Sample + FromSample<f32> + Send + 'static,
+        f32: FromSample<T>,
+    {
+        let (tx, mut rx) = audio_ring(data.len().max(1));
+        let is_recording = Arc::new(AtomicBool::new(true));
+        let mut on_data = capture_callback::<T>(tx, is_recording, channels, channel);
+        let converted: Vec<T> = data.iter().map(|&s| T::from_sample_(s)).collect();
+        on_data(&converted);
+        rx.try_recv().unwrap_or_default()
+    }
+
+    #[test]
+    fn averages_interleaved_channels() {
+        for channels in [2, 4, 6] {
+            let signals = channel_signals(channels, 32);
+            let expected: Vec<f32> = (0..32)
+                .map(|i| signals.iter().map(|s| s[i]).sum::<f32>() / channels as f32)
+                .collect();
+            let mono: Vec<f32> = downmix(&interleave(&signals), channels, None).collect();
+            assert_close(&format!("{} channels", channels), &mono, &expected, 1e-6);
+        }
+    }
+
+    #[test]
+    fn keeps_one_channel() {
+        for channels in [2, 4, 6] {
+            let signals = channel_signals(channels, 32);
+            let data = interleave(&signals);
+            for channel in 0..channels {
+                let mono: Vec<f32> = downmix(&data, channels, Some(channel)).collect();
+                assert_eq!(
+                    mono, signals[channel],
+                    "{} of {} channels",
+                    channel, channels
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn passes_mono_through() {
+        let data = [0.1, -0.2, 0.3];
+        assert_eq!(downmix(&data, 1, None).collect::<Vec<_>>(), data);
+        // A zero channel count is treated as mono
+        assert_eq!(downmix(&data, 0, None).collect::<Vec<_>>(), data);
+    }
+
+    #[test]
+    fn drops_incomplete_frames() {
+        let data = [0.5, 0.5, 0.5, 0.5, 0.25];
+        assert_eq!(downmix(&data, 2, None).collect::<Vec<_>>(), [0.5, 0.5]);
+    }
+
+    #[test]
+    fn converts_every_sample_format() {
+        let signals = channel_signals(4, 16);
+        let data = interleave(&signals);
+        let average: Vec<f32> = downmix(&data, 4, None).collect();
+
+        // Same order as the formats `open_stream` supports
+        let formats: [(&str, fn(&[f32], usize, Option<usize>) -> Vec<f32>, f32); 10] = [
+            ("f32", capture::<f32>, 0.0),
+            ("f64", capture::<f64>, 1e-6),
+            ("i8", capture::<i8>, 1.0 / 64.0),
+            ("i16", capture::<i16>, 1e-4),
+            ("i32", capture::<i32>, 1e-6),
+            ("i64", capture::<i64>, 1e-6),
+            ("u8", capture::<u8>, 1.0 / 64.0),
+            ("u16", capture::<u16>, 1e-4),
+            ("u32", capture::<u32>, 1e-6),
+            ("u64", capture::<u64>, 1e-6),
+        ];
+        for (name, capture, tolerance) in formats {
+            assert_close(name, &capture(&data, 4, None), &average, tolerance);
+            assert_close(name, &capture(&data, 4, Some(2)), &signals[2], tolerance);
+        }
+    }
+
+    #[test]
+    fn maps_integer_ranges_to_unit_range() {
+        assert_eq!(
+            downmix(&[i16::MIN, 0, i16::MAX], 1, None).collect::<Vec<_>>(),
+            [-1.0, 0.0, i16::MAX as f32 / 32768.0]
+        );
+        assert_eq!(
+            downmix(&[0u8, 128, 255], 1, None).collect::<Vec<_>>(),
+            [-1.0, 0.0, 127.0 / 128.0]
+        );
+        assert_eq!(
+            downmix(&[0u16, 32768], 1, None).collect::<Vec<_>>(),
+            [-1.0, 0.0]
+        );
+    }
+
+    #[test]
+    fn callback_only_records_while_recording() {
+        let (tx, mut rx) = audio_ring(64);
+        let is_recording = Arc::new(AtomicBool::new(false));
+        let mut on_data = capture_callback::<i16>(tx, is_recording.clone(), 2, None);
+
+        on_data(&[i16::MAX, i16::MAX, 0, 0]);
+        assert_eq!(rx.try_recv(), None);
+
+        is_recording.store(true, Ordering::SeqCst);
+        on_data(&[16384, 0, -16384, -16384]);
+        assert_eq!(rx.try_recv(), Some(vec![0.25, -0.5]));
+    }
+
+    #[test]
+    fn falls_back_to_averaging_missing_channel() {
+        let config = |downmix, downmix_channel| CaptureConfig {
+            downmix,
+            downmix_channel,
+            ..Default::default()
+        };
+        assert_eq!(downmix_channel(&config(DownmixMode::Average, 1), 2), None);
+        assert_eq!(
+            downmix_channel(&config(DownmixMode::Channel, 1), 2),
+            Some(1)
+        );
+        assert_eq!(
+            downmix_channel(&config(DownmixMode::Channel, 5), 6),
+            Some(5)
+        );
+        assert_eq!(downmix_channel(&config(DownmixMode::Channel, 2), 2), None);
+    }
+}
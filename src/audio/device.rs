@@ -0,0 +1,201 @@
+use cpal::traits::{DeviceTrait, HostTrait};
+use cpal::{Device, Host, SupportedStreamConfigRange};
+use regex::Regex;
+use tracing::{debug, warn};
+
+use crate::error::AudioError;
+
+/// One entry of `audio.input_devices`: a case-insensitive substring, or a
+/// regular expression written as `/.../`
+#[derive(Debug, Clone)]
+pub enum DevicePattern {
+    Substring(String),
+    Regex(Regex),
+}
+
+impl DevicePattern {
+    pub fn parse(pattern: &str) -> Result<Self, AudioError> {
+        match pattern
+            .strip_prefix('/')
+            .and_then(|p| p.strip_suffix('/'))
+        {
+            Some(re) => Regex::new(re)
+                .map(DevicePattern::Regex)
+                .map_err(|e| AudioError::ConfigError(format!("Invalid device regex {}: {}", pattern, e))),
+            None => Ok(DevicePattern::Substring(pattern.to_lowercase())),
+        }
+    }
+
+    pub fn matches(&self, name: &str) -> bool {
+        match self {
+            DevicePattern::Substring(s) => name.to_lowercase().contains(s),
+            DevicePattern::Regex(re) => re.is_match(name),
+        }
+    }
+}
+
+/// Pick the input device: the explicitly selected one (exact name) if it is
+/// present, then the first available match of the preference list, then the
+/// system default
+pub fn find_input_device(
+    host: &Host,
+    selected: Option<&str>,
+    preferences: &[String],
+) -> Result<Device, AudioError> {
+    let (names, devices): (Vec<String>, Vec<Device>) = host
+        .input_devices()
+        .map_err(|e| AudioError::DeviceError(e.to_string()))?
+        .filter_map(|d| d.name().ok().map(|name| (name, d)))
+        .unzip();
+
+    match choose_device(&names, selected, preferences) {
+        Some(index) => Ok(devices[index].clone()),
+        None => host.default_input_device().ok_or(AudioError::NoInputDevice),
+    }
+}
+
+/// Names of the input devices present, and the name of the one
+/// `find_input_device` would choose among them, from a single enumeration
+pub fn scan_input_devices(
+    selected: Option<&str>,
+    preferences: &[String],
+) -> Result<(Vec<String>, Option<String>), AudioError> {
+    let host = cpal::default_host();
+    let names: Vec<String> = host
+        .input_devices()
+        .map_err(|e| AudioError::DeviceError(e.to_string()))?
+        .filter_map(|d| d.name().ok())
+        .collect();
+
+    let chosen = match choose_device(&names, selected, preferences) {
+        Some(index) => Some(names[index].clone()),
+        None => host.default_input_device().and_then(|d| d.name().ok()),
+    };
+    Ok((names, chosen))
+}
+
+/// Index in `names` of the selected device or the first preference match;
+/// `None` means the system default
+fn choose_device(
+    names: &[String],
+    selected: Option<&str>,
+    preferences: &[String],
+) -> Option<usize> {
+    if let Some(selected) = selected {
+        if let Some(index) = names.iter().position(|name| name == selected) {
+            return Some(index);
+        }
+        debug!("Input device '{}' not available, falling back", selected);
+    }
+
+    for pattern in preferences {
+        // Rejected when the config is loaded; skipped here just in case
+        let matcher = match DevicePattern::parse(pattern) {
+            Ok(matcher) => matcher,
+            Err(e) => {
+                warn!("{}", e);
+                continue;
+            }
+        };
+        if let Some(index) = names.iter().position(|name| matcher.matches(name)) {
+            debug!(
+                "Input device '{}' matches preference '{}'",
+                names[index], pattern
+            );
+            return Some(index);
+        }
+        debug!("No input device matches preference '{}'", pattern);
+    }
+
+    None
+}
+
+/// One supported input configuration range of a device
+#[derive(Debug, Clone)]
+pub struct InputConfigRange {
+    pub channels: u16,
+    pub min_sample_rate: u32,
+    pub max_sample_rate: u32,
+    pub sample_format: String,
+}
+
+impl From<&SupportedStreamConfigRange> for InputConfigRange {
+    fn from(range: &SupportedStreamConfigRange) -> Self {
+        Self {
+            channels: range.channels(),
+            min_sample_rate: range.min_sample_rate().0,
+            max_sample_rate: range.max_sample_rate().0,
+            sample_format: range.sample_format().to_string(),
+        }
+    }
+}
+
+/// Capabilities of an input device, for `super-whisper devices`
+#[derive(Debug, Clone)]
+pub struct InputDeviceInfo {
+    pub name: String,
+    pub is_default: bool,
+    /// Default config as (channels, sample rate, sample format)
+    pub default_config: Option<(u16, u32, String)>,
+    pub configs: Vec<InputConfigRange>,
+}
+
+/// Describe every input device of the default host
+pub fn describe_input_devices() -> Result<Vec<InputDeviceInfo>, AudioError> {
+    let host = cpal::default_host();
+    let default_name = host.default_input_device().and_then(|d| d.name().ok());
+
+    let devices = host
+        .input_devices()
+        .map_err(|e| AudioError::DeviceError(e.to_string()))?
+        .filter_map(|device| {
+            let name = device.name().ok()?;
+            let default_config = device.default_input_config().ok().map(|c| {
+                (c.channels(), c.sample_rate().0, c.sample_format().to_string())
+            });
+            // Some backends fail to enumerate configs for busy devices
+            let configs = match device.supported_input_configs() {
+                Ok(configs) => configs.map(|c| InputConfigRange::from(&c)).collect(),
+                Err(e) => {
+                    debug!("Cannot query configs of '{}': {}", name, e);
+                    Vec::new()
+                }
+            };
+            Some(InputDeviceInfo {
+                is_default: default_name.as_deref() == Some(name.as_str()),
+                name,
+                default_config,
+                configs,
+            })
+        })
+        .collect();
+
+    Ok(devices)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names() -> Vec<String> {
+        ["Built-in Audio", "Jabra Link 380", "USB Headset Mic"]
+            .map(String::from)
+            .to_vec()
+    }
+
+    #[test]
+    fn chooses_selected_then_preferences_then_default() {
+        let preferences = ["jabra".to_string(), "/USB.*Mic/".to_string()];
+
+        assert_eq!(
+            choose_device(&names(), Some("USB Headset Mic"), &preferences),
+            Some(2)
+        );
+        // A missing selection falls back to the preferences, in order
+        assert_eq!(choose_device(&names(), Some("Gone"), &preferences), Some(1));
+        assert_eq!(choose_device(&names()[..1], None, &preferences), None);
+        assert_eq!(choose_device(&names(), None, &preferences[1..]), Some(2));
+        // Invalid patterns are skipped
+        assert_eq!(choose_device(&names(), None, &["/(/".to_string()]), None);
+    }
+}
@@ -0,0 +1,429 @@
+use realfft::num_complex::Complex;
+use realfft::RealFftPlanner;
+use std::f32::consts::PI;
+use tracing::debug;
+
+use crate::config::AudioConfig;
+
+/// STFT frame length for noise suppression (32 ms at 16 kHz)
+const FFT_SIZE: usize = 512;
+const HOP_SIZE: usize = FFT_SIZE / 2;
+
+/// Share of the quietest frames used as the noise profile
+const NOISE_PERCENTILE: f32 = 0.1;
+
+/// Fewest frames a noise profile is built from. Shorter recordings are left
+/// alone: their quietest frames may well be speech.
+const MIN_NOISE_FRAMES: usize = 4;
+
+/// Noise estimate is scaled by this before subtraction, to also catch its peaks
+const OVER_SUBTRACTION: f32 = 1.5;
+
+/// Per-frame decay limit of a bin's gain
+const RELEASE: f32 = 0.5;
+
+/// Lowest gain a bin is attenuated to (-20 dB); a full gate sounds "musical"
+const GAIN_FLOOR: f32 = 0.1;
+
+/// Optional clean-up applied to a recording before transcription:
+/// high-pass filter, spectral noise suppression, then peak normalisation
+#[derive(Debug, Clone)]
+pub struct DspChain {
+    highpass_hz: Option<f32>,
+    noise_suppression: bool,
+    normalize: Option<Normalize>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Normalize {
+    peak_db: f32,
+    max_gain_db: f32,
+}
+
+impl DspChain {
+    pub fn from_config(config: &AudioConfig) -> Self {
+        Self {
+            highpass_hz: config.highpass.then_some(config.highpass_hz),
+            noise_suppression: config.noise_suppression,
+            normalize: config.normalize.then_some(Normalize {
+                peak_db: config.normalize_peak_db,
+                max_gain_db: config.max_gain_db,
+            }),
+        }
+    }
+
+    /// Whether any stage is enabled
+    pub fn is_enabled(&self) -> bool {
+        self.highpass_hz.is_some() || self.noise_suppression || self.normalize.is_some()
+    }
+
+    /// Run the enabled stages over `samples` in place
+    pub fn process(&self, samples: &mut Vec<f32>, sample_rate: u32) {
+        if let Some(cutoff) = self.highpass_hz {
+            highpass(samples, sample_rate, cutoff);
+        }
+        if self.noise_suppression {
+            *samples = suppress_noise(samples);
+        }
+        if let Some(normalize) = self.normalize {
+            normalize_peak(samples, normalize.peak_db, normalize.max_gain_db);
+        }
+    }
+}
+
+/// Second-order Butterworth high-pass filter (removes rumble and DC offset)
+pub fn highpass(samples: &mut [f32], sample_rate: u32, cutoff_hz: f32) {
+    let nyquist = sample_rate as f32 / 2.0;
+    if cutoff_hz <= 0.0 || cutoff_hz >= nyquist {
+        return;
+    }
+
+    // RBJ audio EQ cookbook coefficients, Q = 1/sqrt(2)
+    let w0 = 2.0 * PI * cutoff_hz / sample_rate as f32;
+    let alpha = w0.sin() / std::f32::consts::SQRT_2;
+    let cos = w0.cos();
+    let a0 = 1.0 + alpha;
+    let b0 = (1.0 + cos) / 2.0 / a0;
+    let b1 = -(1.0 + cos) / a0;
+    let b2 = b0;
+    let a1 = -2.0 * cos / a0;
+    let a2 = (1.0 - alpha) / a0;
+
+    let (mut x1, mut x2, mut y1, mut y2) = (0.0, 0.0, 0.0, 0.0);
+    for sample in samples.iter_mut() {
+        let x = *sample;
+        let y = b0 * x + b1 * x1 + b2 * x2 - a1 * y1 - a2 * y2;
+        x2 = x1;
+        x1 = x;
+        y2 = y1;
+        y1 = y;
+        *sample = y;
+    }
+}
+
+/// Spectral noise suppression.
+///
+/// The noise spectrum is estimated from the quietest frames of the recording
+/// (pauses between words), then each STFT bin is attenuated by how much of
+/// its magnitude that noise explains.
+pub fn suppress_noise(samples: &[f32]) -> Vec<f32> {
+    // Pad so every sample is covered by two frames
+    let mut padded = vec![0.0; HOP_SIZE];
+    padded.extend_from_slice(samples);
+    padded.resize(padded.len() + FFT_SIZE, 0.0);
+
+    let frames = (padded.len() - FFT_SIZE) / HOP_SIZE + 1;
+    if noise_frames(frames) < MIN_NOISE_FRAMES {
+        debug!(
+            "Recording too short to estimate noise ({} frames), not suppressing it",
+            frames
+        );
+        return samples.to_vec();
+    }
+
+    let mut planner = RealFftPlanner::<f32>::new();
+    let forward = planner.plan_fft_forward(FFT_SIZE);
+    let inverse = planner.plan_fft_inverse(FFT_SIZE);
+
+    // sqrt-Hann for analysis and synthesis sums to one at 50% overlap
+    let window: Vec<f32> = (0..FFT_SIZE)
+        .map(|i| (0.5 - 0.5 * (2.0 * PI * i as f32 / FFT_SIZE as f32).cos()).sqrt())
+        .collect();
+
+    let mut input = forward.make_input_vec();
+    let mut spectra: Vec<Vec<Complex<f32>>> = padded
+        .windows(FFT_SIZE)
+        .step_by(HOP_SIZE)
+        .map(|frame| {
+            for ((slot, &x), &w) in input.iter_mut().zip(frame).zip(&window) {
+                *slot = x * w;
+            }
+            let mut spectrum = forward.make_output_vec();
+            // Buffer sizes come from the plan, so this cannot fail
+            let _ = forward.process(&mut input, &mut spectrum);
+            spectrum
+        })
+        .collect();
+
+    let noise = noise_profile(&spectra);
+
+    let bins = FFT_SIZE / 2 + 1;
+    let mut previous = vec![1.0f32; bins];
+    for spectrum in spectra.iter_mut() {
+        for (bin, value) in spectrum.iter_mut().enumerate() {
+            let magnitude = value.norm();
+            let gain = if magnitude > 0.0 {
+                ((magnitude - OVER_SUBTRACTION * noise[bin]) / magnitude).clamp(GAIN_FLOOR, 1.0)
+            } else {
+                1.0
+            };
+            // Open instantly but close gradually, so word onsets stay intact
+            // and gains do not flicker between frames
+            let gain = gain.max(RELEASE * previous[bin]);
+            previous[bin] = gain;
+            *value *= gain;
+        }
+    }
+
+    // Overlap-add
+    let mut output = vec![0.0; padded.len()];
+    let mut frame = inverse.make_output_vec();
+    for (i, spectrum) in spectra.iter_mut().enumerate() {
+        spectrum[0].im = 0.0;
+        spectrum[bins - 1].im = 0.0;
+        let _ = inverse.process(spectrum, &mut frame);
+        let start = i * HOP_SIZE;
+        for (j, (&x, &w)) in frame.iter().zip(&window).enumerate() {
+            output[start + j] += x * w / FFT_SIZE as f32;
+        }
+    }
+
+    output[HOP_SIZE..HOP_SIZE + samples.len()].to_vec()
+}
+
+/// Mean magnitude per bin over the quietest frames
+fn noise_profile(spectra: &[Vec<Complex<f32>>]) -> Vec<f32> {
+    let energy = |spectrum: &Vec<Complex<f32>>| spectrum.iter().map(|c| c.norm_sqr()).sum::<f32>();
+    let mut order: Vec<usize> = (0..spectra.len()).collect();
+    order.sort_by(|&a, &b| energy(&spectra[a]).total_cmp(&energy(&spectra[b])));
+
+    let count = noise_frames(spectra.len()).max(1);
+    let bins = FFT_SIZE / 2 + 1;
+    let mut noise = vec![0.0; bins];
+    for &frame in &order[..count] {
+        for (bin, value) in spectra[frame].iter().enumerate() {
+            noise[bin] += value.norm() / count as f32;
+        }
+    }
+    noise
+}
+
+/// Number of frames, out of `frames`, the noise profile averages
+fn noise_frames(frames: usize) -> usize {
+    (frames as f32 * NOISE_PERCENTILE) as usize
+}
+
+/// Scale so the peak reaches `peak_db` dBFS, amplifying by at most
+/// `max_gain_db` (quiet recordings are mostly noise past that point)
+pub fn normalize_peak(samples: &mut [f32], peak_db: f32, max_gain_db: f32) {
+    let peak = samples.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+    if peak == 0.0 {
+        return;
+    }
+
+    let target = 10f32.powf(peak_db / 20.0);
+    let gain = (target / peak).min(10f32.powf(max_gain_db / 20.0));
+    debug!("Normalizing by {:+.1} dB", 20.0 * gain.log10());
+    for sample in samples.iter_mut() {
+        *sample *= gain;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: u32 = 16000;
+
+    fn sine(freq: f32, amplitude: f32, len: usize) -> Vec<f32> {
+        (0..len)
+            .map(|i| amplitude * (2.0 * PI * freq * i as f32 / RATE as f32).sin())
+            .collect()
+    }
+
+    /// Deterministic white noise in [-amplitude, amplitude]
+    fn noise(amplitude: f32, len: usize) -> Vec<f32> {
+        let mut state = 0x2545_f491u32;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                amplitude * ((state >> 8) as f32 / (1u32 << 24) as f32 * 2.0 - 1.0)
+            })
+            .collect()
+    }
+
+    fn rms(samples: &[f32]) -> f32 {
+        (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt()
+    }
+
+    /// Amplitude of the `freq` component (single-bin DFT)
+    fn tone_level(samples: &[f32], freq: f32) -> f32 {
+        let (re, im) = samples
+            .iter()
+            .enumerate()
+            .fold((0.0, 0.0), |(re, im), (i, &s)| {
+                let phase = 2.0 * PI * freq * i as f32 / RATE as f32;
+                (re + s * phase.cos(), im + s * phase.sin())
+            });
+        2.0 * (re * re + im * im).sqrt() / samples.len() as f32
+    }
+
+    /// Gain of the high-pass at `freq`, measured after it settles
+    fn highpass_gain(freq: f32, cutoff: f32) -> f32 {
+        let mut samples = sine(freq, 0.5, RATE as usize * 2);
+        highpass(&mut samples, RATE, cutoff);
+        let settled = &samples[RATE as usize..];
+        rms(settled) / rms(&sine(freq, 0.5, settled.len()))
+    }
+
+    #[test]
+    fn highpass_attenuates_mains_hum() {
+        // Second order: (80 / 50)^2 ≈ 2.6x down at 50 Hz
+        assert!(highpass_gain(50.0, 80.0) < 0.4);
+        assert!(highpass_gain(20.0, 80.0) < 0.07);
+        // Speech band passes
+        let gain = highpass_gain(1000.0, 80.0);
+        assert!((gain - 1.0).abs() < 0.01, "1 kHz gain {}", gain);
+        // -3 dB at the cutoff
+        let gain = highpass_gain(80.0, 80.0);
+        assert!(
+            (gain - std::f32::consts::FRAC_1_SQRT_2).abs() < 0.02,
+            "cutoff gain {}",
+            gain
+        );
+    }
+
+    #[test]
+    fn highpass_removes_dc_offset() {
+        let mut samples: Vec<f32> = sine(440.0, 0.2, RATE as usize)
+            .into_iter()
+            .map(|s| s + 0.3)
+            .collect();
+        highpass(&mut samples, RATE, 80.0);
+        let tail = &samples[RATE as usize / 2..];
+        let mean = tail.iter().sum::<f32>() / tail.len() as f32;
+        assert!(mean.abs() < 1e-3, "mean {}", mean);
+    }
+
+    #[test]
+    fn highpass_ignores_invalid_cutoffs() {
+        let original = sine(50.0, 0.5, 1000);
+        for cutoff in [0.0, -10.0, 8000.0, 12000.0] {
+            let mut samples = original.clone();
+            highpass(&mut samples, RATE, cutoff);
+            assert_eq!(samples, original);
+        }
+    }
+
+    #[test]
+    fn suppression_lowers_noise_floor_and_keeps_tone() {
+        // Three 0.5 s "words" separated by 0.5 s pauses, over steady hiss
+        let word = RATE as usize / 2;
+        let mut clean = Vec::new();
+        for _ in 0..3 {
+            clean.extend(vec![0.0; word]);
+            clean.extend(sine(440.0, 0.3, word));
+        }
+        clean.extend(vec![0.0; word]);
+        let hiss = noise(0.02, clean.len());
+        let noisy: Vec<f32> = clean.iter().zip(&hiss).map(|(c, n)| c + n).collect();
+
+        let output = suppress_noise(&noisy);
+        assert_eq!(output.len(), noisy.len());
+
+        // Middle of each pause, clear of the words' edges
+        let pause = |samples: &[f32], n: usize| {
+            let start = 2 * n * word + word / 4;
+            samples[start..start + word / 2].to_vec()
+        };
+        for n in 0..4 {
+            let before = rms(&pause(&noisy, n));
+            let after = rms(&pause(&output, n));
+            assert!(after < before / 4.0, "pause {}: {} -> {}", n, before, after);
+        }
+
+        for n in 0..3 {
+            let start = (2 * n + 1) * word + word / 4;
+            let range = start..start + word / 2;
+            let level = tone_level(&output[range.clone()], 440.0);
+            assert!(
+                (level - 0.3).abs() < 0.03,
+                "word {}: tone level {}",
+                n,
+                level
+            );
+            // What is left besides the tone is quieter than the hiss was
+            let residual: Vec<f32> = output[range.clone()]
+                .iter()
+                .zip(&clean[range])
+                .map(|(o, c)| o - c)
+                .collect();
+            assert!(rms(&residual) < rms(&hiss), "word {}", n);
+        }
+    }
+
+    #[test]
+    fn suppression_skips_short_recordings() {
+        // Too few frames for a noise profile of their own
+        let short: Vec<f32> = sine(440.0, 0.3, RATE as usize / 4)
+            .iter()
+            .zip(noise(0.02, RATE as usize / 4))
+            .map(|(s, n)| s + n)
+            .collect();
+        assert_eq!(suppress_noise(&short), short);
+        assert!(suppress_noise(&[]).is_empty());
+
+        let frames = |len: usize| (len + HOP_SIZE) / HOP_SIZE + 1;
+        let shortest = (0..RATE as usize)
+            .find(|&len| noise_frames(frames(len)) >= MIN_NOISE_FRAMES)
+            .unwrap();
+        let long = noise(0.02, shortest);
+        assert_ne!(suppress_noise(&long), long);
+    }
+
+    #[test]
+    fn normalizes_to_target_peak_without_clipping() {
+        let mut samples = sine(440.0, 0.25, 4000);
+        normalize_peak(&mut samples, -1.0, 20.0);
+        let peak = samples.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+        let target = 10f32.powf(-1.0 / 20.0);
+        assert!((peak - target).abs() < 1e-4, "peak {}", peak);
+
+        // Loud input is brought down to the target, never past full scale
+        let mut samples = sine(440.0, 1.0, 4000);
+        samples[100] = 1.0;
+        normalize_peak(&mut samples, -1.0, 20.0);
+        assert!(samples.iter().all(|s| s.abs() <= target + 1e-6));
+
+        // A 0 dBFS target stays within full scale
+        let mut samples = sine(440.0, 0.3, 4000);
+        normalize_peak(&mut samples, 0.0, 20.0);
+        assert!(samples.iter().all(|s| s.abs() <= 1.0));
+    }
+
+    #[test]
+    fn normalization_gain_is_limited() {
+        // 40 dB below the target, but only 20 dB of gain allowed
+        let mut samples = sine(440.0, 0.01, 4000);
+        normalize_peak(&mut samples, 0.0, 20.0);
+        let peak = samples.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+        assert!((peak - 0.1).abs() < 1e-3, "peak {}", peak);
+
+        let mut silence = vec![0.0; 100];
+        normalize_peak(&mut silence, -1.0, 20.0);
+        assert!(silence.iter().all(|&s| s == 0.0));
+    }
+
+    #[test]
+    fn chain_follows_config() {
+        let mut config = AudioConfig::default();
+        config.highpass = false;
+        config.noise_suppression = false;
+        config.normalize = false;
+        let chain = DspChain::from_config(&config);
+        assert!(!chain.is_enabled());
+
+        let mut samples = sine(440.0, 0.1, 4000);
+        let original = samples.clone();
+        chain.process(&mut samples, RATE);
+        assert_eq!(samples, original);
+
+        config.normalize = true;
+        config.normalize_peak_db = 0.0;
+        let chain = DspChain::from_config(&config);
+        assert!(chain.is_enabled());
+        chain.process(&mut samples, RATE);
+        let peak = samples.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+        assert!((peak - 1.0).abs() < 1e-3);
+    }
+}
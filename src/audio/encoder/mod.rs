@@ -0,0 +1,100 @@
+mod flac;
+#[cfg(feature = "opus")]
+mod opus;
+
+pub use flac::FlacEncoder;
+#[cfg(feature = "opus")]
+pub use opus::OpusEncoder;
+
+use crate::audio::encode_wav;
+use crate::config::AudioFormat;
+use crate::error::AudioError;
+
+/// Encodes mono f32 samples into an upload format
+pub trait AudioEncoder: Send + Sync {
+    /// Encode the samples into a complete file
+    fn encode(&self, samples: &[f32], sample_rate: u32) -> Result<Vec<u8>, AudioError>;
+
+    /// MIME type of the encoded data
+    fn mime_type(&self) -> &'static str;
+
+    /// File name to use for uploads (APIs detect the format from it)
+    fn file_name(&self) -> &'static str;
+}
+
+/// Uncompressed 16-bit PCM WAV
+pub struct WavEncoder;
+
+impl AudioEncoder for WavEncoder {
+    fn encode(&self, samples: &[f32], sample_rate: u32) -> Result<Vec<u8>, AudioError> {
+        encode_wav(samples, sample_rate)
+    }
+
+    fn mime_type(&self) -> &'static str {
+        "audio/wav"
+    }
+
+    fn file_name(&self) -> &'static str {
+        "audio.wav"
+    }
+}
+
+/// Create the encoder for an upload format
+pub fn encoder(format: AudioFormat) -> Result<Box<dyn AudioEncoder>, AudioError> {
+    match format {
+        AudioFormat::Wav => Ok(Box::new(WavEncoder)),
+        AudioFormat::Flac => Ok(Box::new(FlacEncoder)),
+        #[cfg(feature = "opus")]
+        AudioFormat::Opus => Ok(Box::new(OpusEncoder)),
+        #[cfg(not(feature = "opus"))]
+        AudioFormat::Opus => Err(AudioError::EncodeError(
+            "Opus support is not compiled in (build with --features opus)".into(),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wav_round_trips() {
+        let samples: Vec<f32> = (0..1001).map(|i| (i as f32 * 0.01).sin() * 0.8).collect();
+        let data = WavEncoder.encode(&samples, 16000).unwrap();
+
+        let mut reader = hound::WavReader::new(data.as_slice()).unwrap();
+        assert_eq!(reader.spec().sample_rate, 16000);
+        assert_eq!(reader.spec().channels, 1);
+        let decoded: Vec<i16> = reader.samples::<i16>().map(|s| s.unwrap()).collect();
+        let expected: Vec<i16> = samples.iter().map(|&s| (s * i16::MAX as f32) as i16).collect();
+        assert_eq!(decoded, expected);
+    }
+
+    #[test]
+    fn selects_encoder_per_format() {
+        let wav = encoder(AudioFormat::Wav).unwrap();
+        assert_eq!(wav.mime_type(), "audio/wav");
+        assert_eq!(wav.file_name(), "audio.wav");
+
+        let flac = encoder(AudioFormat::Flac).unwrap();
+        assert_eq!(flac.mime_type(), "audio/flac");
+        assert_eq!(flac.file_name(), "audio.flac");
+    }
+
+    #[cfg(feature = "opus")]
+    #[test]
+    fn selects_opus_encoder() {
+        let opus = encoder(AudioFormat::Opus).unwrap();
+        assert_eq!(opus.mime_type(), "audio/ogg");
+        assert_eq!(opus.file_name(), "audio.ogg");
+    }
+
+    #[cfg(not(feature = "opus"))]
+    #[test]
+    fn opus_needs_the_feature() {
+        assert!(matches!(
+            encoder(AudioFormat::Opus),
+            Err(AudioError::EncodeError(_))
+        ));
+    }
+}
@@ -0,0 +1,249 @@
+//! Opus in an Ogg container (RFC 7845)
+
+use audiopus::coder::Encoder;
+use audiopus::{Application, Bitrate, Channels, SampleRate};
+use ogg::writing::{PacketWriteEndInfo, PacketWriter};
+
+use crate::audio::capture::resample;
+use crate::audio::encoder::AudioEncoder;
+use crate::error::AudioError;
+
+/// Rate audio is encoded at; wideband covers speech
+const ENCODE_RATE: u32 = 16000;
+
+/// 20 ms frames
+const FRAME_SIZE: usize = ENCODE_RATE as usize / 50;
+
+/// Ogg Opus granule positions always count 48 kHz samples
+const GRANULE_SCALE: u64 = 48000 / ENCODE_RATE as u64;
+
+/// Plenty for speech recognition
+const BITRATE: i32 = 24000;
+
+/// Largest packet libopus produces
+const MAX_PACKET: usize = 4000;
+
+const STREAM_SERIAL: u32 = 1;
+
+/// Lossy Opus at 24 kbit/s
+pub struct OpusEncoder;
+
+impl AudioEncoder for OpusEncoder {
+    fn encode(&self, samples: &[f32], sample_rate: u32) -> Result<Vec<u8>, AudioError> {
+        let mut encoder = Encoder::new(SampleRate::Hz16000, Channels::Mono, Application::Voip)
+            .map_err(encode_error)?;
+        encoder
+            .set_bitrate(Bitrate::BitsPerSecond(BITRATE))
+            .map_err(encode_error)?;
+        let lookahead = encoder.lookahead().map_err(encode_error)? as usize;
+
+        // Feed the encoder's lookahead as silence so the end is not cut off
+        let mut pcm = resample(samples, sample_rate, ENCODE_RATE);
+        let length = pcm.len() as u64;
+        pcm.resize(pcm.len() + lookahead, 0.0);
+        let pre_skip = lookahead as u64 * GRANULE_SCALE;
+
+        let mut out = Vec::new();
+        let mut writer = PacketWriter::new(&mut out);
+        writer
+            .write_packet(
+                opus_head(pre_skip as u16, sample_rate).into_boxed_slice(),
+                STREAM_SERIAL,
+                PacketWriteEndInfo::EndPage,
+                0,
+            )
+            .map_err(encode_error)?;
+        writer
+            .write_packet(
+                opus_tags().into_boxed_slice(),
+                STREAM_SERIAL,
+                PacketWriteEndInfo::EndPage,
+                0,
+            )
+            .map_err(encode_error)?;
+
+        let frames = pcm.len().div_ceil(FRAME_SIZE);
+        let mut frame = vec![0.0f32; FRAME_SIZE];
+        let mut packet = vec![0u8; MAX_PACKET];
+        for (i, chunk) in pcm.chunks(FRAME_SIZE).enumerate() {
+            // Zero-pad the last frame
+            frame[..chunk.len()].copy_from_slice(chunk);
+            frame[chunk.len()..].fill(0.0);
+
+            let len = encoder
+                .encode_float(&frame, &mut packet)
+                .map_err(encode_error)?;
+
+            let last = i + 1 == frames;
+            let decoded = (i as u64 + 1) * FRAME_SIZE as u64 * GRANULE_SCALE;
+            let (end, granule) = if last {
+                // The final granule position trims the padding
+                let end = pre_skip + length * GRANULE_SCALE;
+                (PacketWriteEndInfo::EndStream, end.min(decoded))
+            } else {
+                (PacketWriteEndInfo::NormalPacket, decoded)
+            };
+
+            writer
+                .write_packet(packet[..len].into(), STREAM_SERIAL, end, granule)
+                .map_err(encode_error)?;
+        }
+
+        drop(writer);
+        Ok(out)
+    }
+
+    fn mime_type(&self) -> &'static str {
+        "audio/ogg"
+    }
+
+    fn file_name(&self) -> &'static str {
+        "audio.ogg"
+    }
+}
+
+/// Identification header
+fn opus_head(pre_skip: u16, input_rate: u32) -> Vec<u8> {
+    let mut head = Vec::with_capacity(19);
+    head.extend_from_slice(b"OpusHead");
+    head.push(1); // version
+    head.push(1); // channels
+    head.extend_from_slice(&pre_skip.to_le_bytes());
+    head.extend_from_slice(&input_rate.to_le_bytes());
+    head.extend_from_slice(&0i16.to_le_bytes()); // output gain
+    head.push(0); // mono/stereo channel mapping
+    head
+}
+
+/// Comment header with only the vendor string
+fn opus_tags() -> Vec<u8> {
+    let vendor = env!("CARGO_PKG_NAME").as_bytes();
+    let mut tags = Vec::with_capacity(16 + vendor.len());
+    tags.extend_from_slice(b"OpusTags");
+    tags.extend_from_slice(&(vendor.len() as u32).to_le_bytes());
+    tags.extend_from_slice(vendor);
+    tags.extend_from_slice(&0u32.to_le_bytes()); // no comments
+    tags
+}
+
+fn encode_error(e: impl std::fmt::Display) -> AudioError {
+    AudioError::EncodeError(e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use audiopus::coder::Decoder;
+    use ogg::reading::PacketReader;
+    use std::io::Cursor;
+
+    struct Decoded {
+        head: Vec<u8>,
+        tags: Vec<u8>,
+        samples: Vec<f32>,
+        final_granule: u64,
+    }
+
+    /// Demux and decode, dropping the pre-skip and the padding the final
+    /// granule position trims
+    fn decode(data: &[u8]) -> Decoded {
+        let mut reader = PacketReader::new(Cursor::new(data));
+        let head = reader.read_packet_expected().unwrap().data;
+        let tags = reader.read_packet_expected().unwrap().data;
+
+        let mut decoder = Decoder::new(SampleRate::Hz16000, Channels::Mono).unwrap();
+        let mut samples = Vec::new();
+        let mut final_granule = 0;
+        let mut frame = vec![0.0f32; FRAME_SIZE * 6];
+        while let Some(packet) = reader.read_packet().unwrap() {
+            let len = decoder
+                .decode_float(
+                    Some(packet.data.as_slice().try_into().unwrap()),
+                    frame.as_mut_slice().try_into().unwrap(),
+                    false,
+                )
+                .unwrap();
+            samples.extend_from_slice(&frame[..len]);
+            if packet.last_in_stream() {
+                final_granule = packet.absgp_page();
+            }
+        }
+
+        let pre_skip = u16::from_le_bytes([head[10], head[11]]) as usize;
+        let end = (final_granule / GRANULE_SCALE) as usize;
+        Decoded {
+            samples: samples[pre_skip / GRANULE_SCALE as usize..end].to_vec(),
+            head,
+            tags,
+            final_granule,
+        }
+    }
+
+    fn tone(len: usize, sample_rate: u32) -> Vec<f32> {
+        (0..len)
+            .map(|i| 0.5 * (2.0 * std::f32::consts::PI * 440.0 * i as f32 / sample_rate as f32).sin())
+            .collect()
+    }
+
+    #[test]
+    fn writes_headers() {
+        let data = OpusEncoder.encode(&tone(16000, 16000), 16000).unwrap();
+        let decoded = decode(&data);
+
+        assert_eq!(&decoded.head[..8], b"OpusHead");
+        assert_eq!(decoded.head[9], 1);
+        assert_eq!(
+            u32::from_le_bytes(decoded.head[12..16].try_into().unwrap()),
+            16000
+        );
+        assert_eq!(&decoded.tags[..8], b"OpusTags");
+    }
+
+    #[test]
+    fn keeps_length_with_odd_tail() {
+        // Not a whole number of 20 ms frames
+        let samples = tone(16000 + 137, 16000);
+        let data = OpusEncoder.encode(&samples, 16000).unwrap();
+        let decoded = decode(&data);
+
+        assert_eq!(decoded.samples.len(), samples.len());
+        let pre_skip = u16::from_le_bytes([decoded.head[10], decoded.head[11]]) as u64;
+        assert_eq!(
+            decoded.final_granule,
+            pre_skip + samples.len() as u64 * GRANULE_SCALE
+        );
+    }
+
+    #[test]
+    fn resamples_to_encode_rate() {
+        let samples = tone(48000, 48000);
+        let decoded = decode(&OpusEncoder.encode(&samples, 48000).unwrap());
+        assert_eq!(decoded.samples.len(), 16000);
+    }
+
+    #[test]
+    fn preserves_tone() {
+        let samples = tone(16000, 16000);
+        let decoded = decode(&OpusEncoder.encode(&samples, 16000).unwrap());
+
+        // Lossy: compare level and shape once the codec has settled
+        let settled = 1600..samples.len() - 1600;
+        let dot = |a: &[f32], b: &[f32]| settled.clone().map(|i| a[i] * b[i]).sum::<f32>();
+        let original = dot(&samples, &samples);
+        let output = dot(&decoded.samples, &decoded.samples);
+        let correlation = dot(&samples, &decoded.samples) / (original * output).sqrt();
+
+        let level_db = 10.0 * (output / original).log10();
+        assert!(level_db.abs() < 3.0, "level off by {:.1} dB", level_db);
+        assert!(correlation > 0.7, "correlation {:.2}", correlation);
+    }
+
+    #[test]
+    fn encodes_silence_and_full_scale() {
+        for samples in [vec![0.0; 4000], vec![1.0; 4000], vec![-1.0; 4000]] {
+            let decoded = decode(&OpusEncoder.encode(&samples, 16000).unwrap());
+            assert_eq!(decoded.samples.len(), samples.len());
+            assert!(decoded.samples.iter().all(|s| s.is_finite()));
+        }
+    }
+}
@@ -0,0 +1,445 @@
+use cpal::traits::StreamTrait;
+use cpal::Stream;
+use std::collections::VecDeque;
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+use tracing::{debug, error, info, warn};
+
+use crate::audio::capture::{AudioCapture, CaptureConfig};
+use crate::audio::device;
+use crate::audio::ring::{audio_ring, AudioReceiver, AudioSender, RING_SECONDS};
+use crate::error::AudioError;
+
+/// Initial and maximum delay between attempts to reopen a failed device
+const REOPEN_DELAY: Duration = Duration::from_millis(500);
+const MAX_REOPEN_DELAY: Duration = Duration::from_secs(10);
+
+/// How often to look for added or removed devices while the stream runs
+/// without a recording (pre-roll)
+const HOTPLUG_POLL: Duration = Duration::from_secs(3);
+
+/// The same while the stream is paused between recordings. Listing devices
+/// is not free: ALSA opens each one to enumerate it, which takes time and can
+/// briefly grab a device another program is using. An idle daemon therefore
+/// looks rarely, and a device plugged in meanwhile is only picked up this
+/// late (or once the next recording ends).
+const IDLE_HOTPLUG_POLL: Duration = Duration::from_secs(30);
+
+/// How often captured audio is moved out of the stream's ring
+const PUMP_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Where captured audio goes: the pre-roll ring while idle, a recording once
+/// one has begun.
+///
+/// The stream's callback only writes its own lock-free ring (`source`); the
+/// pump thread moves that audio here, off the real-time thread.
+struct Route {
+    source: Option<AudioReceiver>,
+    // Samples the source had dropped at the last pump
+    source_dropped: u64,
+    ring: VecDeque<f32>,
+    capacity: usize,
+    recording: Option<AudioSender>,
+    // Set when a recording begins, cleared by its first chunk
+    resumed_at: Option<Instant>,
+}
+
+impl Route {
+    /// Move captured audio to the recording, or into the pre-roll
+    fn pump(&mut self) {
+        let Some(source) = &mut self.source else {
+            return;
+        };
+        let samples = source.try_recv();
+        let dropped = source.dropped() - self.source_dropped;
+        self.source_dropped += dropped;
+
+        if let Some(tx) = &mut self.recording {
+            tx.add_dropped(dropped);
+            if let Some(samples) = samples {
+                if let Some(resumed_at) = self.resumed_at.take() {
+                    debug!("Capture start latency: {:?}", resumed_at.elapsed());
+                }
+                tx.push(&samples);
+            }
+            return;
+        }
+
+        let Some(samples) = samples else {
+            return;
+        };
+        if self.capacity == 0 {
+            return;
+        }
+        self.ring.extend(samples);
+        let excess = self.ring.len().saturating_sub(self.capacity);
+        self.ring.drain(..excess);
+    }
+
+    /// Take audio from a newly opened stream, after what is left of the old one
+    fn set_source(&mut self, source: AudioReceiver) {
+        self.pump();
+        self.source = Some(source);
+        self.source_dropped = 0;
+    }
+}
+
+/// Pump captured audio until the engine is gone
+fn spawn_pump(route: Weak<Mutex<Route>>) -> Result<(), AudioError> {
+    std::thread::Builder::new()
+        .name("capture-pump".into())
+        .spawn(move || {
+            while let Some(route) = route.upgrade() {
+                route.lock().unwrap().pump();
+                drop(route);
+                std::thread::sleep(PUMP_INTERVAL);
+            }
+        })
+        .map(|_| ())
+        .map_err(|e| AudioError::StreamError(e.to_string()))
+}
+
+/// Requests for the engine thread
+enum Control {
+    Resume,
+    Pause,
+    SetDevice(Option<String>),
+    DeviceError(String),
+    Shutdown,
+}
+
+/// Persistent capture engine.
+///
+/// The input device is opened once and its stream is paused between
+/// recordings, so starting a recording only resumes it. If the device fails
+/// (unplugged, server restart) it is reopened automatically, and between
+/// recordings it switches to a more preferred device when one is plugged in.
+/// The names of the input devices present are published as they change.
+///
+/// With a pre-roll the stream is never paused: the most recent audio is kept
+/// in memory and prepended when a recording begins. The microphone (and any
+/// "mic in use" indicator) then stays on for as long as the engine exists.
+pub struct CaptureEngine {
+    route: Arc<Mutex<Route>>,
+    control: Sender<Control>,
+    device: watch::Receiver<Option<String>>,
+    sample_rate: u32,
+}
+
+impl CaptureEngine {
+    /// Open the input device on a dedicated thread (the cpal stream is not
+    /// Send); the names of the input devices present are sent to `devices`
+    pub fn start(
+        config: CaptureConfig,
+        pre_roll: Duration,
+        devices: watch::Sender<Vec<String>>,
+    ) -> Result<Self, AudioError> {
+        let sample_rate = config.sample_rate;
+        let capacity = (pre_roll.as_secs_f64() * sample_rate as f64) as usize;
+        let route = Arc::new(Mutex::new(Route {
+            source: None,
+            source_dropped: 0,
+            ring: VecDeque::with_capacity(capacity),
+            capacity,
+            recording: None,
+            resumed_at: None,
+        }));
+
+        let (control_tx, control_rx) = std::sync::mpsc::channel::<Control>();
+        let (ready_tx, ready_rx) = std::sync::mpsc::channel::<Result<(), AudioError>>();
+
+        let (worker_device, device) = watch::channel(None);
+        let worker_route = route.clone();
+        let worker_control = control_tx.clone();
+        std::thread::Builder::new()
+            .name("capture".into())
+            .spawn(move || {
+                let worker = Worker {
+                    config,
+                    route: worker_route,
+                    control: worker_control,
+                    device: worker_device,
+                    devices,
+                    keep_open: capacity > 0,
+                    active: capacity > 0,
+                    stream: None,
+                    delay: REOPEN_DELAY,
+                };
+                worker.run(control_rx, ready_tx)
+            })
+            .map_err(|e| AudioError::StreamError(e.to_string()))?;
+
+        ready_rx
+            .recv()
+            .map_err(|e| AudioError::StreamError(e.to_string()))??;
+        spawn_pump(Arc::downgrade(&route))?;
+
+        if capacity > 0 {
+            info!(
+                "Microphone kept open for {} ms of pre-roll (audio stays in memory)",
+                pre_roll.as_millis()
+            );
+        }
+
+        Ok(Self {
+            route,
+            control: control_tx,
+            device,
+            sample_rate,
+        })
+    }
+
+    /// Begin a recording: returns the buffered pre-roll (if any) and a
+    /// receiver for the audio that follows
+    pub fn begin(&self) -> (Vec<f32>, AudioReceiver) {
+        let (tx, rx) = audio_ring(self.sample_rate as usize * RING_SECONDS);
+        let pre_roll = {
+            let mut route = self.route.lock().unwrap();
+            // Stale audio goes to the pre-roll (or nowhere without one)
+            route.pump();
+            if route.recording.is_some() {
+                warn!("Capture already recording, restarting it");
+            }
+            route.recording = Some(tx);
+            route.resumed_at = Some(Instant::now());
+            route.ring.drain(..).collect()
+        };
+        let _ = self.control.send(Control::Resume);
+        (pre_roll, rx)
+    }
+
+    /// End the recording; the stream is paused (or refills the pre-roll)
+    pub fn end(&self) {
+        {
+            let mut route = self.route.lock().unwrap();
+            // Deliver the tail before closing the recording
+            route.pump();
+            route.recording = None;
+            route.resumed_at = None;
+        }
+        let _ = self.control.send(Control::Pause);
+    }
+
+    /// Name of the device currently open, if any
+    pub fn device_name(&self) -> Option<String> {
+        self.device.borrow().clone()
+    }
+
+    /// Get a receiver for the name of the device currently open
+    pub fn device_receiver(&self) -> watch::Receiver<Option<String>> {
+        self.device.clone()
+    }
+
+    /// Switch to another input device (None = default)
+    pub fn set_device(&self, device_name: Option<String>) {
+        let _ = self.control.send(Control::SetDevice(device_name));
+    }
+}
+
+impl Drop for CaptureEngine {
+    fn drop(&mut self) {
+        let _ = self.control.send(Control::Shutdown);
+    }
+}
+
+/// State owned by the engine thread
+struct Worker {
+    config: CaptureConfig,
+    route: Arc<Mutex<Route>>,
+    control: Sender<Control>,
+    device: watch::Sender<Option<String>>,
+    // Names of the input devices present
+    devices: watch::Sender<Vec<String>>,
+    // Never pause (pre-roll)
+    keep_open: bool,
+    // Whether the stream should be running
+    active: bool,
+    stream: Option<Stream>,
+    delay: Duration,
+}
+
+impl Worker {
+    fn run(mut self, control: Receiver<Control>, ready: Sender<Result<(), AudioError>>) {
+        // Report the first open to the caller; later failures are retried
+        if let Err(e) = self.open() {
+            let _ = ready.send(Err(e));
+            return;
+        }
+        let _ = ready.send(Ok(()));
+        self.check_devices();
+
+        loop {
+            let timeout = if self.stream.is_none() && self.active {
+                self.delay
+            } else if self.active {
+                HOTPLUG_POLL
+            } else {
+                IDLE_HOTPLUG_POLL
+            };
+            let message = control.recv_timeout(timeout);
+
+            match message {
+                Ok(Control::Resume) => {
+                    self.active = true;
+                    self.apply();
+                }
+                Ok(Control::Pause) => {
+                    if !self.keep_open {
+                        self.active = false;
+                        self.apply();
+                    }
+                    // Catch up on devices that changed during the recording
+                    self.check_devices();
+                }
+                Ok(Control::SetDevice(device_name)) => {
+                    self.config.device_name = device_name;
+                    self.close();
+                    self.delay = REOPEN_DELAY;
+                    self.reopen();
+                }
+                // A failing stream may report many errors; reopen once
+                Ok(Control::DeviceError(e)) if self.stream.is_some() => {
+                    warn!("Audio device error: {}, reopening", e);
+                    self.close();
+                    self.reopen();
+                }
+                Ok(Control::DeviceError(_)) => {}
+                Err(RecvTimeoutError::Timeout) if self.stream.is_none() && self.active => {
+                    self.reopen()
+                }
+                Err(RecvTimeoutError::Timeout) => self.check_devices(),
+                Ok(Control::Shutdown) | Err(RecvTimeoutError::Disconnected) => break,
+            }
+        }
+
+        debug!("Capture engine stopped");
+    }
+
+    /// Publish the devices present and switch devices if the preferred one
+    /// changed (plugged in or removed). Never runs during a recording.
+    fn check_devices(&mut self) {
+        if self.route.lock().unwrap().recording.is_some() {
+            return;
+        }
+
+        let (names, preferred) = match device::scan_input_devices(
+            self.config.device_name.as_deref(),
+            &self.config.preferences,
+        ) {
+            Ok(scan) => scan,
+            Err(e) => {
+                debug!("Cannot list input devices: {}", e);
+                return;
+            }
+        };
+        self.devices.send_if_modified(|devices| {
+            let changed = *devices != names;
+            *devices = names;
+            changed
+        });
+
+        // A closed device is opened as preferred when next needed
+        if self.stream.is_none() {
+            return;
+        }
+
+        let current = self.device.borrow().clone();
+        if preferred.is_some() && preferred != current {
+            info!(
+                "Input device changed: {} -> {}",
+                current.as_deref().unwrap_or("none"),
+                preferred.as_deref().unwrap_or("none")
+            );
+            self.close();
+            if self.active {
+                self.reopen();
+            } else if let Err(e) = self.open() {
+                warn!("Failed to open audio device: {}", e);
+            }
+        }
+    }
+
+    fn close(&mut self) {
+        self.stream = None;
+        self.device.send_replace(None);
+    }
+
+    /// Open the device and build a stream in the current active/paused state
+    fn open(&mut self) -> Result<(), AudioError> {
+        let capture = AudioCapture::new(self.config.clone())?;
+
+        let device_rate = capture.device_sample_rate();
+        let (sender, receiver) = audio_ring(device_rate as usize * RING_SECONDS);
+        let control = self.control.clone();
+        let stream = capture.open_stream(sender, move |err| {
+            error!("Audio stream error: {}", err);
+            let _ = control.send(Control::DeviceError(err.to_string()));
+        })?;
+        self.route
+            .lock()
+            .unwrap()
+            .set_source(receiver.resampled(device_rate, self.config.sample_rate));
+
+        if self.active {
+            stream
+                .play()
+                .map_err(|e| AudioError::StreamError(e.to_string()))?;
+        } else if let Err(e) = stream.pause() {
+            // Not every backend can pause; keep the device closed until needed
+            debug!("Cannot pause audio stream ({}), closing it", e);
+            self.close();
+            return Ok(());
+        }
+        self.stream = Some(stream);
+        self.device
+            .send_replace(Some(capture.device_name().to_string()));
+        Ok(())
+    }
+
+    /// Reopen a lost device while it is needed; otherwise wait for the next
+    /// recording
+    fn reopen(&mut self) {
+        if self.stream.is_some() || !self.active {
+            return;
+        }
+
+        match self.open() {
+            Ok(()) => {
+                info!("Audio device reopened");
+                self.delay = REOPEN_DELAY;
+            }
+            Err(e) => {
+                error!("Failed to open audio device: {}, retrying in {:?}", e, self.delay);
+                self.delay = (self.delay * 2).min(MAX_REOPEN_DELAY);
+            }
+        }
+    }
+
+    /// Play or pause the stream to match `active`
+    fn apply(&mut self) {
+        let stream = match &self.stream {
+            Some(stream) => stream,
+            None => return self.reopen(),
+        };
+
+        let result = if self.active {
+            stream.play().map_err(|e| e.to_string())
+        } else {
+            stream.pause().map_err(|e| e.to_string())
+        };
+
+        if let Err(e) = result {
+            if self.active {
+                warn!("Failed to start audio stream: {}, reopening", e);
+            } else {
+                // Not every backend can pause; close the device instead so the
+                // microphone is not left open
+                debug!("Cannot pause audio stream ({}), closing it", e);
+            }
+            self.close();
+            self.reopen();
+        }
+    }
+}
@@ -0,0 +1,453 @@
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{Device, SampleFormat, StreamConfig};
+use std::f32::consts::PI;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+use tracing::{debug, error, warn};
+
+use crate::audio::capture::resample;
+use crate::config::SoundsConfig;
+use crate::error::AudioError;
+
+/// Sample rate the embedded cues are synthesized at
+const CUE_SAMPLE_RATE: u32 = 48000;
+
+/// Feedback sounds played on state transitions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cue {
+    /// Recording started
+    Start,
+    /// Recording stopped, transcription in progress
+    Stop,
+    /// Transcription finished
+    Success,
+    /// Transcription or recording failed
+    Error,
+    /// Recording cancelled
+    Cancelled,
+}
+
+impl Cue {
+    const ALL: [Cue; 5] = [
+        Cue::Start,
+        Cue::Stop,
+        Cue::Success,
+        Cue::Error,
+        Cue::Cancelled,
+    ];
+
+    fn index(self) -> usize {
+        self as usize
+    }
+
+    /// Tone sequence for the embedded cue: (frequency Hz, length ms)
+    fn tones(self) -> &'static [(f32, u32)] {
+        match self {
+            Cue::Start => &[(660.0, 70), (880.0, 90)],
+            Cue::Stop => &[(880.0, 70), (660.0, 90)],
+            Cue::Success => &[(1320.0, 60)],
+            Cue::Error => &[(220.0, 150), (0.0, 60), (220.0, 150)],
+            Cue::Cancelled => &[(440.0, 80), (330.0, 120)],
+        }
+    }
+}
+
+/// Mono cue audio at its own sample rate
+#[derive(Debug, Clone)]
+struct Sound {
+    samples: Vec<f32>,
+    sample_rate: u32,
+}
+
+impl Sound {
+    fn duration(&self) -> Duration {
+        Duration::from_secs_f32(self.samples.len() as f32 / self.sample_rate as f32)
+    }
+}
+
+/// Receives when a queued cue played, once its output stream has started;
+/// disconnects without a value if the cue could not be played
+pub type Playback = mpsc::Receiver<Range<Instant>>;
+
+/// Plays short feedback cues on the default output device.
+///
+/// Playback happens on a dedicated thread because cpal streams are not `Send`;
+/// `play` only queues the cue and never blocks the caller.
+pub struct FeedbackPlayer {
+    sounds: Vec<Sound>,
+    tx: mpsc::Sender<(Sound, mpsc::Sender<Range<Instant>>)>,
+}
+
+impl FeedbackPlayer {
+    /// Load cues (custom WAV files where configured, embedded tones otherwise)
+    /// and start the playback thread
+    pub fn new(config: &SoundsConfig) -> Self {
+        let volume = config.volume.clamp(0.0, 1.0);
+
+        let sounds = Cue::ALL
+            .iter()
+            .map(|&cue| {
+                let mut sound = match cue_path(config, cue) {
+                    Some(path) => load_wav(path).unwrap_or_else(|e| {
+                        warn!("Failed to load {:?} cue from {:?}: {}", cue, path, e);
+                        synth_cue(cue)
+                    }),
+                    None => synth_cue(cue),
+                };
+                sound.samples.iter_mut().for_each(|s| *s *= volume);
+                sound
+            })
+            .collect();
+
+        let (tx, rx) = mpsc::channel::<(Sound, mpsc::Sender<Range<Instant>>)>();
+        std::thread::spawn(move || {
+            while let Ok((sound, played)) = rx.recv() {
+                let duration = sound.duration();
+                let result = play_blocking(&sound, |started| {
+                    let _ = played.send(started..started + duration);
+                });
+                if let Err(e) = result {
+                    warn!("Failed to play feedback sound: {}", e);
+                }
+            }
+            debug!("Feedback player thread finished");
+        });
+
+        Self { sounds, tx }
+    }
+
+    /// Queue a cue for playback
+    pub fn play(&self, cue: Cue) -> Playback {
+        debug!("Playing {:?} cue", cue);
+        let (played_tx, played_rx) = mpsc::channel();
+        let _ = self.tx.send((self.sounds[cue.index()].clone(), played_tx));
+        played_rx
+    }
+
+    /// Length of a cue
+    pub fn duration(&self, cue: Cue) -> Duration {
+        self.sounds[cue.index()].duration()
+    }
+}
+
+/// Echo and output latency still heard after a cue has played
+const CUE_TAIL: Duration = Duration::from_millis(50);
+
+/// Gain applied to the recording while the start cue plays (-20 dB)
+const DUCK_GAIN: f32 = 0.1;
+
+/// Ramp into and out of the ducked window, so its edges don't click
+const DUCK_RAMP: Duration = Duration::from_millis(5);
+
+/// Attenuates the start cue in a recording: only the captured samples that
+/// overlap the cue's actual playback (and its tail) are turned down, so
+/// speech begun during the cue is kept, just quieter.
+pub struct Duck {
+    playback: Playback,
+    // When the first recorded sample after `offset` was captured
+    started: Instant,
+    sample_rate: u32,
+    // Samples at the start of the recording captured before `started`
+    offset: usize,
+    // Recording samples the cue overlaps, once it has played
+    window: Option<Range<usize>>,
+}
+
+impl Duck {
+    pub fn new(playback: Playback, started: Instant, sample_rate: u32, offset: usize) -> Self {
+        Self {
+            playback,
+            started,
+            sample_rate,
+            offset,
+            window: None,
+        }
+    }
+
+    /// Attenuate the cue in `recording` once it has played and the samples
+    /// it overlaps have been recorded. Returns true when done, including when
+    /// the cue could not be played.
+    pub fn apply(&mut self, recording: &mut [f32]) -> bool {
+        let window = match &self.window {
+            Some(window) => window.clone(),
+            None => match self.playback.try_recv() {
+                Ok(played) => {
+                    let window =
+                        self.to_sample(played.start)..self.to_sample(played.end + CUE_TAIL);
+                    self.window = Some(window.clone());
+                    window
+                }
+                Err(mpsc::TryRecvError::Empty) => return false,
+                Err(mpsc::TryRecvError::Disconnected) => return true,
+            },
+        };
+
+        if recording.len() < window.end {
+            return false;
+        }
+        debug!(
+            "Ducking {} ms of start cue in the recording",
+            window.len() as u64 * 1000 / self.sample_rate as u64
+        );
+        let ramp = (DUCK_RAMP.as_secs_f32() * self.sample_rate as f32) as usize;
+        let len = window.len();
+        for (i, sample) in recording[window].iter_mut().enumerate() {
+            // Distance from the nearest edge of the window, as a fraction of the ramp
+            let edge = (i.min(len - 1 - i) as f32 / ramp.max(1) as f32).min(1.0);
+            *sample *= 1.0 - (1.0 - DUCK_GAIN) * edge;
+        }
+        true
+    }
+
+    /// Index of the sample recorded at `at`
+    fn to_sample(&self, at: Instant) -> usize {
+        let since = at.saturating_duration_since(self.started);
+        self.offset + (since.as_secs_f64() * self.sample_rate as f64) as usize
+    }
+}
+
+/// Play mono samples on the default output device and wait for them to finish
+pub fn play_samples(samples: &[f32], sample_rate: u32) -> Result<(), AudioError> {
+    let sound = Sound {
+        samples: samples.to_vec(),
+        sample_rate,
+    };
+    play_blocking(&sound, |_| {})
+}
+
+/// Custom WAV file configured for a cue
+fn cue_path(config: &SoundsConfig, cue: Cue) -> Option<&Path> {
+    match cue {
+        Cue::Start => config.start.as_deref(),
+        Cue::Stop => config.stop.as_deref(),
+        Cue::Success => config.success.as_deref(),
+        Cue::Error => config.error.as_deref(),
+        Cue::Cancelled => config.cancelled.as_deref(),
+    }
+}
+
+/// Synthesize an embedded cue from its tone sequence
+fn synth_cue(cue: Cue) -> Sound {
+    let rate = CUE_SAMPLE_RATE as f32;
+    // 5ms fade in/out per tone to avoid clicks
+    let fade = (rate * 0.005) as usize;
+    let mut samples = Vec::new();
+
+    for &(freq, ms) in cue.tones() {
+        let len = (rate * ms as f32 / 1000.0) as usize;
+        for i in 0..len {
+            let envelope = (i.min(len - i) as f32 / fade as f32).min(1.0);
+            let value = if freq > 0.0 {
+                (2.0 * PI * freq * i as f32 / rate).sin() * 0.4 * envelope
+            } else {
+                0.0
+            };
+            samples.push(value);
+        }
+    }
+
+    Sound {
+        samples,
+        sample_rate: CUE_SAMPLE_RATE,
+    }
+}
+
+/// Load a WAV file as mono f32
+fn load_wav(path: &Path) -> Result<Sound, AudioError> {
+    let reader = hound::WavReader::open(path).map_err(|e| AudioError::WavError(e.to_string()))?;
+    let spec = reader.spec();
+
+    let interleaved: Vec<f32> = match spec.sample_format {
+        hound::SampleFormat::Float => reader
+            .into_samples::<f32>()
+            .collect::<Result<_, _>>()
+            .map_err(|e| AudioError::WavError(e.to_string()))?,
+        hound::SampleFormat::Int => {
+            let scale = (1i64 << (spec.bits_per_sample - 1)) as f32;
+            reader
+                .into_samples::<i32>()
+                .map(|s| s.map(|s| s as f32 / scale))
+                .collect::<Result<_, _>>()
+                .map_err(|e| AudioError::WavError(e.to_string()))?
+        }
+    };
+
+    let channels = spec.channels.max(1) as usize;
+    let samples = interleaved
+        .chunks(channels)
+        .map(|frame| frame.iter().sum::<f32>() / frame.len() as f32)
+        .collect();
+
+    Ok(Sound {
+        samples,
+        sample_rate: spec.sample_rate,
+    })
+}
+
+/// Play a sound on the default output device and wait for it to finish;
+/// `started` is called once the stream is running
+fn play_blocking(sound: &Sound, started: impl FnOnce(Instant)) -> Result<(), AudioError> {
+    let host = cpal::default_host();
+    let device = host
+        .default_output_device()
+        .ok_or_else(|| AudioError::DeviceError("No audio output device found".into()))?;
+
+    let supported_config = device
+        .default_output_config()
+        .map_err(|e| AudioError::ConfigError(e.to_string()))?;
+    let sample_format = supported_config.sample_format();
+    let config: StreamConfig = supported_config.into();
+
+    let samples = resample(&sound.samples, sound.sample_rate, config.sample_rate.0);
+
+    let stream = match sample_format {
+        SampleFormat::F32 => build_output_stream::<f32>(&device, &config, samples)?,
+        SampleFormat::I16 => build_output_stream::<i16>(&device, &config, samples)?,
+        SampleFormat::U16 => build_output_stream::<u16>(&device, &config, samples)?,
+        _ => return Err(AudioError::ConfigError("Unsupported sample format".into())),
+    };
+
+    stream
+        .play()
+        .map_err(|e| AudioError::StreamError(e.to_string()))?;
+    started(Instant::now());
+
+    // Keep the stream alive until the cue (plus device latency) has played
+    std::thread::sleep(sound.duration() + Duration::from_millis(100));
+    Ok(())
+}
+
+fn build_output_stream<T>(
+    device: &Device,
+    config: &StreamConfig,
+    samples: Vec<f32>,
+) -> Result<cpal::Stream, AudioError>
+where
+    T: cpal::Sample + cpal::SizedSample + cpal::FromSample<f32> + Send + 'static,
+{
+    let channels = config.channels as usize;
+    let mut position = 0;
+
+    device
+        .build_output_stream(
+            config,
+            move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
+                for frame in data.chunks_mut(channels) {
+                    let value = samples.get(position).copied().unwrap_or(0.0);
+                    position += 1;
+                    for out in frame.iter_mut() {
+                        *out = T::from_sample(value);
+                    }
+                }
+            },
+            |err| error!("Feedback stream error: {}", err),
+            None,
+        )
+        .map_err(|e| AudioError::StreamError(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: u32 = 1000;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    /// Recording of `len` samples numbered from 0
+    fn recording(len: usize) -> Vec<f32> {
+        (0..len).map(|i| i as f32).collect()
+    }
+
+    /// Whether `sample` is the recording's sample `i` at `gain`
+    fn scaled(sample: f32, i: usize, gain: f32) -> bool {
+        (sample - i as f32 * gain).abs() < 1e-3
+    }
+
+    #[test]
+    fn ducks_only_the_playback_window() {
+        let started = Instant::now();
+        let (tx, rx) = mpsc::channel();
+        let mut duck = Duck::new(rx, started, RATE, 0);
+        tx.send(started + ms(100)..started + ms(200)).unwrap();
+
+        let mut samples = recording(400);
+        assert!(duck.apply(&mut samples));
+
+        // 100 ms of cue plus its 50 ms tail are attenuated, nothing is removed
+        assert_eq!(samples.len(), 400);
+        assert_eq!(&samples[..100], &recording(100)[..]);
+        assert!(scaled(samples[105], 105, DUCK_GAIN));
+        assert!(scaled(samples[200], 200, DUCK_GAIN));
+        assert!(scaled(samples[244], 244, DUCK_GAIN));
+        assert_eq!(&samples[250..], &recording(400)[250..]);
+    }
+
+    #[test]
+    fn ramps_into_and_out_of_the_window() {
+        let started = Instant::now();
+        let (tx, rx) = mpsc::channel();
+        let mut duck = Duck::new(rx, started, RATE, 0);
+        tx.send(started + ms(100)..started + ms(200)).unwrap();
+
+        let mut samples = recording(400);
+        assert!(duck.apply(&mut samples));
+
+        // 5 ms ramps at 1 kHz: the edges are untouched, halfway is half ducked
+        let half = 1.0 - (1.0 - DUCK_GAIN) * 0.4;
+        assert!(scaled(samples[100], 100, 1.0));
+        assert!(scaled(samples[102], 102, half));
+        assert!(scaled(samples[247], 247, half));
+        assert!(scaled(samples[249], 249, 1.0));
+    }
+
+    #[test]
+    fn keeps_the_pre_roll() {
+        let started = Instant::now();
+        let (tx, rx) = mpsc::channel();
+        let mut duck = Duck::new(rx, started, RATE, 300);
+        tx.send(started..started + ms(100)).unwrap();
+
+        let mut samples = recording(600);
+        assert!(duck.apply(&mut samples));
+
+        assert_eq!(samples.len(), 600);
+        assert_eq!(&samples[..300], &recording(300)[..]);
+        assert!(scaled(samples[350], 350, DUCK_GAIN));
+        assert_eq!(samples[450], 450.0);
+    }
+
+    #[test]
+    fn waits_until_the_window_is_recorded() {
+        let started = Instant::now();
+        let (tx, rx) = mpsc::channel();
+        let mut duck = Duck::new(rx, started, RATE, 0);
+
+        let mut samples = recording(100);
+        assert!(!duck.apply(&mut samples));
+
+        tx.send(started + ms(50)..started + ms(150)).unwrap();
+        assert!(!duck.apply(&mut samples));
+        assert_eq!(samples, recording(100));
+
+        samples.extend(recording(200));
+        assert!(duck.apply(&mut samples));
+        assert_eq!(samples.len(), 300);
+        assert!(scaled(samples[120], 20, DUCK_GAIN));
+    }
+
+    #[test]
+    fn gives_up_when_the_cue_is_not_played() {
+        let (tx, rx) = mpsc::channel();
+        let mut duck = Duck::new(rx, Instant::now(), RATE, 0);
+        drop(tx);
+
+        let mut samples = recording(400);
+        assert!(duck.apply(&mut samples));
+        assert_eq!(samples.len(), 400);
+    }
+}
@@ -0,0 +1,131 @@
+use std::ops::Range;
+
+/// Lowest level reported in dBFS (digital silence)
+const FLOOR_DB: f32 = -100.0;
+
+/// Range shown by level meters, in dBFS
+const METER_RANGE_DB: f32 = 60.0;
+
+/// Signal level of a chunk of samples
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct AudioLevel {
+    /// Root mean square amplitude (0.0 - 1.0)
+    pub rms: f32,
+    /// Largest absolute sample (0.0 - 1.0)
+    pub peak: f32,
+}
+
+impl AudioLevel {
+    /// Measure a chunk of samples
+    pub fn measure(samples: &[f32]) -> Self {
+        if samples.is_empty() {
+            return Self::default();
+        }
+
+        let (sum, peak) = samples
+            .iter()
+            .fold((0.0f32, 0.0f32), |(sum, peak), &s| (sum + s * s, peak.max(s.abs())));
+
+        Self {
+            rms: (sum / samples.len() as f32).sqrt(),
+            peak: peak.min(1.0),
+        }
+    }
+
+    /// RMS in dBFS
+    pub fn rms_db(&self) -> f32 {
+        to_db(self.rms)
+    }
+
+    /// Peak in dBFS
+    pub fn peak_db(&self) -> f32 {
+        to_db(self.peak)
+    }
+
+    /// Level for meters (0.0 - 1.0), linear in dB over the last 60 dB
+    pub fn meter(&self) -> f32 {
+        ((self.rms_db() + METER_RANGE_DB) / METER_RANGE_DB).clamp(0.0, 1.0)
+    }
+}
+
+fn to_db(amplitude: f32) -> f32 {
+    if amplitude > 0.0 {
+        (20.0 * amplitude.log10()).max(FLOOR_DB)
+    } else {
+        FLOOR_DB
+    }
+}
+
+/// Frame length used to find speech, in milliseconds
+const SPEECH_FRAME_MS: usize = 20;
+
+/// Audio kept around detected speech so soft onsets and endings survive
+const SPEECH_PADDING_MS: usize = 200;
+
+/// Range of `samples` from the first to the last 20 ms frame whose RMS is
+/// above `threshold`, padded slightly. `None` if no frame is above it.
+pub fn speech_bounds(samples: &[f32], sample_rate: u32, threshold: f32) -> Option<Range<usize>> {
+    let frame = (sample_rate as usize * SPEECH_FRAME_MS / 1000).max(1);
+    let padding = sample_rate as usize * SPEECH_PADDING_MS / 1000;
+    let loud = |f: &[f32]| AudioLevel::measure(f).rms > threshold;
+
+    let first = samples.chunks(frame).position(loud)?;
+    let last = samples.chunks(frame).rposition(loud)?;
+
+    let start = (first * frame).saturating_sub(padding);
+    let end = ((last + 1) * frame + padding).min(samples.len());
+    Some(start..end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 20 ms frames of 20 samples and 200 samples of padding
+    const RATE: u32 = 1000;
+    const THRESHOLD: f32 = 0.01;
+
+    /// `before` samples of silence, `loud` of speech, then `after` of silence
+    fn recording(before: usize, loud: usize, after: usize) -> Vec<f32> {
+        let mut samples = vec![0.0; before];
+        samples.extend(vec![0.5; loud]);
+        samples.extend(vec![0.0; after]);
+        samples
+    }
+
+    #[test]
+    fn finds_nothing_in_silence() {
+        assert_eq!(speech_bounds(&[0.0; 1000], RATE, THRESHOLD), None);
+        assert_eq!(speech_bounds(&[0.005; 1000], RATE, THRESHOLD), None);
+        assert_eq!(speech_bounds(&[], RATE, THRESHOLD), None);
+    }
+
+    #[test]
+    fn trims_leading_and_trailing_silence() {
+        let samples = recording(1000, 400, 1000);
+        assert_eq!(speech_bounds(&samples, RATE, THRESHOLD), Some(800..1600));
+    }
+
+    #[test]
+    fn rounds_out_to_whole_frames() {
+        // Speech starting and ending mid-frame keeps both frames
+        let samples = recording(1010, 400, 990);
+        assert_eq!(speech_bounds(&samples, RATE, THRESHOLD), Some(800..1620));
+    }
+
+    #[test]
+    fn clamps_padding_at_the_edges() {
+        let samples = recording(100, 400, 100);
+        assert_eq!(speech_bounds(&samples, RATE, THRESHOLD), Some(0..600));
+
+        let samples = recording(0, 400, 0);
+        assert_eq!(speech_bounds(&samples, RATE, THRESHOLD), Some(0..400));
+    }
+
+    #[test]
+    fn keeps_pauses_between_words() {
+        let mut samples = recording(1000, 200, 1000);
+        samples.extend(recording(0, 200, 1000));
+        assert_eq!(speech_bounds(&samples, RATE, THRESHOLD), Some(800..2600));
+    }
+}
@@ -1,5 +1,21 @@
 mod capture;
+mod device;
 mod buffer;
+mod dsp;
+mod encoder;
+mod feedback;
+mod level;
+mod engine;
+mod ring;
 
 pub use capture::{AudioCapture, CaptureConfig};
+pub use device::{describe_input_devices, DevicePattern, InputConfigRange, InputDeviceInfo};
 pub use buffer::encode_wav;
+pub use dsp::{highpass, normalize_peak, suppress_noise, DspChain};
+pub use encoder::{encoder, AudioEncoder, FlacEncoder, WavEncoder};
+#[cfg(feature = "opus")]
+pub use encoder::OpusEncoder;
+pub use feedback::{play_samples, Cue, Duck, FeedbackPlayer, Playback};
+pub use level::{speech_bounds, AudioLevel};
+pub use engine::CaptureEngine;
+pub use ring::{audio_ring, AudioReceiver, AudioSender};
@@ -0,0 +1,165 @@
+use ringbuf::{HeapConsumer, HeapProducer, HeapRb};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::audio::capture::resample;
+
+/// Seconds of audio the ring holds before the capture callback starts
+/// dropping samples
+pub const RING_SECONDS: usize = 5;
+
+/// How often an idle receiver checks for audio. The sender never wakes it,
+/// since waking a task can take a lock.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// State shared by both ends of an audio ring
+struct Shared {
+    dropped: AtomicU64,
+    closed: AtomicBool,
+}
+
+/// Create a single-producer, single-consumer audio ring holding `capacity`
+/// samples
+pub fn audio_ring(capacity: usize) -> (AudioSender, AudioReceiver) {
+    let (producer, consumer) = HeapRb::<f32>::new(capacity.max(1)).split();
+    let shared = Arc::new(Shared {
+        dropped: AtomicU64::new(0),
+        closed: AtomicBool::new(false),
+    });
+
+    (
+        AudioSender {
+            producer,
+            shared: shared.clone(),
+        },
+        AudioReceiver {
+            consumer,
+            shared,
+            rates: None,
+        },
+    )
+}
+
+/// Capture side of the ring. Pushing only touches atomics: it never blocks,
+/// allocates or wakes the receiver, so it is safe in the audio callback.
+pub struct AudioSender {
+    producer: HeapProducer<f32>,
+    shared: Arc<Shared>,
+}
+
+impl AudioSender {
+    /// Queue samples; whatever does not fit is dropped and counted
+    pub fn push(&mut self, samples: &[f32]) {
+        let pushed = self.producer.push_slice(samples);
+        self.add_dropped((samples.len() - pushed) as u64);
+    }
+
+    /// Queue samples from an iterator, like `push`
+    pub fn push_iter(&mut self, mut samples: impl Iterator<Item = f32>) {
+        self.producer.push_iter(&mut samples);
+        self.add_dropped(samples.count() as u64);
+    }
+
+    /// Count samples lost before they reached this ring
+    pub fn add_dropped(&self, count: u64) {
+        if count > 0 {
+            self.shared.dropped.fetch_add(count, Ordering::Relaxed);
+        }
+    }
+}
+
+impl Drop for AudioSender {
+    fn drop(&mut self) {
+        self.shared.closed.store(true, Ordering::Release);
+    }
+}
+
+/// Consumer side of the ring
+pub struct AudioReceiver {
+    consumer: HeapConsumer<f32>,
+    shared: Arc<Shared>,
+    // Source and target rate when received audio is resampled
+    rates: Option<(u32, u32)>,
+}
+
+impl AudioReceiver {
+    /// Resample received audio from `source_rate` to `target_rate`
+    pub fn resampled(mut self, source_rate: u32, target_rate: u32) -> Self {
+        self.rates = (source_rate != target_rate).then_some((source_rate, target_rate));
+        self
+    }
+
+    /// Everything queued so far, without waiting
+    pub fn try_recv(&mut self) -> Option<Vec<f32>> {
+        if self.consumer.is_empty() {
+            return None;
+        }
+
+        let mut samples = vec![0.0; self.consumer.len()];
+        let len = self.consumer.pop_slice(&mut samples);
+        samples.truncate(len);
+
+        Some(match self.rates {
+            Some((source, target)) => resample(&samples, source, target),
+            None => samples,
+        })
+    }
+
+    /// Wait for audio and return everything queued so far; `None` once the
+    /// sender is gone and the ring is drained
+    pub async fn recv(&mut self) -> Option<Vec<f32>> {
+        loop {
+            // Check `closed` before draining so samples pushed just before the
+            // sender was dropped are not lost
+            let closed = self.shared.closed.load(Ordering::Acquire);
+            if let Some(samples) = self.try_recv() {
+                return Some(samples);
+            }
+            if closed {
+                return None;
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+
+    /// Samples dropped because the ring was full
+    pub fn dropped(&self) -> u64 {
+        self.shared.dropped.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_samples_that_do_not_fit() {
+        let (mut tx, mut rx) = audio_ring(4);
+        tx.push(&[0.1, 0.2, 0.3]);
+        tx.push_iter([0.4, 0.5, 0.6].into_iter());
+
+        assert_eq!(rx.try_recv(), Some(vec![0.1, 0.2, 0.3, 0.4]));
+        assert_eq!(rx.dropped(), 2);
+        assert_eq!(rx.try_recv(), None);
+    }
+
+    #[tokio::test]
+    async fn drains_before_reporting_closed() {
+        let (mut tx, mut rx) = audio_ring(16);
+        tx.push(&[0.5; 8]);
+        drop(tx);
+
+        assert_eq!(rx.recv().await, Some(vec![0.5; 8]));
+        assert_eq!(rx.recv().await, None);
+    }
+
+    #[test]
+    fn resamples_on_receive() {
+        let (mut tx, rx) = audio_ring(64);
+        let mut rx = rx.resampled(32000, 16000);
+        tx.push(&[0.25; 32]);
+
+        assert_eq!(rx.try_recv().map(|s| s.len()), Some(16));
+    }
+}
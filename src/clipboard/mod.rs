@@ -1,3 +1,20 @@
 mod wayland;
 
-pub use wayland::{set_clipboard, paste_text};
+pub use wayland::{output_text, paste_text, set_clipboard, set_primary, WaylandOutput};
+
+use async_trait::async_trait;
+
+use crate::config::OutputMode;
+use crate::error::ClipboardError;
+
+/// Destination for transcripts
+#[async_trait]
+pub trait TextOutput: Send + Sync {
+    /// Place text in the selections chosen by `output`, pasting it if requested
+    async fn output_text(
+        &self,
+        text: &str,
+        output: OutputMode,
+        auto_paste: bool,
+    ) -> Result<(), ClipboardError>;
+}
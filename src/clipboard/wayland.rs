@@ -1,13 +1,75 @@
+use async_trait::async_trait;
 use std::process::Stdio;
 use std::time::Duration;
 use tokio::process::Command;
 use tracing::{debug, warn};
 
+use crate::clipboard::TextOutput;
+use crate::config::OutputMode;
 use crate::error::ClipboardError;
 
+/// Writes transcripts with wl-copy and pastes them with wtype
+pub struct WaylandOutput;
+
+#[async_trait]
+impl TextOutput for WaylandOutput {
+    async fn output_text(
+        &self,
+        text: &str,
+        output: OutputMode,
+        auto_paste: bool,
+    ) -> Result<(), ClipboardError> {
+        output_text(text, output, auto_paste).await
+    }
+}
+
 /// Set text to clipboard using wl-copy (handles Wayland clipboard properly)
 pub async fn set_clipboard(text: &str) -> Result<(), ClipboardError> {
-    let mut child = Command::new("wl-copy")
+    wl_copy(text, false).await
+}
+
+/// Set text to the primary selection (middle-click paste) using wl-copy --primary
+pub async fn set_primary(text: &str) -> Result<(), ClipboardError> {
+    wl_copy(text, true).await
+}
+
+/// Place text in the selections chosen by `output`, pasting it if requested.
+/// Auto-paste uses Ctrl+V, so it is skipped when the clipboard is not written.
+pub async fn output_text(
+    text: &str,
+    output: OutputMode,
+    auto_paste: bool,
+) -> Result<(), ClipboardError> {
+    if output.primary() {
+        set_primary(text).await?;
+    }
+
+    if !output.clipboard() {
+        if auto_paste {
+            debug!("Skipping auto-paste: output is primary selection only");
+        }
+        return Ok(());
+    }
+
+    if auto_paste {
+        if let Err(e) = paste_text(text).await {
+            warn!("Failed to paste: {}", e);
+            // Still copy to clipboard at least
+            set_clipboard(text).await?;
+        }
+        Ok(())
+    } else {
+        set_clipboard(text).await
+    }
+}
+
+async fn wl_copy(text: &str, primary: bool) -> Result<(), ClipboardError> {
+    let mut cmd = Command::new("wl-copy");
+    if primary {
+        cmd.arg("--primary");
+    }
+
+    let mut child = cmd
         .stdin(Stdio::piped())
         .stdout(Stdio::null())
         .stderr(Stdio::piped())
@@ -42,7 +104,11 @@ pub async fn set_clipboard(text: &str) -> Result<(), ClipboardError> {
         }
     }
 
-    debug!("Text copied to clipboard ({} chars)", text.len());
+    debug!(
+        "Text copied to {} ({} chars)",
+        if primary { "primary selection" } else { "clipboard" },
+        text.len()
+    );
     Ok(())
 }
 
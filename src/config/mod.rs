@@ -2,8 +2,9 @@ mod schema;
 
 pub use schema::*;
 
-use crate::error::{ConfigError, Result};
-use std::path::PathBuf;
+use crate::audio::DevicePattern;
+use crate::error::{AudioError, ConfigError, Result};
+use std::path::{Path, PathBuf};
 use tracing::info;
 
 /// Get the configuration directory path
@@ -33,19 +34,43 @@ pub fn default_model_path() -> PathBuf {
     data_dir().join("models").join("ggml-base.bin")
 }
 
-/// Load configuration from file or return defaults
-pub fn load_config() -> Result<AppConfig> {
-    let config_path = config_dir().join("config.toml");
+/// Get the configuration file path
+pub fn config_path() -> PathBuf {
+    config_dir().join("config.toml")
+}
+
+/// Expand a leading `~` to the home directory
+pub fn expand_home(path: &Path) -> PathBuf {
+    match dirs::home_dir() {
+        Some(home) => expand_tilde(path, &home),
+        None => path.to_path_buf(),
+    }
+}
+
+fn expand_tilde(path: &Path, home: &Path) -> PathBuf {
+    match path.strip_prefix("~") {
+        Ok(rest) => home.join(rest),
+        Err(_) => path.to_path_buf(),
+    }
+}
+
+/// Load configuration from file or return defaults, along with the file it
+/// was read from. Runs before logging is set up, so the caller reports where
+/// the configuration came from.
+pub fn load_config() -> Result<(AppConfig, Option<PathBuf>)> {
+    load_config_from(config_path())
+}
 
-    if config_path.exists() {
-        info!("Loading configuration from {:?}", config_path);
-        let content = std::fs::read_to_string(&config_path).map_err(ConfigError::ReadError)?;
-        let config: AppConfig = toml::from_str(&content).map_err(ConfigError::ParseError)?;
-        Ok(config)
-    } else {
-        info!("No configuration file found, using defaults");
-        Ok(AppConfig::default())
+fn load_config_from(config_path: PathBuf) -> Result<(AppConfig, Option<PathBuf>)> {
+    if !config_path.exists() {
+        return Ok((AppConfig::default(), None));
     }
+
+    let content = std::fs::read_to_string(&config_path).map_err(ConfigError::ReadError)?;
+    let mut config: AppConfig = toml::from_str(&content).map_err(ConfigError::ParseError)?;
+    config.validate()?;
+    config.expand_paths();
+    Ok((config, Some(config_path)))
 }
 
 /// Save configuration to file
@@ -79,7 +104,99 @@ pub fn init_dirs() -> Result<()> {
     Ok(())
 }
 
+/// Beam widths whisper.cpp supports (it runs at most 8 decoders)
+const MAX_BEAM_SIZE: u32 = 8;
+
+/// Largest thread count or segment length whisper.cpp accepts (a C `int`)
+const MAX_WHISPER_INT: u32 = i32::MAX as u32;
+
+impl WhisperLocalConfig {
+    /// Check the decoding parameters are in range
+    pub fn validate(&self) -> std::result::Result<(), ConfigError> {
+        let invalid = |message: String| Err(ConfigError::ValidationError(message));
+
+        if !(1..=MAX_BEAM_SIZE).contains(&self.beam_size) {
+            return invalid(format!(
+                "whisper-local beam_size must be between 1 and {}, got {}",
+                MAX_BEAM_SIZE, self.beam_size
+            ));
+        }
+        for (name, value) in [
+            ("threads", self.threads),
+            ("max_segment_len", self.max_segment_len),
+        ] {
+            if value > MAX_WHISPER_INT {
+                return invalid(format!(
+                    "whisper-local {} must be at most {}, got {}",
+                    name, MAX_WHISPER_INT, value
+                ));
+            }
+        }
+        for (name, value) in [
+            ("temperature", self.temperature),
+            ("temperature_inc", self.temperature_inc),
+            ("no_speech_threshold", self.no_speech_threshold),
+        ] {
+            if !(0.0..=1.0).contains(&value) {
+                return invalid(format!(
+                    "whisper-local {} must be between 0.0 and 1.0, got {}",
+                    name, value
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl HistoryConfig {
+    /// Get the effective history file path
+    pub fn path(&self) -> PathBuf {
+        self.file
+            .clone()
+            .unwrap_or_else(|| data_dir().join("history.jsonl"))
+    }
+}
+
 impl AppConfig {
+    /// Check settings that deserialize fine but are out of range
+    pub fn validate(&self) -> Result<()> {
+        self.providers.whisper_local.validate()?;
+        for pattern in &self.audio.input_devices {
+            if let Err(AudioError::ConfigError(message)) = DevicePattern::parse(pattern) {
+                let message = format!("audio.input_devices: {}", message);
+                return Err(ConfigError::ValidationError(message).into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Expand `~` in the configured file and directory paths
+    fn expand_paths(&mut self) {
+        let expand = |path: &mut PathBuf| *path = expand_home(path);
+        let sounds = &mut self.sounds;
+        [
+            &mut self.providers.whisper_local.model_path,
+            &mut self.hotkey.socket_path,
+            &mut self.history.file,
+            &mut self.logging.file,
+            &mut sounds.start,
+            &mut sounds.stop,
+            &mut sounds.success,
+            &mut sounds.error,
+            &mut sounds.cancelled,
+        ]
+        .into_iter()
+        .flatten()
+        .for_each(expand);
+        self.hotkey.devices.iter_mut().for_each(expand);
+
+        // Also "embedded" or "system"; only a path can start with `~`
+        let theme = &mut self.tray.icon_theme;
+        *theme = expand_home(Path::new(theme.as_str()))
+            .to_string_lossy()
+            .into_owned();
+    }
+
     /// Get the effective socket path
     pub fn socket_path(&self) -> PathBuf {
         self.hotkey
@@ -124,3 +241,127 @@ impl AppConfig {
             .or_else(|| std::env::var("DEEPGRAM_API_KEY").ok())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_the_default_decoding() {
+        assert!(WhisperLocalConfig::default().validate().is_ok());
+    }
+
+    #[test]
+    fn rejects_out_of_range_decoding() {
+        let cases: [fn(&mut WhisperLocalConfig); 6] = [
+            |c| c.beam_size = 0,
+            |c| c.beam_size = MAX_BEAM_SIZE + 1,
+            |c| c.temperature = 1.5,
+            |c| c.no_speech_threshold = -0.1,
+            |c| c.threads = MAX_WHISPER_INT + 1,
+            |c| c.max_segment_len = u32::MAX,
+        ];
+        for set in cases {
+            let mut config = WhisperLocalConfig::default();
+            set(&mut config);
+            assert!(config.validate().is_err(), "{:?}", config);
+        }
+
+        let mut config = WhisperLocalConfig::default();
+        config.threads = MAX_WHISPER_INT;
+        config.max_segment_len = MAX_WHISPER_INT;
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn rejects_out_of_range_files() {
+        let config: AppConfig =
+            toml::from_str("[providers.whisper-local]\nthreads = 4294967295\n").unwrap();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_device_regexes() {
+        let config: AppConfig =
+            toml::from_str("[audio]\ninput_devices = [\"USB\", \"/^Blue (Yeti/\"]\n").unwrap();
+        let error = config.validate().unwrap_err().to_string();
+        assert!(error.contains("/^Blue (Yeti/"), "{}", error);
+
+        let config: AppConfig =
+            toml::from_str("[audio]\ninput_devices = [\"USB\", \"/^Blue (Yeti)/\"]\n").unwrap();
+        assert!(config.validate().is_ok());
+    }
+
+    /// Config file path unique to this test run
+    fn temp_config(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "super-whisper-{}-{}.toml",
+            name,
+            std::process::id()
+        ))
+    }
+
+    #[test]
+    fn loads_defaults_without_a_file() {
+        let (config, path) = load_config_from(temp_config("missing")).unwrap();
+        assert_eq!(path, None);
+        assert_eq!(config.general.language, "auto");
+    }
+
+    #[test]
+    fn reports_the_loaded_file() {
+        let file = temp_config("loaded");
+        std::fs::write(&file, "[general]\nlanguage = \"de\"\n").unwrap();
+
+        let loaded = load_config_from(file.clone());
+        std::fs::remove_file(&file).unwrap();
+        let (config, path) = loaded.unwrap();
+        assert_eq!(path, Some(file));
+        assert_eq!(config.general.language, "de");
+    }
+
+    #[test]
+    fn expands_a_leading_tilde() {
+        let home = Path::new("/home/user");
+        for (path, expanded) in [
+            (
+                "~/.local/share/start.wav",
+                "/home/user/.local/share/start.wav",
+            ),
+            ("~", "/home/user"),
+            ("/usr/share/sounds/start.wav", "/usr/share/sounds/start.wav"),
+            ("sounds/~/start.wav", "sounds/~/start.wav"),
+            // Other users' homes are not looked up
+            ("~other/start.wav", "~other/start.wav"),
+            ("embedded", "embedded"),
+        ] {
+            assert_eq!(expand_tilde(Path::new(path), home), Path::new(expanded));
+        }
+    }
+
+    #[test]
+    fn expands_tilde_in_loaded_paths() {
+        let Some(home) = dirs::home_dir() else {
+            return;
+        };
+        let file = temp_config("tilde");
+        std::fs::write(
+            &file,
+            "[sounds]\nstart = \"~/sounds/start.wav\"\n\
+             [history]\nfile = \"~/history.jsonl\"\n\
+             [tray]\nicon_theme = \"~/icons\"\n\
+             [logging]\nfile = \"whisper.log\"\n",
+        )
+        .unwrap();
+
+        let loaded = load_config_from(file.clone());
+        std::fs::remove_file(&file).unwrap();
+        let (config, _) = loaded.unwrap();
+        assert_eq!(config.sounds.start, Some(home.join("sounds/start.wav")));
+        assert_eq!(config.history.file, Some(home.join("history.jsonl")));
+        assert_eq!(config.tray.icon_theme, home.join("icons").to_string_lossy());
+        // Relative paths are left to the setting that uses them
+        assert_eq!(config.logging.file, Some(PathBuf::from("whisper.log")));
+        assert_eq!(config.sounds.stop, None);
+    }
+}
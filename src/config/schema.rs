@@ -9,6 +9,10 @@ pub struct AppConfig {
     pub audio: AudioConfig,
     pub hotkey: HotkeyConfig,
     pub tray: TrayConfig,
+    pub sounds: SoundsConfig,
+    pub notifications: NotificationsConfig,
+    pub history: HistoryConfig,
+    pub chunking: ChunkingConfig,
     pub providers: ProvidersConfig,
     pub logging: LoggingConfig,
 }
@@ -20,6 +24,10 @@ impl Default for AppConfig {
             audio: AudioConfig::default(),
             hotkey: HotkeyConfig::default(),
             tray: TrayConfig::default(),
+            sounds: SoundsConfig::default(),
+            notifications: NotificationsConfig::default(),
+            history: HistoryConfig::default(),
+            chunking: ChunkingConfig::default(),
             providers: ProvidersConfig::default(),
             logging: LoggingConfig::default(),
         }
@@ -37,6 +45,8 @@ pub struct GeneralConfig {
     pub audio_feedback: bool,
     /// Auto-paste after transcription
     pub auto_paste: bool,
+    /// Where to place the transcript: clipboard, primary, or both
+    pub output: OutputMode,
 }
 
 impl Default for GeneralConfig {
@@ -46,6 +56,7 @@ impl Default for GeneralConfig {
             language: "auto".to_string(),
             audio_feedback: true,
             auto_paste: true,
+            output: OutputMode::Clipboard,
         }
     }
 }
@@ -59,6 +70,26 @@ pub enum ProviderType {
     Deepgram,
 }
 
+impl ProviderType {
+    pub const ALL: [ProviderType; 4] = [
+        ProviderType::WhisperLocal,
+        ProviderType::OpenAI,
+        ProviderType::Groq,
+        ProviderType::Deepgram,
+    ];
+}
+
+impl std::str::FromStr for ProviderType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ProviderType::ALL
+            .into_iter()
+            .find(|p| p.to_string() == s.trim().to_lowercase())
+            .ok_or_else(|| format!("Unknown provider: {}", s))
+    }
+}
+
 impl std::fmt::Display for ProviderType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -70,11 +101,61 @@ impl std::fmt::Display for ProviderType {
     }
 }
 
+/// Selection(s) the transcript is written to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OutputMode {
+    /// Regular clipboard (Ctrl+V)
+    Clipboard,
+    /// Primary selection (middle-click)
+    Primary,
+    /// Both clipboard and primary selection
+    Both,
+}
+
+impl OutputMode {
+    /// Whether the regular clipboard is written
+    pub fn clipboard(self) -> bool {
+        matches!(self, OutputMode::Clipboard | OutputMode::Both)
+    }
+
+    /// Whether the primary selection is written
+    pub fn primary(self) -> bool {
+        matches!(self, OutputMode::Primary | OutputMode::Both)
+    }
+}
+
+impl std::fmt::Display for OutputMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OutputMode::Clipboard => write!(f, "clipboard"),
+            OutputMode::Primary => write!(f, "primary"),
+            OutputMode::Both => write!(f, "both"),
+        }
+    }
+}
+
+impl std::str::FromStr for OutputMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "clipboard" => Ok(OutputMode::Clipboard),
+            "primary" => Ok(OutputMode::Primary),
+            "both" => Ok(OutputMode::Both),
+            other => Err(format!("Unknown output mode: {}", other)),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
 pub struct AudioConfig {
     /// Input device name (empty = default)
     pub input_device: Option<String>,
+    /// Ordered device preferences (substring or /regex/), tried when
+    /// `input_device` is unset or unavailable
+    pub input_devices: Vec<String>,
     /// Sample rate (whisper requires 16000)
     pub sample_rate: u32,
     /// Silence detection threshold (0.0 - 1.0)
@@ -83,30 +164,120 @@ pub struct AudioConfig {
     pub silence_timeout: f32,
     /// Maximum recording duration (seconds)
     pub max_duration: u32,
+    /// What happens when `max_duration` is reached
+    pub on_max_duration: MaxDurationAction,
+    /// Audio kept from before a recording starts (milliseconds, 0 = disabled).
+    /// Keeps the microphone open while idle.
+    pub pre_roll_ms: u32,
+    /// How multi-channel input is reduced to mono
+    pub downmix: DownmixMode,
+    /// Channel recorded with `downmix = "channel"` (0-based)
+    pub downmix_channel: u16,
+    /// Device buffer size in frames (0 = backend default)
+    pub buffer_size: u32,
+    /// High-pass filter recordings to remove rumble
+    pub highpass: bool,
+    /// High-pass cutoff frequency (Hz)
+    pub highpass_hz: f32,
+    /// Spectral noise suppression of steady background noise
+    pub noise_suppression: bool,
+    /// Normalize the recording's peak level
+    pub normalize: bool,
+    /// Peak level to normalize to (dBFS)
+    pub normalize_peak_db: f32,
+    /// Maximum amplification when normalizing (dB)
+    pub max_gain_db: f32,
 }
 
 impl Default for AudioConfig {
     fn default() -> Self {
         Self {
             input_device: None,
+            input_devices: Vec::new(),
             sample_rate: 16000,
             silence_threshold: 0.01,
             silence_timeout: 2.0,
             max_duration: 300,
+            on_max_duration: MaxDurationAction::Stop,
+            pre_roll_ms: 0,
+            downmix: DownmixMode::Average,
+            downmix_channel: 0,
+            buffer_size: 0,
+            highpass: false,
+            highpass_hz: 80.0,
+            noise_suppression: false,
+            normalize: false,
+            normalize_peak_db: -1.0,
+            max_gain_db: 20.0,
         }
     }
 }
 
+/// Behaviour when a recording reaches `max_duration`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MaxDurationAction {
+    /// Stop and transcribe
+    Stop,
+    /// Transcribe the audio so far and keep recording
+    Rollover,
+}
+
+/// Mono downmix of multi-channel input devices
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DownmixMode {
+    /// Average all channels
+    Average,
+    /// Keep a single channel (`downmix_channel`)
+    Channel,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
 pub struct HotkeyConfig {
     /// IPC socket path (default: $XDG_RUNTIME_DIR/super-whisper.sock)
     pub socket_path: Option<PathBuf>,
+    /// Push-to-talk presses shorter than this are cancelled (milliseconds)
+    pub ptt_min_hold_ms: u64,
+    /// Keep recording this long after push-to-talk release (milliseconds)
+    pub ptt_tail_ms: u64,
+    /// Built-in global hotkey listener
+    pub backend: HotkeyBackend,
+    /// Key combination that toggles recording, e.g. "Super+B"
+    pub toggle: Option<String>,
+    /// Key combination to hold while talking
+    pub push_to_talk: Option<String>,
+    /// Key combination that cancels recording or transcription
+    pub cancel: Option<String>,
+    /// evdev devices to read (default: every keyboard in /dev/input)
+    pub devices: Vec<PathBuf>,
+}
+
+/// Where global hotkeys come from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum HotkeyBackend {
+    /// No built-in listener, use compositor binds with `trigger`
+    None,
+    /// Read key events from /dev/input (needs the input group)
+    Evdev,
+    /// org.freedesktop.portal.GlobalShortcuts
+    Portal,
 }
 
 impl Default for HotkeyConfig {
     fn default() -> Self {
-        Self { socket_path: None }
+        Self {
+            socket_path: None,
+            ptt_min_hold_ms: 300,
+            ptt_tail_ms: 250,
+            backend: HotkeyBackend::None,
+            toggle: None,
+            push_to_talk: None,
+            cancel: None,
+            devices: Vec::new(),
+        }
     }
 }
 
@@ -117,6 +288,8 @@ pub struct TrayConfig {
     pub enabled: bool,
     /// Icon theme (embedded, system, or path)
     pub icon_theme: String,
+    /// Languages offered in the tray menu
+    pub languages: Vec<String>,
 }
 
 impl Default for TrayConfig {
@@ -124,6 +297,120 @@ impl Default for TrayConfig {
         Self {
             enabled: true,
             icon_theme: "embedded".to_string(),
+            languages: ["auto", "en", "es", "fr", "de", "pt"]
+                .iter()
+                .map(|l| l.to_string())
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HistoryConfig {
+    /// Save transcripts to the history file
+    pub enabled: bool,
+    /// History file (default: data directory's history.jsonl)
+    pub file: Option<PathBuf>,
+    /// Number of recent transcripts kept in memory (tray menu)
+    pub recent: usize,
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            file: None,
+            recent: 5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SoundsConfig {
+    /// Playback volume (0.0 - 1.0)
+    pub volume: f32,
+    /// Turn the recording down while the start cue plays; overlapping speech
+    /// is kept, attenuated
+    pub duck: bool,
+    /// Custom WAV file for the start cue (default: embedded)
+    pub start: Option<PathBuf>,
+    /// Custom WAV file for the stop cue
+    pub stop: Option<PathBuf>,
+    /// Custom WAV file for the success cue
+    pub success: Option<PathBuf>,
+    /// Custom WAV file for the error cue
+    pub error: Option<PathBuf>,
+    /// Custom WAV file for the cancelled cue
+    pub cancelled: Option<PathBuf>,
+}
+
+impl Default for SoundsConfig {
+    fn default() -> Self {
+        Self {
+            volume: 0.5,
+            duck: true,
+            start: None,
+            stop: None,
+            success: None,
+            error: None,
+            cancelled: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NotificationsConfig {
+    /// Enable desktop notifications
+    pub enabled: bool,
+    /// Notify when recording or transcription fails
+    pub errors: bool,
+    /// Notify about recording problems: no input, dropped audio, or the
+    /// maximum length being reached
+    pub warnings: bool,
+    /// Notify with the transcript text (with a "Copy" action)
+    pub transcripts: bool,
+    /// Expiration timeout in milliseconds (-1 = server default, 0 = never)
+    pub timeout_ms: i32,
+}
+
+impl Default for NotificationsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            errors: true,
+            warnings: true,
+            transcripts: false,
+            timeout_ms: -1,
+        }
+    }
+}
+
+/// Splitting long recordings into separately transcribed chunks
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChunkingConfig {
+    /// Split recordings longer than `max_chunk_secs`
+    pub enabled: bool,
+    /// Upper bound for a chunk (seconds); cuts are made at the quietest point
+    /// of the chunk's second half
+    pub max_chunk_secs: u32,
+    /// Overlap between chunks when no silence is found to cut at
+    /// (milliseconds); repeated words are removed when stitching
+    pub overlap_ms: u32,
+    /// Chunks transcribed concurrently by cloud providers
+    pub parallel_requests: usize,
+}
+
+impl Default for ChunkingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_chunk_secs: 60,
+            overlap_ms: 1000,
+            parallel_requests: 4,
         }
     }
 }
@@ -161,6 +448,24 @@ pub struct WhisperLocalConfig {
     pub use_gpu: bool,
     /// Number of threads (0 = auto)
     pub threads: u32,
+    /// Beam search width (1 = greedy decoding)
+    pub beam_size: u32,
+    /// Initial sampling temperature (0.0 = deterministic)
+    pub temperature: f32,
+    /// Temperature step when a decode fails and is retried (0.0 = no retries)
+    pub temperature_inc: f32,
+    /// Segments above this no-speech probability are treated as silence
+    pub no_speech_threshold: f32,
+    /// Suppress blank output at the start of a segment
+    pub suppress_blank: bool,
+    /// Suppress non-speech tokens (music notes, speaker tags, ...)
+    pub suppress_non_speech: bool,
+    /// Maximum segment length in characters (0 = no limit)
+    pub max_segment_len: u32,
+    /// Force the transcript into a single segment
+    pub single_segment: bool,
+    /// Guard against phantom and looping segments
+    pub hallucination_filter: HallucinationFilterConfig,
 }
 
 impl Default for WhisperLocalConfig {
@@ -171,10 +476,71 @@ impl Default for WhisperLocalConfig {
             model: "base".to_string(),  // multilingual model
             use_gpu: true,
             threads: 0,
+            beam_size: 1,
+            temperature: 0.0,
+            temperature_inc: 0.2,
+            no_speech_threshold: 0.6,
+            suppress_blank: true,
+            suppress_non_speech: false,
+            max_segment_len: 0,
+            single_segment: false,
+            hallucination_filter: HallucinationFilterConfig::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HallucinationFilterConfig {
+    pub enabled: bool,
+    /// What to do with a suspect segment
+    pub action: HallucinationAction,
+    /// Segments whisper rates as more likely than this to be non-speech are
+    /// suspect, when it was also unsure of their words (`min_avg_logprob`)
+    pub max_no_speech_prob: f32,
+    /// Average token log probability below which whisper counts as unsure of
+    /// a segment's words
+    pub min_avg_logprob: f32,
+    /// Segments more compressible than this are repeating themselves
+    pub max_compression_ratio: f32,
+    /// Known hallucinations, matched against whole segments ignoring case
+    /// and punctuation. Only applied to segments past one of the thresholds
+    /// above, or the last segment of a recording.
+    pub blocklist: Vec<String>,
+}
+
+impl Default for HallucinationFilterConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            action: HallucinationAction::Drop,
+            max_no_speech_prob: 0.6,
+            min_avg_logprob: -1.0,
+            max_compression_ratio: 2.4,
+            blocklist: [
+                "Thank you.",
+                "Thanks for watching!",
+                "Thank you for watching.",
+                "Please subscribe to my channel.",
+                "Subtitles by the Amara.org community",
+            ]
+            .into_iter()
+            .map(String::from)
+            .collect(),
         }
     }
 }
 
+/// Handling of segments the hallucination filter catches
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum HallucinationAction {
+    /// Remove the segment from the transcript
+    Drop,
+    /// Keep the segment, but log it and mark the history entry
+    Flag,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
 pub struct OpenAIConfig {
@@ -185,6 +551,8 @@ pub struct OpenAIConfig {
     pub model: String,
     /// API endpoint
     pub endpoint: String,
+    /// Audio format sent to the API
+    pub upload_format: AudioFormat,
 }
 
 impl Default for OpenAIConfig {
@@ -194,6 +562,7 @@ impl Default for OpenAIConfig {
             api_key: None,
             model: "whisper-1".to_string(),
             endpoint: "https://api.openai.com/v1/audio/transcriptions".to_string(),
+            upload_format: AudioFormat::Wav,
         }
     }
 }
@@ -208,6 +577,8 @@ pub struct GroqConfig {
     pub model: String,
     /// API endpoint
     pub endpoint: String,
+    /// Audio format sent to the API
+    pub upload_format: AudioFormat,
 }
 
 impl Default for GroqConfig {
@@ -217,6 +588,7 @@ impl Default for GroqConfig {
             api_key: None,
             model: "whisper-large-v3".to_string(),
             endpoint: "https://api.groq.com/openai/v1/audio/transcriptions".to_string(),
+            upload_format: AudioFormat::Wav,
         }
     }
 }
@@ -231,6 +603,8 @@ pub struct DeepgramConfig {
     pub model: String,
     /// Features to enable
     pub features: Vec<String>,
+    /// Audio format sent to the API
+    pub upload_format: AudioFormat,
 }
 
 impl Default for DeepgramConfig {
@@ -240,17 +614,34 @@ impl Default for DeepgramConfig {
             api_key: None,
             model: "nova-2".to_string(),
             features: vec!["punctuate".to_string(), "smart_format".to_string()],
+            upload_format: AudioFormat::Wav,
         }
     }
 }
 
+/// Audio format uploaded to cloud providers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AudioFormat {
+    /// Uncompressed 16-bit PCM (default)
+    Wav,
+    /// Lossless, roughly half the size of WAV
+    Flac,
+    /// Lossy Opus in Ogg, smallest (needs the `opus` feature)
+    Opus,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
 pub struct LoggingConfig {
-    /// Log level: trace, debug, info, warn, error
+    /// Log level: trace, debug, info, warn, error (or a RUST_LOG-style directive)
     pub level: String,
-    /// Log to file
+    /// Log to file (relative paths are placed under the data directory's logs/)
     pub file: Option<PathBuf>,
+    /// Log file rotation: hourly, daily, never
+    pub rotation: LogRotation,
+    /// Number of rotated log files to keep
+    pub max_files: usize,
 }
 
 impl Default for LoggingConfig {
@@ -258,6 +649,16 @@ impl Default for LoggingConfig {
         Self {
             level: "info".to_string(),
             file: None,
+            rotation: LogRotation::Daily,
+            max_files: 7,
         }
     }
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LogRotation {
+    Hourly,
+    Daily,
+    Never,
+}
@@ -21,6 +21,12 @@ pub enum AppError {
     #[error("Clipboard error: {0}")]
     Clipboard(#[from] ClipboardError),
 
+    #[error("Notification error: {0}")]
+    Notification(#[from] NotificationError),
+
+    #[error("Hotkey error: {0}")]
+    Hotkey(#[from] HotkeyError),
+
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
 
@@ -67,6 +73,9 @@ pub enum AudioError {
 
     #[error("WAV encoding error: {0}")]
     WavError(String),
+
+    #[error("Audio encoding error: {0}")]
+    EncodeError(String),
 }
 
 /// Speech-to-text provider errors
@@ -136,4 +145,33 @@ pub enum ClipboardError {
     WtypeNotFound,
 }
 
+/// Desktop notification errors
+#[derive(Error, Debug)]
+pub enum NotificationError {
+    #[error("D-Bus error: {0}")]
+    DbusError(String),
+
+    #[error("No notification server running")]
+    ServerUnavailable,
+}
+
+/// Global hotkey errors
+#[derive(Error, Debug)]
+pub enum HotkeyError {
+    #[error("Invalid key binding: {0}")]
+    InvalidBinding(String),
+
+    #[error("No readable keyboard in /dev/input (is the user in the input group?)")]
+    NoDevices,
+
+    #[error("Input device error: {0}")]
+    DeviceError(String),
+
+    #[error("D-Bus error: {0}")]
+    DbusError(String),
+
+    #[error("GlobalShortcuts portal error: {0}")]
+    PortalError(String),
+}
+
 pub type Result<T> = std::result::Result<T, AppError>;
@@ -0,0 +1,139 @@
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{debug, warn};
+
+use crate::config::HistoryConfig;
+
+/// A finished transcription
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// Unix timestamp (seconds)
+    pub timestamp: u64,
+    /// Transcribed text
+    pub text: String,
+    /// Provider that produced the transcript
+    pub provider: String,
+    /// Recording length in milliseconds
+    pub duration_ms: u64,
+    /// Audio was dropped during capture, so the transcript may have holes
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub gaps: bool,
+    /// Part of the transcript looked like a hallucination
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub suspect: bool,
+}
+
+impl HistoryEntry {
+    pub fn new(text: impl Into<String>, provider: impl Into<String>, duration_ms: u64) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+
+        Self {
+            timestamp,
+            text: text.into(),
+            provider: provider.into(),
+            duration_ms,
+            gaps: false,
+            suspect: false,
+        }
+    }
+
+    pub fn with_gaps(mut self, gaps: bool) -> Self {
+        self.gaps = gaps;
+        self
+    }
+
+    pub fn with_suspect(mut self, suspect: bool) -> Self {
+        self.suspect = suspect;
+        self
+    }
+}
+
+/// Transcript history: the most recent entries in memory, all entries
+/// appended to a JSON lines file
+pub struct History {
+    path: Option<PathBuf>,
+    recent: VecDeque<HistoryEntry>,
+    limit: usize,
+}
+
+impl History {
+    /// Load the most recent entries from the history file (if enabled)
+    pub fn load(config: &HistoryConfig) -> Self {
+        let path = config.enabled.then(|| config.path());
+        let limit = config.recent.max(1);
+        let mut recent = VecDeque::with_capacity(limit);
+
+        if let Some(path) = path.as_ref().filter(|p| p.exists()) {
+            match std::fs::read_to_string(path) {
+                Ok(content) => {
+                    for line in content.lines().filter(|l| !l.trim().is_empty()) {
+                        match serde_json::from_str::<HistoryEntry>(line) {
+                            Ok(entry) => {
+                                if recent.len() == limit {
+                                    recent.pop_front();
+                                }
+                                recent.push_back(entry);
+                            }
+                            Err(e) => debug!("Skipping invalid history line: {}", e),
+                        }
+                    }
+                }
+                Err(e) => warn!("Failed to read history {:?}: {}", path, e),
+            }
+        }
+
+        Self {
+            path,
+            recent,
+            limit,
+        }
+    }
+
+    /// Record a transcript
+    pub fn push(&mut self, entry: HistoryEntry) {
+        if let Some(path) = &self.path {
+            if let Err(e) = append(path, &entry) {
+                warn!("Failed to write history {:?}: {}", path, e);
+            }
+        }
+
+        if self.recent.len() == self.limit {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(entry);
+    }
+
+    /// Recent entries, newest first
+    pub fn recent(&self) -> Vec<HistoryEntry> {
+        self.recent.iter().rev().cloned().collect()
+    }
+
+    /// Recent entry by index (0 = newest)
+    pub fn get(&self, index: usize) -> Option<&HistoryEntry> {
+        self.recent.iter().rev().nth(index)
+    }
+
+    /// History file path, if history is persisted
+    pub fn path(&self) -> Option<&PathBuf> {
+        self.path.as_ref()
+    }
+}
+
+fn append(path: &PathBuf, entry: &HistoryEntry) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let line = serde_json::to_string(entry).map_err(std::io::Error::other)?;
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    writeln!(file, "{}", line)
+}
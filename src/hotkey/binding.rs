@@ -0,0 +1,366 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::config::HotkeyConfig;
+use crate::error::HotkeyError;
+use crate::ipc::IpcCommand;
+
+/// What a hotkey does
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HotkeyAction {
+    Toggle,
+    PushToTalk,
+    Cancel,
+}
+
+impl HotkeyAction {
+    /// Stable id (also used as the portal shortcut id)
+    pub fn id(self) -> &'static str {
+        match self {
+            HotkeyAction::Toggle => "toggle",
+            HotkeyAction::PushToTalk => "push-to-talk",
+            HotkeyAction::Cancel => "cancel",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        match id {
+            "toggle" => Some(HotkeyAction::Toggle),
+            "push-to-talk" => Some(HotkeyAction::PushToTalk),
+            "cancel" => Some(HotkeyAction::Cancel),
+            _ => None,
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            HotkeyAction::Toggle => "Start or stop recording",
+            HotkeyAction::PushToTalk => "Record while held",
+            HotkeyAction::Cancel => "Cancel recording",
+        }
+    }
+
+    /// Command to send when the key goes down
+    pub fn pressed(self) -> Option<IpcCommand> {
+        match self {
+            HotkeyAction::Toggle => Some(IpcCommand::Toggle { output: None }),
+            HotkeyAction::PushToTalk => Some(IpcCommand::PttDown),
+            HotkeyAction::Cancel => Some(IpcCommand::Cancel),
+        }
+    }
+
+    /// Command to send when the key goes up
+    pub fn released(self) -> Option<IpcCommand> {
+        match self {
+            HotkeyAction::PushToTalk => Some(IpcCommand::PttUp),
+            _ => None,
+        }
+    }
+}
+
+/// Modifier keys of a binding
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Modifiers {
+    pub ctrl: bool,
+    pub alt: bool,
+    pub shift: bool,
+    pub super_: bool,
+}
+
+/// A key combination such as "Super+Shift+B"
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Binding {
+    pub modifiers: Modifiers,
+    /// Key name without modifiers, e.g. "B", "F9", "SPACE" (upper case)
+    pub key: String,
+}
+
+impl FromStr for Binding {
+    type Err = HotkeyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut modifiers = Modifiers::default();
+        let mut key = None;
+
+        for part in s.split('+').map(str::trim) {
+            match part.to_lowercase().as_str() {
+                "ctrl" | "control" => modifiers.ctrl = true,
+                "alt" => modifiers.alt = true,
+                "shift" => modifiers.shift = true,
+                "super" | "meta" | "logo" | "win" => modifiers.super_ = true,
+                "" => return Err(HotkeyError::InvalidBinding(s.to_string())),
+                _ if key.is_some() => return Err(HotkeyError::InvalidBinding(s.to_string())),
+                name => key = Some(name.to_uppercase()),
+            }
+        }
+
+        match key {
+            Some(key) => Ok(Self { modifiers, key }),
+            None => Err(HotkeyError::InvalidBinding(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let m = self.modifiers;
+        for (on, name) in [
+            (m.ctrl, "Ctrl"),
+            (m.alt, "Alt"),
+            (m.shift, "Shift"),
+            (m.super_, "Super"),
+        ] {
+            if on {
+                write!(f, "{}+", name)?;
+            }
+        }
+        write!(f, "{}", self.key)
+    }
+}
+
+/// Other names accepted for keys, mapped to the name used for the key
+/// code (`KEY_<name>` in evdev)
+const KEY_ALIASES: &[(&str, &str)] = &[
+    ("ESCAPE", "ESC"),
+    ("RETURN", "ENTER"),
+    ("DEL", "DELETE"),
+    ("INS", "INSERT"),
+    ("PGUP", "PAGEUP"),
+    ("PAGE_UP", "PAGEUP"),
+    ("PGDN", "PAGEDOWN"),
+    ("PAGE_DOWN", "PAGEDOWN"),
+    ("PRINT", "SYSRQ"),
+    ("CAPS_LOCK", "CAPSLOCK"),
+];
+
+/// Keysym names of keys whose key code name differs
+const KEYSYMS: &[(&str, &str)] = &[
+    ("SPACE", "space"),
+    ("ENTER", "Return"),
+    ("ESC", "Escape"),
+    ("TAB", "Tab"),
+    ("BACKSPACE", "BackSpace"),
+    ("DELETE", "Delete"),
+    ("INSERT", "Insert"),
+    ("HOME", "Home"),
+    ("END", "End"),
+    ("PAGEUP", "Page_Up"),
+    ("PAGEDOWN", "Page_Down"),
+    ("UP", "Up"),
+    ("DOWN", "Down"),
+    ("LEFT", "Left"),
+    ("RIGHT", "Right"),
+    ("SYSRQ", "Print"),
+    ("CAPSLOCK", "Caps_Lock"),
+    ("PAUSE", "Pause"),
+];
+
+fn lookup<'a>(table: &[(&str, &'a str)], name: &str) -> Option<&'a str> {
+    table
+        .iter()
+        .find(|(from, _)| *from == name)
+        .map(|(_, to)| *to)
+}
+
+impl Binding {
+    /// Key code name with aliases resolved, e.g. "ESC" for "Escape"
+    pub fn key_name(&self) -> &str {
+        lookup(KEY_ALIASES, &self.key).unwrap_or(&self.key)
+    }
+
+    /// Trigger in the XDG shortcuts format used by the portal ("LOGO+b")
+    pub fn portal_trigger(&self) -> String {
+        let m = self.modifiers;
+        let mut parts: Vec<String> = [
+            (m.ctrl, "CTRL"),
+            (m.alt, "ALT"),
+            (m.shift, "SHIFT"),
+            (m.super_, "LOGO"),
+        ]
+        .iter()
+        .filter(|(on, _)| *on)
+        .map(|(_, name)| name.to_string())
+        .collect();
+
+        // Keysym names: single letters are lower case, named keys as in xkb
+        let key = match self.key_name() {
+            k if k.len() == 1 => k.to_lowercase(),
+            k => lookup(KEYSYMS, k).unwrap_or(k).to_string(),
+        };
+        parts.push(key);
+        parts.join("+")
+    }
+}
+
+/// Parse the bindings declared in `[hotkey]`
+pub fn bindings(config: &HotkeyConfig) -> Result<Vec<(HotkeyAction, Binding)>, HotkeyError> {
+    [
+        (HotkeyAction::Toggle, &config.toggle),
+        (HotkeyAction::PushToTalk, &config.push_to_talk),
+        (HotkeyAction::Cancel, &config.cancel),
+    ]
+    .into_iter()
+    .filter_map(|(action, binding)| binding.as_ref().map(|b| (action, b)))
+    .map(|(action, binding)| Ok((action, binding.parse()?)))
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> Binding {
+        s.parse().unwrap()
+    }
+
+    fn modifiers(ctrl: bool, alt: bool, shift: bool, super_: bool) -> Modifiers {
+        Modifiers {
+            ctrl,
+            alt,
+            shift,
+            super_,
+        }
+    }
+
+    #[test]
+    fn parses_modifiers_and_key() {
+        assert_eq!(
+            parse("Super+B"),
+            Binding {
+                modifiers: modifiers(false, false, false, true),
+                key: "B".into(),
+            }
+        );
+        assert_eq!(
+            parse("Ctrl+Alt+Shift+Space"),
+            Binding {
+                modifiers: modifiers(true, true, true, false),
+                key: "SPACE".into(),
+            }
+        );
+        assert_eq!(
+            parse("F9"),
+            Binding {
+                modifiers: Modifiers::default(),
+                key: "F9".into(),
+            }
+        );
+    }
+
+    #[test]
+    fn parses_aliases_case_and_spacing() {
+        let super_b = parse("Super+B");
+        for s in ["meta+b", "LOGO+b", "Win + B", " super+b "] {
+            assert_eq!(parse(s), super_b, "{}", s);
+        }
+        assert_eq!(parse("control+x"), parse("Ctrl+X"));
+        // Modifier order does not matter
+        assert_eq!(parse("Shift+Ctrl+A"), parse("Ctrl+Shift+A"));
+    }
+
+    #[test]
+    fn rejects_invalid_bindings() {
+        for s in [
+            "",
+            "+",
+            "Super+",
+            "Ctrl++B",
+            "Super+Ctrl",
+            "A+B",
+            "Super+B+C",
+        ] {
+            assert!(
+                matches!(s.parse::<Binding>(), Err(HotkeyError::InvalidBinding(_))),
+                "{:?}",
+                s
+            );
+        }
+    }
+
+    #[test]
+    fn displays_in_canonical_order() {
+        assert_eq!(
+            parse("shift+super+ctrl+b").to_string(),
+            "Ctrl+Shift+Super+B"
+        );
+        assert_eq!(parse("alt+f4").to_string(), "Alt+F4");
+        let binding = parse("Super+Alt+Escape");
+        assert_eq!(parse(&binding.to_string()), binding);
+    }
+
+    #[test]
+    fn portal_triggers_use_keysym_names() {
+        assert_eq!(parse("Super+B").portal_trigger(), "LOGO+b");
+        assert_eq!(
+            parse("Ctrl+Alt+Shift+Super+Space").portal_trigger(),
+            "CTRL+ALT+SHIFT+LOGO+space"
+        );
+        assert_eq!(parse("Super+Enter").portal_trigger(), "LOGO+Return");
+        assert_eq!(parse("Super+Return").portal_trigger(), "LOGO+Return");
+        assert_eq!(parse("Esc").portal_trigger(), "Escape");
+        assert_eq!(parse("Ctrl+F9").portal_trigger(), "CTRL+F9");
+        assert_eq!(parse("Super+1").portal_trigger(), "LOGO+1");
+        assert_eq!(parse("Ctrl+PgDn").portal_trigger(), "CTRL+Page_Down");
+    }
+
+    #[test]
+    fn resolves_key_aliases() {
+        assert_eq!(parse("Super+Escape").key_name(), "ESC");
+        assert_eq!(parse("Super+Esc").key_name(), "ESC");
+        assert_eq!(parse("Return").key_name(), "ENTER");
+        assert_eq!(parse("Shift+Del").key_name(), "DELETE");
+        assert_eq!(parse("Page_Up").key_name(), "PAGEUP");
+        assert_eq!(parse("Super+B").key_name(), "B");
+        assert_eq!(parse("F9").key_name(), "F9");
+    }
+
+    #[test]
+    fn action_ids_round_trip() {
+        for action in [
+            HotkeyAction::Toggle,
+            HotkeyAction::PushToTalk,
+            HotkeyAction::Cancel,
+        ] {
+            assert_eq!(HotkeyAction::from_id(action.id()), Some(action));
+        }
+        assert_eq!(HotkeyAction::from_id("unknown"), None);
+    }
+
+    #[test]
+    fn only_push_to_talk_acts_on_release() {
+        assert_eq!(
+            HotkeyAction::PushToTalk.pressed(),
+            Some(IpcCommand::PttDown)
+        );
+        assert_eq!(HotkeyAction::PushToTalk.released(), Some(IpcCommand::PttUp));
+        assert_eq!(
+            HotkeyAction::Toggle.pressed(),
+            Some(IpcCommand::Toggle { output: None })
+        );
+        assert_eq!(HotkeyAction::Toggle.released(), None);
+        assert_eq!(HotkeyAction::Cancel.pressed(), Some(IpcCommand::Cancel));
+        assert_eq!(HotkeyAction::Cancel.released(), None);
+    }
+
+    #[test]
+    fn reads_configured_bindings() {
+        let config = HotkeyConfig {
+            toggle: Some("Super+B".into()),
+            cancel: Some("Super+Escape".into()),
+            ..Default::default()
+        };
+        assert_eq!(
+            bindings(&config).unwrap(),
+            [
+                (HotkeyAction::Toggle, parse("Super+B")),
+                (HotkeyAction::Cancel, parse("Super+Escape")),
+            ]
+        );
+
+        let config = HotkeyConfig {
+            push_to_talk: Some("Super+".into()),
+            ..Default::default()
+        };
+        assert!(bindings(&config).is_err());
+    }
+}
@@ -0,0 +1,686 @@
+use evdev::{Device, InputEventKind, Key};
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+use crate::error::HotkeyError;
+use crate::hotkey::binding::{Binding, HotkeyAction, Modifiers};
+use crate::hotkey::Dispatcher;
+
+/// evdev key event values
+const KEY_RELEASE: i32 = 0;
+const KEY_PRESS: i32 = 1;
+
+/// Where keyboards show up
+const INPUT_DIR: &str = "/dev/input";
+
+/// How often to look for keyboards plugged in after startup. Only the
+/// directory is listed; a new node is opened once to see if it is a keyboard.
+const RESCAN_INTERVAL: Duration = Duration::from_secs(2);
+
+/// A binding resolved to an evdev key code
+#[derive(Debug, Clone, Copy)]
+struct KeyBinding {
+    action: HotkeyAction,
+    modifiers: Modifiers,
+    key: Key,
+}
+
+/// A binding going down or up, dispatched once the key state is unlocked
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Hotkey {
+    Pressed(HotkeyAction),
+    Released(HotkeyAction),
+}
+
+fn dispatch(dispatcher: &Dispatcher, hotkeys: impl IntoIterator<Item = Hotkey>) {
+    for hotkey in hotkeys {
+        match hotkey {
+            Hotkey::Pressed(action) => dispatcher.pressed(action),
+            Hotkey::Released(action) => dispatcher.released(action),
+        }
+    }
+}
+
+/// Open the keyboards and start one reader thread per device, plus a thread
+/// picking up keyboards plugged in later
+pub fn start(
+    bindings: &[(HotkeyAction, Binding)],
+    devices: &[PathBuf],
+    dispatcher: Dispatcher,
+) -> Result<(), HotkeyError> {
+    let mut scanner = Scanner {
+        configured: devices.to_vec(),
+        shared: Shared {
+            bindings: key_bindings(bindings)?,
+            dispatcher,
+            state: Arc::new(Mutex::new(KeyState::default())),
+            open: Arc::new(Mutex::new(HashSet::new())),
+        },
+        skipped: HashSet::new(),
+        next_id: 0,
+    };
+
+    let errors = scanner.scan();
+    if scanner.shared.open.lock().unwrap().is_empty() {
+        return Err(errors.into_iter().next().unwrap_or(HotkeyError::NoDevices));
+    }
+    for e in errors {
+        warn!("{}", e);
+    }
+
+    std::thread::Builder::new()
+        .name("hotkey-scan".into())
+        .spawn(move || {
+            std::thread::sleep(RESCAN_INTERVAL);
+            while !scanner.shared.dispatcher.stopped() {
+                scanner.scan();
+                std::thread::sleep(RESCAN_INTERVAL);
+            }
+            debug!("Hotkey device scan stopped");
+        })
+        .map_err(|e| HotkeyError::DeviceError(e.to_string()))?;
+
+    Ok(())
+}
+
+/// Resolve bindings to evdev key codes
+fn key_bindings(bindings: &[(HotkeyAction, Binding)]) -> Result<Vec<KeyBinding>, HotkeyError> {
+    bindings
+        .iter()
+        .map(|(action, binding)| {
+            let key = Key::from_str(&format!("KEY_{}", binding.key_name()))
+                .map_err(|_| HotkeyError::InvalidBinding(binding.to_string()))?;
+            Ok(KeyBinding {
+                action: *action,
+                modifiers: binding.modifiers,
+                key,
+            })
+        })
+        .collect()
+}
+
+/// What the reader threads share
+#[derive(Clone)]
+struct Shared {
+    bindings: Vec<KeyBinding>,
+    dispatcher: Dispatcher,
+    // One state for all keyboards: a modifier held on one node applies to
+    // keys on another (many keyboards expose several nodes)
+    state: Arc<Mutex<KeyState>>,
+    // Nodes with a running reader
+    open: Arc<Mutex<HashSet<PathBuf>>>,
+}
+
+/// Starts readers for the configured devices, or every keyboard in
+/// /dev/input, as they appear
+struct Scanner {
+    configured: Vec<PathBuf>,
+    shared: Shared,
+    // Nodes that are not keyboards or cannot be opened, until they disappear
+    skipped: HashSet<PathBuf>,
+    next_id: usize,
+}
+
+impl Scanner {
+    /// Start readers for keyboards that are not read yet; returns why
+    /// configured devices could not be opened
+    fn scan(&mut self) -> Vec<HotkeyError> {
+        let candidates = if self.configured.is_empty() {
+            event_nodes()
+        } else {
+            self.configured.clone()
+        };
+        self.skipped.retain(|path| candidates.contains(path));
+
+        let mut errors = Vec::new();
+        for path in candidates {
+            if self.skipped.contains(&path) || self.shared.open.lock().unwrap().contains(&path) {
+                continue;
+            }
+            match Device::open(&path) {
+                Ok(device) if !self.configured.is_empty() || is_keyboard(&device) => {
+                    if let Err(e) = self.spawn_reader(path, device) {
+                        errors.push(e);
+                    }
+                }
+                Ok(_) => {
+                    self.skipped.insert(path);
+                }
+                Err(e) if self.configured.is_empty() => {
+                    debug!("Cannot open {:?}: {}", path, e);
+                    self.skipped.insert(path);
+                }
+                // Retried on the next scan, the device may be plugged in later
+                Err(e) => errors.push(HotkeyError::DeviceError(format!("{:?}: {}", path, e))),
+            }
+        }
+        errors
+    }
+
+    fn spawn_reader(&mut self, path: PathBuf, device: Device) -> Result<(), HotkeyError> {
+        info!(
+            "Listening for hotkeys on {:?} ({})",
+            path,
+            device.name().unwrap_or("unknown")
+        );
+
+        let id = self.next_id;
+        self.next_id += 1;
+        self.shared.open.lock().unwrap().insert(path.clone());
+
+        let shared = self.shared.clone();
+        let thread_path = path.clone();
+        std::thread::Builder::new()
+            .name("hotkey-evdev".into())
+            .spawn(move || read_device(thread_path, device, id, shared))
+            .map(|_| ())
+            .map_err(|e| {
+                self.shared.open.lock().unwrap().remove(&path);
+                HotkeyError::DeviceError(e.to_string())
+            })
+    }
+}
+
+/// Event nodes currently in /dev/input
+fn event_nodes() -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(INPUT_DIR) else {
+        return Vec::new();
+    };
+    let mut nodes: Vec<PathBuf> = entries
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with("event"))
+        })
+        .collect();
+    nodes.sort();
+    nodes
+}
+
+fn is_keyboard(device: &Device) -> bool {
+    device.supported_keys().is_some_and(|keys| {
+        keys.contains(Key::KEY_A) && keys.contains(Key::KEY_Z) && keys.contains(Key::KEY_ENTER)
+    })
+}
+
+/// Read one keyboard until it goes away; `id` tells its keys apart from
+/// other keyboards'
+fn read_device(path: PathBuf, mut device: Device, id: usize, shared: Shared) {
+    while !shared.dispatcher.stopped() {
+        let events = match device.fetch_events() {
+            Ok(events) => events,
+            Err(e) => {
+                info!("Stopped reading hotkeys from {:?}: {}", path, e);
+                break;
+            }
+        };
+
+        let mut hotkeys = Vec::new();
+        {
+            let mut state = shared.state.lock().unwrap();
+            for event in events {
+                if let InputEventKind::Key(key) = event.kind() {
+                    hotkeys.extend(state.handle(id, key, event.value(), &shared.bindings));
+                }
+            }
+        }
+        dispatch(&shared.dispatcher, hotkeys);
+    }
+
+    let released = shared.state.lock().unwrap().forget(id);
+    dispatch(&shared.dispatcher, released);
+    // Picked up again by the next scan if it comes back
+    shared.open.lock().unwrap().remove(&path);
+}
+
+/// Modifier and held-binding state of all keyboards
+#[derive(Debug, Default)]
+struct KeyState {
+    // Modifier keys that are down, with the keyboard they are down on
+    modifiers: Vec<(usize, Key)>,
+    // Bindings whose key is currently held, so releases reach the same action
+    held: Vec<(usize, KeyBinding)>,
+}
+
+impl KeyState {
+    /// Track a key event; returns the binding it presses or releases
+    fn handle(
+        &mut self,
+        device: usize,
+        key: Key,
+        value: i32,
+        bindings: &[KeyBinding],
+    ) -> Option<Hotkey> {
+        if is_modifier(key) {
+            self.modifiers.retain(|&held| held != (device, key));
+            if value != KEY_RELEASE {
+                self.modifiers.push((device, key));
+            }
+            return None;
+        }
+
+        match value {
+            KEY_PRESS => {
+                let modifiers = self.modifiers();
+                if let Some(binding) = bindings
+                    .iter()
+                    .find(|b| b.key == key && b.modifiers == modifiers)
+                {
+                    debug!("Hotkey {:?} pressed", binding.action);
+                    self.held.push((device, *binding));
+                    return Some(Hotkey::Pressed(binding.action));
+                }
+            }
+            KEY_RELEASE => {
+                if let Some(index) = self
+                    .held
+                    .iter()
+                    .position(|(d, b)| *d == device && b.key == key)
+                {
+                    let (_, binding) = self.held.remove(index);
+                    debug!("Hotkey {:?} released", binding.action);
+                    return Some(Hotkey::Released(binding.action));
+                }
+            }
+            // Auto-repeat
+            _ => {}
+        }
+        None
+    }
+
+    /// Modifiers held on any keyboard
+    fn modifiers(&self) -> Modifiers {
+        let mut modifiers = Modifiers::default();
+        for &(_, key) in &self.modifiers {
+            set_modifier(&mut modifiers, key);
+        }
+        modifiers
+    }
+
+    /// A keyboard went away: its modifiers no longer count and bindings held
+    /// on it are released, so push-to-talk does not stay down
+    fn forget(&mut self, device: usize) -> Vec<Hotkey> {
+        self.modifiers.retain(|(d, _)| *d != device);
+        let (released, held): (Vec<_>, _) = std::mem::take(&mut self.held)
+            .into_iter()
+            .partition(|(d, _)| *d == device);
+        self.held = held;
+        released
+            .into_iter()
+            .map(|(_, binding)| {
+                debug!("Hotkey {:?} released (keyboard removed)", binding.action);
+                Hotkey::Released(binding.action)
+            })
+            .collect()
+    }
+}
+
+fn is_modifier(key: Key) -> bool {
+    set_modifier(&mut Modifiers::default(), key)
+}
+
+/// Set the modifier `key` stands for; returns false if it is not a modifier
+fn set_modifier(modifiers: &mut Modifiers, key: Key) -> bool {
+    match key {
+        Key::KEY_LEFTCTRL | Key::KEY_RIGHTCTRL => modifiers.ctrl = true,
+        Key::KEY_LEFTALT | Key::KEY_RIGHTALT => modifiers.alt = true,
+        Key::KEY_LEFTSHIFT | Key::KEY_RIGHTSHIFT => modifiers.shift = true,
+        Key::KEY_LEFTMETA | Key::KEY_RIGHTMETA => modifiers.super_ = true,
+        _ => return false,
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ipc::{CommandRequest, IpcCommand};
+    use evdev::uinput::VirtualDeviceBuilder;
+    use evdev::{AttributeSet, EventType, InputEvent};
+    use std::path::Path;
+    use std::time::{Duration, Instant};
+    use tokio::sync::mpsc;
+
+    const KEY_REPEAT: i32 = 2;
+
+    fn dispatcher() -> (Dispatcher, mpsc::Receiver<CommandRequest>) {
+        let (tx, rx) = mpsc::channel(16);
+        (Dispatcher::new(tx), rx)
+    }
+
+    fn test_bindings() -> Vec<KeyBinding> {
+        key_bindings(&[
+            (HotkeyAction::Toggle, "Super+B".parse().unwrap()),
+            (HotkeyAction::PushToTalk, "Ctrl+Alt+Space".parse().unwrap()),
+            (HotkeyAction::Cancel, "F9".parse().unwrap()),
+        ])
+        .unwrap()
+    }
+
+    fn commands(rx: &mut mpsc::Receiver<CommandRequest>) -> Vec<IpcCommand> {
+        std::iter::from_fn(|| rx.try_recv().ok())
+            .map(|request| request.command)
+            .collect()
+    }
+
+    /// Feed `(key, value)` events of one keyboard and collect the commands
+    /// they send
+    fn feed(events: &[(Key, i32)]) -> Vec<IpcCommand> {
+        let events: Vec<_> = events.iter().map(|&(key, value)| (0, key, value)).collect();
+        feed_devices(&events)
+    }
+
+    /// Feed `(keyboard, key, value)` events and collect the commands they send
+    fn feed_devices(events: &[(usize, Key, i32)]) -> Vec<IpcCommand> {
+        let bindings = test_bindings();
+        let (dispatcher, mut rx) = dispatcher();
+        let mut state = KeyState::default();
+        for &(device, key, value) in events {
+            dispatch(&dispatcher, state.handle(device, key, value, &bindings));
+        }
+        commands(&mut rx)
+    }
+
+    #[test]
+    fn resolves_key_codes() {
+        let bindings = test_bindings();
+        let keys: Vec<Key> = bindings.iter().map(|b| b.key).collect();
+        assert_eq!(keys, [Key::KEY_B, Key::KEY_SPACE, Key::KEY_F9]);
+
+        let unknown = [(HotkeyAction::Toggle, "Super+Nope".parse().unwrap())];
+        assert!(matches!(
+            key_bindings(&unknown),
+            Err(HotkeyError::InvalidBinding(_))
+        ));
+    }
+
+    /// Bindings set in the README and config.example.toml, commented or not
+    fn documented_bindings() -> Vec<Binding> {
+        [
+            include_str!("../../README.md"),
+            include_str!("../../config.example.toml"),
+        ]
+        .iter()
+        .flat_map(|doc| doc.lines())
+        .filter_map(|line| line.trim_start_matches(['#', ' ']).split_once(" = "))
+        .filter(|(name, _)| ["toggle", "push_to_talk", "cancel"].contains(name))
+        .map(|(_, value)| value.trim_matches('"').parse().unwrap())
+        .collect()
+    }
+
+    #[test]
+    fn resolves_documented_bindings() {
+        let documented = documented_bindings();
+        assert!(documented.len() >= 3);
+        for binding in documented {
+            let resolved = key_bindings(&[(HotkeyAction::Toggle, binding.clone())]);
+            assert!(resolved.is_ok(), "{}", binding);
+        }
+        let escape = key_bindings(&[(HotkeyAction::Cancel, "Super+Escape".parse().unwrap())]);
+        assert_eq!(escape.unwrap()[0].key, Key::KEY_ESC);
+        let enter = key_bindings(&[(HotkeyAction::Cancel, "Return".parse().unwrap())]);
+        assert_eq!(enter.unwrap()[0].key, Key::KEY_ENTER);
+    }
+
+    #[test]
+    fn matches_exact_modifiers() {
+        let toggle = vec![IpcCommand::Toggle { output: None }];
+        assert_eq!(
+            feed(&[(Key::KEY_LEFTMETA, KEY_PRESS), (Key::KEY_B, KEY_PRESS)]),
+            toggle
+        );
+        // Either side of a modifier
+        assert_eq!(
+            feed(&[(Key::KEY_RIGHTMETA, KEY_PRESS), (Key::KEY_B, KEY_PRESS)]),
+            toggle
+        );
+        // Missing or extra modifiers do not match
+        assert_eq!(feed(&[(Key::KEY_B, KEY_PRESS)]), []);
+        assert_eq!(
+            feed(&[
+                (Key::KEY_LEFTMETA, KEY_PRESS),
+                (Key::KEY_LEFTSHIFT, KEY_PRESS),
+                (Key::KEY_B, KEY_PRESS),
+            ]),
+            []
+        );
+        // A released modifier no longer counts
+        assert_eq!(
+            feed(&[
+                (Key::KEY_LEFTMETA, KEY_PRESS),
+                (Key::KEY_LEFTMETA, KEY_RELEASE),
+                (Key::KEY_B, KEY_PRESS),
+            ]),
+            []
+        );
+        // Bindings without modifiers
+        assert_eq!(feed(&[(Key::KEY_F9, KEY_PRESS)]), [IpcCommand::Cancel]);
+    }
+
+    #[test]
+    fn ignores_key_repeat() {
+        assert_eq!(
+            feed(&[
+                (Key::KEY_LEFTCTRL, KEY_PRESS),
+                (Key::KEY_LEFTALT, KEY_PRESS),
+                (Key::KEY_SPACE, KEY_PRESS),
+                (Key::KEY_SPACE, KEY_REPEAT),
+                (Key::KEY_SPACE, KEY_REPEAT),
+                (Key::KEY_LEFTCTRL, KEY_REPEAT),
+                (Key::KEY_SPACE, KEY_RELEASE),
+            ]),
+            [IpcCommand::PttDown, IpcCommand::PttUp]
+        );
+    }
+
+    #[test]
+    fn releases_after_modifiers_are_let_go() {
+        // Modifiers released first still end push-to-talk
+        assert_eq!(
+            feed(&[
+                (Key::KEY_LEFTCTRL, KEY_PRESS),
+                (Key::KEY_LEFTALT, KEY_PRESS),
+                (Key::KEY_SPACE, KEY_PRESS),
+                (Key::KEY_LEFTCTRL, KEY_RELEASE),
+                (Key::KEY_LEFTALT, KEY_RELEASE),
+                (Key::KEY_SPACE, KEY_RELEASE),
+            ]),
+            [IpcCommand::PttDown, IpcCommand::PttUp]
+        );
+        // A release without a matching press sends nothing
+        assert_eq!(feed(&[(Key::KEY_SPACE, KEY_RELEASE)]), []);
+    }
+
+    #[test]
+    fn combines_modifiers_across_keyboards() {
+        // Modifier on one node, key on another
+        assert_eq!(
+            feed_devices(&[
+                (0, Key::KEY_LEFTMETA, KEY_PRESS),
+                (1, Key::KEY_B, KEY_PRESS)
+            ]),
+            [IpcCommand::Toggle { output: None }]
+        );
+        // Ctrl stays down while held on either keyboard
+        assert_eq!(
+            feed_devices(&[
+                (0, Key::KEY_LEFTCTRL, KEY_PRESS),
+                (1, Key::KEY_LEFTCTRL, KEY_PRESS),
+                (0, Key::KEY_LEFTCTRL, KEY_RELEASE),
+                (1, Key::KEY_LEFTALT, KEY_PRESS),
+                (0, Key::KEY_SPACE, KEY_PRESS),
+            ]),
+            [IpcCommand::PttDown]
+        );
+        assert_eq!(
+            feed_devices(&[
+                (0, Key::KEY_LEFTMETA, KEY_PRESS),
+                (0, Key::KEY_LEFTMETA, KEY_RELEASE),
+                (1, Key::KEY_B, KEY_PRESS),
+            ]),
+            []
+        );
+    }
+
+    #[test]
+    fn forgets_removed_keyboards() {
+        let bindings = test_bindings();
+        let (dispatcher, mut rx) = dispatcher();
+        let mut state = KeyState::default();
+        for (device, key) in [
+            (0, Key::KEY_LEFTCTRL),
+            (0, Key::KEY_LEFTALT),
+            (0, Key::KEY_SPACE),
+            (1, Key::KEY_LEFTMETA),
+        ] {
+            dispatch(&dispatcher, state.handle(device, key, KEY_PRESS, &bindings));
+        }
+        assert_eq!(commands(&mut rx), [IpcCommand::PttDown]);
+
+        // Push-to-talk held on the unplugged keyboard is released
+        assert_eq!(
+            state.forget(0),
+            [Hotkey::Released(HotkeyAction::PushToTalk)]
+        );
+        // Its modifiers are gone, the other keyboard's remain
+        assert_eq!(
+            state.modifiers(),
+            Modifiers {
+                super_: true,
+                ..Default::default()
+            }
+        );
+        assert_eq!(state.forget(1), []);
+    }
+
+    #[test]
+    fn never_waits_on_a_busy_main_loop() {
+        let (tx, mut rx) = mpsc::channel(1);
+        let dispatcher = Dispatcher::new(tx);
+        // The second command does not fit and is dropped instead of blocking
+        dispatch(
+            &dispatcher,
+            [
+                Hotkey::Pressed(HotkeyAction::Toggle),
+                Hotkey::Pressed(HotkeyAction::Cancel),
+            ],
+        );
+        assert_eq!(commands(&mut rx), [IpcCommand::Toggle { output: None }]);
+
+        assert!(!dispatcher.stopped());
+        drop(rx);
+        assert!(dispatcher.stopped());
+    }
+
+    /// Open a node of a freshly created uinput device, waiting for udev
+    fn open_node(path: &Path) -> Option<Device> {
+        let started = Instant::now();
+        loop {
+            match Device::open(path) {
+                Ok(device) => return Some(device),
+                Err(_) if started.elapsed() < Duration::from_secs(2) => {
+                    std::thread::sleep(Duration::from_millis(20))
+                }
+                Err(e) => {
+                    eprintln!("cannot open {:?} ({}), skipping", path, e);
+                    return None;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn reads_a_virtual_keyboard() {
+        let mut keys = AttributeSet::<Key>::new();
+        for key in [
+            Key::KEY_A,
+            Key::KEY_B,
+            Key::KEY_Z,
+            Key::KEY_ENTER,
+            Key::KEY_SPACE,
+            Key::KEY_LEFTCTRL,
+            Key::KEY_LEFTALT,
+            Key::KEY_LEFTMETA,
+        ] {
+            keys.insert(key);
+        }
+        let device = VirtualDeviceBuilder::new()
+            .and_then(|builder| builder.name("super-whisper test keyboard").with_keys(&keys))
+            .and_then(|builder| builder.build());
+        let mut keyboard = match device {
+            Ok(keyboard) => keyboard,
+            Err(e) => {
+                eprintln!("uinput unavailable ({}), skipping", e);
+                return;
+            }
+        };
+
+        let path = keyboard
+            .enumerate_dev_nodes_blocking()
+            .unwrap()
+            .find_map(Result::ok)
+            .unwrap();
+        let Some(device) = open_node(&path) else {
+            return;
+        };
+        assert!(is_keyboard(&device));
+        drop(device);
+
+        let bindings = [
+            (HotkeyAction::Toggle, "Super+B".parse().unwrap()),
+            (HotkeyAction::PushToTalk, "Ctrl+Alt+Space".parse().unwrap()),
+        ];
+        let (dispatcher, mut rx) = dispatcher();
+        start(&bindings, &[path], dispatcher).unwrap();
+        // Let the reader thread start before typing
+        std::thread::sleep(Duration::from_millis(100));
+
+        let key = |key: Key, value| InputEvent::new(EventType::KEY, key.code(), value);
+        for batch in [
+            vec![
+                key(Key::KEY_LEFTMETA, KEY_PRESS),
+                key(Key::KEY_B, KEY_PRESS),
+            ],
+            vec![
+                key(Key::KEY_B, KEY_RELEASE),
+                key(Key::KEY_LEFTMETA, KEY_RELEASE),
+            ],
+            vec![
+                key(Key::KEY_LEFTCTRL, KEY_PRESS),
+                key(Key::KEY_LEFTALT, KEY_PRESS),
+            ],
+            vec![key(Key::KEY_SPACE, KEY_PRESS)],
+            vec![key(Key::KEY_SPACE, KEY_REPEAT)],
+            vec![key(Key::KEY_SPACE, KEY_RELEASE)],
+            vec![
+                key(Key::KEY_LEFTALT, KEY_RELEASE),
+                key(Key::KEY_LEFTCTRL, KEY_RELEASE),
+            ],
+            // Unbound
+            vec![key(Key::KEY_A, KEY_PRESS), key(Key::KEY_A, KEY_RELEASE)],
+        ] {
+            keyboard.emit(&batch).unwrap();
+        }
+
+        let expected = [
+            IpcCommand::Toggle { output: None },
+            IpcCommand::PttDown,
+            IpcCommand::PttUp,
+        ];
+        let mut received = Vec::new();
+        let started = Instant::now();
+        while received.len() < expected.len() && started.elapsed() < Duration::from_secs(5) {
+            received.extend(commands(&mut rx));
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        std::thread::sleep(Duration::from_millis(100));
+        received.extend(commands(&mut rx));
+        assert_eq!(received, expected);
+    }
+}
@@ -0,0 +1,123 @@
+mod binding;
+mod evdev;
+mod portal;
+
+pub use binding::{Binding, HotkeyAction, Modifiers};
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::sync::watch;
+use tracing::{info, warn};
+
+use crate::config::{HotkeyBackend, HotkeyConfig};
+use crate::error::HotkeyError;
+use crate::ipc::{CommandRequest, CommandSender, IpcCommand};
+
+/// Forwards hotkey presses to the daemon's main loop, and backend failures
+/// to the listener
+#[derive(Clone)]
+pub(crate) struct Dispatcher {
+    tx: CommandSender,
+    failure: watch::Sender<Option<String>>,
+    // Set when the listener is dropped; backend threads exit once they see it
+    shutdown: Arc<AtomicBool>,
+}
+
+impl Dispatcher {
+    pub(crate) fn new(tx: CommandSender) -> Self {
+        let (failure, _) = watch::channel(None);
+        Self {
+            tx,
+            failure,
+            shutdown: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Whether backend threads should stop: the listener is gone or nothing
+    /// receives commands anymore
+    pub(crate) fn stopped(&self) -> bool {
+        self.shutdown.load(Ordering::Relaxed) || self.tx.is_closed()
+    }
+
+    pub(crate) fn pressed(&self, action: HotkeyAction) {
+        if let Some(command) = action.pressed() {
+            self.send(command);
+        }
+    }
+
+    pub(crate) fn released(&self, action: HotkeyAction) {
+        if let Some(command) = action.released() {
+            self.send(command);
+        }
+    }
+
+    /// The backend stopped working and will not deliver hotkeys anymore
+    pub(crate) fn failed(&self, error: &HotkeyError) {
+        self.failure.send_replace(Some(error.to_string()));
+    }
+
+    // Never blocks: the main loop may be busy transcribing, and a backend
+    // thread waiting on it would stop tracking keys
+    fn send(&self, command: IpcCommand) {
+        if let Err(e) = self.tx.try_send(CommandRequest::new(command)) {
+            warn!("Dropped hotkey command: {}", e);
+        }
+    }
+}
+
+/// Built-in global hotkey listener
+pub struct HotkeyListener {
+    failure: watch::Receiver<Option<String>>,
+    shutdown: Arc<AtomicBool>,
+}
+
+impl HotkeyListener {
+    /// Start listening with the configured backend.
+    ///
+    /// Returns `Ok(None)` when no backend or no bindings are configured.
+    pub fn new(
+        config: &HotkeyConfig,
+        commands: CommandSender,
+    ) -> Result<Option<Self>, HotkeyError> {
+        let bindings = binding::bindings(config)?;
+        let dispatcher = Dispatcher::new(commands);
+        let failure = dispatcher.failure.subscribe();
+        let shutdown = dispatcher.shutdown.clone();
+
+        match config.backend {
+            HotkeyBackend::None => return Ok(None),
+            _ if bindings.is_empty() => {
+                warn!(
+                    "Hotkey backend {:?} configured without bindings",
+                    config.backend
+                );
+                return Ok(None);
+            }
+            HotkeyBackend::Evdev => evdev::start(&bindings, &config.devices, dispatcher)?,
+            HotkeyBackend::Portal => portal::start(&bindings, dispatcher)?,
+        }
+
+        for (action, binding) in &bindings {
+            info!("Hotkey {} -> {}", binding, action.id());
+        }
+
+        Ok(Some(Self { failure, shutdown }))
+    }
+
+    /// Wait until the backend fails after startup (e.g. the portal session
+    /// is refused) and return why
+    pub async fn failed(&self) -> String {
+        let mut failure = self.failure.clone();
+        let error = match failure.wait_for(Option::is_some).await {
+            Ok(error) => error.clone().unwrap_or_default(),
+            Err(_) => "hotkey listener stopped".to_string(),
+        };
+        error
+    }
+}
+
+impl Drop for HotkeyListener {
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,251 @@
+use dbus::arg::{PropMap, RefArg, Variant};
+use dbus::blocking::Connection;
+use dbus::message::MatchRule;
+use dbus::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{debug, error, info, warn};
+
+use crate::error::HotkeyError;
+use crate::hotkey::binding::{Binding, HotkeyAction};
+use crate::hotkey::Dispatcher;
+
+const PORTAL_BUS: &str = "org.freedesktop.portal.Desktop";
+const PORTAL_PATH: &str = "/org/freedesktop/portal/desktop";
+const SHORTCUTS_IFACE: &str = "org.freedesktop.portal.GlobalShortcuts";
+const REQUEST_IFACE: &str = "org.freedesktop.portal.Request";
+
+/// Binding shortcuts may show a confirmation dialog, give the user time
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(300);
+const CALL_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Register the shortcuts with the portal and start the listener thread.
+/// Only connecting is checked here; the session is set up on the thread
+/// (the user may have to confirm it) and its failure reported to `dispatcher`.
+pub fn start(
+    bindings: &[(HotkeyAction, Binding)],
+    dispatcher: Dispatcher,
+) -> Result<(), HotkeyError> {
+    let bindings = bindings.to_vec();
+    let (ready_tx, ready_rx) = std::sync::mpsc::channel::<Result<(), HotkeyError>>();
+
+    std::thread::Builder::new()
+        .name("hotkey-portal".into())
+        .spawn(move || {
+            let conn = match Connection::new_session() {
+                Ok(conn) => {
+                    let _ = ready_tx.send(Ok(()));
+                    conn
+                }
+                Err(e) => {
+                    let _ = ready_tx.send(Err(HotkeyError::DbusError(e.to_string())));
+                    return;
+                }
+            };
+            if let Err(e) = run(&conn, &bindings, &dispatcher) {
+                error!("Global shortcuts unavailable: {}", e);
+                dispatcher.failed(&e);
+            }
+        })
+        .map_err(|e| HotkeyError::DbusError(e.to_string()))?;
+
+    ready_rx
+        .recv()
+        .map_err(|e| HotkeyError::DbusError(e.to_string()))?
+}
+
+fn run(
+    conn: &Connection,
+    bindings: &[(HotkeyAction, Binding)],
+    dispatcher: &Dispatcher,
+) -> Result<(), HotkeyError> {
+    let session = create_session(conn)?;
+    debug!("GlobalShortcuts session {}", session);
+
+    bind_shortcuts(conn, &session, bindings)?;
+
+    let pressed = dispatcher.clone();
+    let activated = MatchRule::new_signal(SHORTCUTS_IFACE, "Activated");
+    conn.add_match(
+        activated,
+        move |(_, id, _, _): (Path<'static>, String, u64, PropMap), _, _| {
+            if let Some(action) = HotkeyAction::from_id(&id) {
+                debug!("Hotkey {:?} pressed", action);
+                pressed.pressed(action);
+            }
+            true
+        },
+    )
+    .map_err(|e| HotkeyError::DbusError(e.to_string()))?;
+
+    let released = dispatcher.clone();
+    let deactivated = MatchRule::new_signal(SHORTCUTS_IFACE, "Deactivated");
+    conn.add_match(
+        deactivated,
+        move |(_, id, _, _): (Path<'static>, String, u64, PropMap), _, _| {
+            if let Some(action) = HotkeyAction::from_id(&id) {
+                debug!("Hotkey {:?} released", action);
+                released.released(action);
+            }
+            true
+        },
+    )
+    .map_err(|e| HotkeyError::DbusError(e.to_string()))?;
+
+    while !dispatcher.stopped() {
+        if let Err(e) = conn.process(Duration::from_secs(1)) {
+            return Err(HotkeyError::DbusError(e.to_string()));
+        }
+    }
+    Ok(())
+}
+
+fn create_session(conn: &Connection) -> Result<String, HotkeyError> {
+    let results = request(conn, "CreateSession", |proxy, mut options| {
+        options.insert(
+            "session_handle_token".into(),
+            string_variant("super_whisper_session"),
+        );
+        let (_,): (Path,) = proxy
+            .method_call(SHORTCUTS_IFACE, "CreateSession", (options,))
+            .map_err(|e| HotkeyError::PortalError(e.to_string()))?;
+        Ok(())
+    })?;
+
+    dbus::arg::prop_cast::<String>(&results, "session_handle")
+        .cloned()
+        .ok_or_else(|| HotkeyError::PortalError("No session handle in response".into()))
+}
+
+fn bind_shortcuts(
+    conn: &Connection,
+    session: &str,
+    bindings: &[(HotkeyAction, Binding)],
+) -> Result<(), HotkeyError> {
+    let session = Path::new(session.to_string()).map_err(HotkeyError::PortalError)?;
+    let shortcuts = shortcuts(bindings);
+
+    let results = request(conn, "BindShortcuts", move |proxy, options| {
+        let (_,): (Path,) = proxy
+            .method_call(
+                SHORTCUTS_IFACE,
+                "BindShortcuts",
+                (session, shortcuts, "", options),
+            )
+            .map_err(|e| HotkeyError::PortalError(e.to_string()))?;
+        Ok(())
+    })?;
+
+    // The compositor may have assigned different triggers than requested
+    match results.get("shortcuts") {
+        Some(bound) => info!("Global shortcuts bound: {:?}", bound),
+        None => warn!("Portal did not report the bound shortcuts"),
+    }
+    Ok(())
+}
+
+/// Shortcuts as BindShortcuts takes them: id and description plus the
+/// preferred trigger
+fn shortcuts(bindings: &[(HotkeyAction, Binding)]) -> Vec<(String, PropMap)> {
+    bindings
+        .iter()
+        .map(|(action, binding)| {
+            let mut props = PropMap::new();
+            props.insert("description".into(), string_variant(action.description()));
+            props.insert(
+                "preferred_trigger".into(),
+                string_variant(&binding.portal_trigger()),
+            );
+            (action.id().to_string(), props)
+        })
+        .collect()
+}
+
+/// Call a portal method that answers through an org.freedesktop.portal.Request
+/// object and wait for its Response signal
+fn request<F>(conn: &Connection, method: &str, call: F) -> Result<PropMap, HotkeyError>
+where
+    F: FnOnce(&dbus::blocking::Proxy<'_, &Connection>, PropMap) -> Result<(), HotkeyError>,
+{
+    let token = format!("super_whisper_{}", method.to_lowercase());
+    // Subscribe before calling so the response cannot be missed
+    let sender = conn.unique_name().trim_start_matches(':').replace('.', "_");
+    let handle = format!("{}/request/{}/{}", PORTAL_PATH, sender, token);
+
+    let response: Arc<Mutex<Option<(u32, PropMap)>>> = Arc::new(Mutex::new(None));
+    let received = response.clone();
+    let rule = MatchRule::new_signal(REQUEST_IFACE, "Response")
+        .with_path(Path::new(handle).map_err(HotkeyError::PortalError)?);
+    let token_match = conn
+        .add_match(rule, move |(code, results): (u32, PropMap), _, _| {
+            *received.lock().unwrap() = Some((code, results));
+            true
+        })
+        .map_err(|e| HotkeyError::DbusError(e.to_string()))?;
+
+    let mut options = PropMap::new();
+    options.insert("handle_token".into(), string_variant(&token));
+    let proxy = conn.with_proxy(PORTAL_BUS, PORTAL_PATH, CALL_TIMEOUT);
+    let result = call(&proxy, options);
+
+    let started = Instant::now();
+    while result.is_ok()
+        && response.lock().unwrap().is_none()
+        && started.elapsed() < RESPONSE_TIMEOUT
+    {
+        conn.process(Duration::from_millis(200))
+            .map_err(|e| HotkeyError::DbusError(e.to_string()))?;
+    }
+    let _ = conn.remove_match(token_match);
+    result?;
+
+    let taken = response.lock().unwrap().take();
+    match taken {
+        Some((0, results)) => Ok(results),
+        Some((1, _)) => Err(HotkeyError::PortalError(format!(
+            "{} cancelled by the user",
+            method
+        ))),
+        Some((code, _)) => Err(HotkeyError::PortalError(format!(
+            "{} failed ({})",
+            method, code
+        ))),
+        None => Err(HotkeyError::PortalError(format!("{} timed out", method))),
+    }
+}
+
+fn string_variant(value: &str) -> Variant<Box<dyn RefArg>> {
+    Variant(Box::new(value.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn describes_shortcuts_with_preferred_triggers() {
+        let bindings = [
+            (HotkeyAction::Toggle, "Super+B".parse().unwrap()),
+            (
+                HotkeyAction::PushToTalk,
+                "Ctrl+Shift+Space".parse().unwrap(),
+            ),
+            (HotkeyAction::Cancel, "Super+Escape".parse().unwrap()),
+        ];
+        let shortcuts: Vec<(String, String, String)> = shortcuts(&bindings)
+            .iter()
+            .map(|(id, props)| {
+                let prop = |name| dbus::arg::prop_cast::<String>(props, name).unwrap().clone();
+                (id.clone(), prop("description"), prop("preferred_trigger"))
+            })
+            .collect();
+
+        let expected = [
+            ("toggle", "Start or stop recording", "LOGO+b"),
+            ("push-to-talk", "Record while held", "CTRL+SHIFT+space"),
+            ("cancel", "Cancel recording", "LOGO+Escape"),
+        ]
+        .map(|(id, description, trigger)| (id.into(), description.into(), trigger.into()));
+        assert_eq!(shortcuts, expected);
+    }
+}
@@ -1,3 +1,3 @@
 mod socket;
 
-pub use socket::{IpcServer, IpcClient, IpcCommand};
+pub use socket::{IpcServer, IpcClient, IpcCommand, IpcEvent, CommandRequest, CommandSender};
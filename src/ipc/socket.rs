@@ -1,42 +1,150 @@
+use serde::Serialize;
 use std::path::PathBuf;
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines};
 use tokio::net::{UnixListener, UnixStream};
-use tokio::sync::mpsc;
+use tokio::sync::{broadcast, mpsc, oneshot};
 use tracing::{debug, error, info, warn};
 
+use crate::config::{OutputMode, ProviderType};
 use crate::error::IpcError;
 
 /// Commands that can be sent via IPC
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum IpcCommand {
-    /// Toggle recording on/off
-    Toggle,
+    /// Toggle recording on/off, optionally overriding the output selection
+    Toggle { output: Option<OutputMode> },
     /// Start recording
     Start,
-    /// Stop recording and transcribe
-    Stop,
+    /// Stop recording and transcribe, optionally overriding the output selection
+    Stop { output: Option<OutputMode> },
+    /// Transcribe what was recorded so far and keep recording
+    Rollover,
     /// Cancel current operation
     Cancel,
+    /// Push-to-talk key pressed: start recording
+    PttDown,
+    /// Push-to-talk key released: stop and transcribe (or cancel a short tap)
+    PttUp,
     /// Get current status
     Status,
+    /// Change the log level/filter at runtime
+    LogLevel(String),
+    /// Switch the STT provider
+    Provider(ProviderType),
+    /// Set the language hint ("auto" to detect)
+    Language(String),
+    /// Select the input device (None = default)
+    Device(Option<String>),
+    /// Enable or disable auto-paste
+    AutoPaste(bool),
+    /// Copy a recent transcript to the clipboard (0 = newest)
+    Copy(usize),
+    /// Paste a recent transcript again (0 = newest)
+    Paste(usize),
+    /// Open the configuration file
+    OpenConfig,
+    /// Open the transcript history file
+    OpenHistory,
     /// Shutdown the application
     Shutdown,
 }
 
 impl IpcCommand {
+    /// Parse a command line such as `stop` or `stop primary`
     pub fn from_str(s: &str) -> Option<Self> {
-        match s.trim().to_lowercase().as_str() {
-            "toggle" => Some(Self::Toggle),
-            "start" => Some(Self::Start),
-            "stop" => Some(Self::Stop),
-            "cancel" => Some(Self::Cancel),
-            "status" => Some(Self::Status),
-            "shutdown" | "quit" | "exit" => Some(Self::Shutdown),
+        let mut parts = s.split_whitespace();
+        let name = parts.next()?.to_lowercase();
+        let args: Vec<&str> = parts.collect();
+
+        match (name.as_str(), args.as_slice()) {
+            ("toggle", []) => Some(Self::Toggle { output: None }),
+            ("toggle", [output]) => Some(Self::Toggle {
+                output: Some(output.parse().ok()?),
+            }),
+            ("start", []) => Some(Self::Start),
+            ("stop", []) => Some(Self::Stop { output: None }),
+            ("stop", [output]) => Some(Self::Stop {
+                output: Some(output.parse().ok()?),
+            }),
+            ("rollover", []) => Some(Self::Rollover),
+            ("cancel", []) => Some(Self::Cancel),
+            ("ptt-down", []) => Some(Self::PttDown),
+            ("ptt-up", []) => Some(Self::PttUp),
+            ("status", []) => Some(Self::Status),
+            ("log-level", [level]) => Some(Self::LogLevel(level.to_string())),
+            ("provider", [provider]) => Some(Self::Provider(provider.parse().ok()?)),
+            ("language", [language]) => Some(Self::Language(language.to_lowercase())),
+            // Device names may contain spaces
+            ("device", ["default"]) => Some(Self::Device(None)),
+            ("device", name) if !name.is_empty() => Some(Self::Device(Some(name.join(" ")))),
+            ("auto-paste", ["on"]) => Some(Self::AutoPaste(true)),
+            ("auto-paste", ["off"]) => Some(Self::AutoPaste(false)),
+            ("copy", [index]) => Some(Self::Copy(index.parse().ok()?)),
+            ("paste", [index]) => Some(Self::Paste(index.parse().ok()?)),
+            ("open-config", []) => Some(Self::OpenConfig),
+            ("open-history", []) => Some(Self::OpenHistory),
+            ("shutdown" | "quit" | "exit", []) => Some(Self::Shutdown),
             _ => None,
         }
     }
 }
 
+/// Line a client sends to receive the event stream instead of running a command
+const EVENTS_REQUEST: &str = "events";
+
+/// Events streamed to `events` subscribers, one JSON object per line
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+pub enum IpcEvent {
+    /// The app state changed
+    State { state: String },
+    /// Input level of the last captured chunk (linear, 0.0 - 1.0)
+    Level { rms: f32, peak: f32 },
+    /// Something the user should look at (e.g. silent input)
+    Warning { message: String },
+}
+
+/// A command for the daemon, with an optional channel for its result
+/// (the response text on success)
+#[derive(Debug)]
+pub struct CommandRequest {
+    pub command: IpcCommand,
+    pub reply: Option<oneshot::Sender<Result<String, String>>>,
+}
+
+impl CommandRequest {
+    /// A command whose result nobody waits for
+    pub fn new(command: IpcCommand) -> Self {
+        Self {
+            command,
+            reply: None,
+        }
+    }
+
+    /// A command plus a receiver for its result
+    pub fn with_reply(command: IpcCommand) -> (Self, oneshot::Receiver<Result<String, String>>) {
+        let (tx, rx) = oneshot::channel();
+        (
+            Self {
+                command,
+                reply: Some(tx),
+            },
+            rx,
+        )
+    }
+
+    /// Report the result to the sender (if it is still waiting)
+    pub fn respond(&mut self, result: Result<String, String>) {
+        if let Some(reply) = self.reply.take() {
+            let _ = reply.send(result);
+        }
+    }
+}
+
+/// Channel used to send commands to the daemon's main loop
+pub type CommandSender = mpsc::Sender<CommandRequest>;
+
 /// IPC server that listens for commands
 pub struct IpcServer {
     socket_path: PathBuf,
@@ -47,8 +155,15 @@ impl IpcServer {
         Self { socket_path }
     }
 
-    /// Start the IPC server and return a receiver for commands
-    pub async fn start(&self) -> Result<mpsc::Receiver<IpcCommand>, IpcError> {
+    /// Start the IPC server, forwarding received commands to `tx` and
+    /// streaming `events` to clients that subscribe. `status` answers the
+    /// status command directly, so it never waits behind a transcription.
+    pub async fn start(
+        &self,
+        tx: CommandSender,
+        events: broadcast::Sender<IpcEvent>,
+        status: impl Fn() -> String + Send + Sync + 'static,
+    ) -> Result<(), IpcError> {
         // Clean up old socket
         if self.socket_path.exists() {
             std::fs::remove_file(&self.socket_path)?;
@@ -65,15 +180,16 @@ impl IpcServer {
 
         info!("IPC server listening on {:?}", self.socket_path);
 
-        let (tx, rx) = mpsc::channel::<IpcCommand>(32);
-
+        let status: Arc<dyn Fn() -> String + Send + Sync> = Arc::new(status);
         tokio::spawn(async move {
             loop {
                 match listener.accept().await {
                     Ok((stream, _)) => {
                         let tx = tx.clone();
+                        let events = events.clone();
+                        let status = status.clone();
                         tokio::spawn(async move {
-                            if let Err(e) = handle_client(stream, tx).await {
+                            if let Err(e) = handle_client(stream, tx, events, status).await {
                                 warn!("IPC client error: {}", e);
                             }
                         });
@@ -85,7 +201,7 @@ impl IpcServer {
             }
         });
 
-        Ok(rx)
+        Ok(())
     }
 }
 
@@ -98,7 +214,9 @@ impl Drop for IpcServer {
 
 async fn handle_client(
     mut stream: UnixStream,
-    tx: mpsc::Sender<IpcCommand>,
+    tx: CommandSender,
+    events: broadcast::Sender<IpcEvent>,
+    status: Arc<dyn Fn() -> String + Send + Sync>,
 ) -> Result<(), IpcError> {
     let (reader, mut writer) = stream.split();
     let mut reader = BufReader::new(reader);
@@ -107,19 +225,57 @@ async fn handle_client(
     reader.read_line(&mut line).await?;
     debug!("IPC received: {}", line.trim());
 
-    let response = if let Some(cmd) = IpcCommand::from_str(&line) {
-        match tx.send(cmd).await {
-            Ok(_) => "OK\n",
-            Err(_) => "ERROR: Channel closed\n",
+    if line.trim() == EVENTS_REQUEST {
+        return stream_events(&mut writer, events.subscribe()).await;
+    }
+
+    let response = match IpcCommand::from_str(&line) {
+        Some(IpcCommand::Status) => format!("{}\n", status()),
+        // Other commands answer once the daemon has run them: "OK" or the
+        // reason they failed
+        Some(cmd) => {
+            let (request, reply) = CommandRequest::with_reply(cmd);
+            match tx.send(request).await {
+                Ok(_) => match reply.await {
+                    Ok(Ok(response)) => format!("{}\n", response),
+                    Ok(Err(e)) => format!("ERROR: {}\n", e),
+                    Err(_) => "ERROR: No response\n".to_string(),
+                },
+                Err(_) => "ERROR: Channel closed\n".to_string(),
+            }
         }
-    } else {
-        "ERROR: Unknown command\n"
+        None => "ERROR: Unknown command\n".to_string(),
     };
 
     writer.write_all(response.as_bytes()).await?;
     Ok(())
 }
 
+/// Write events to a subscribed client until it disconnects
+async fn stream_events<W: AsyncWriteExt + Unpin>(
+    writer: &mut W,
+    mut events: broadcast::Receiver<IpcEvent>,
+) -> Result<(), IpcError> {
+    loop {
+        match events.recv().await {
+            Ok(event) => {
+                let mut line = serde_json::to_string(&event)
+                    .map_err(|e| IpcError::SendError(e.to_string()))?;
+                line.push('\n');
+                if writer.write_all(line.as_bytes()).await.is_err() {
+                    debug!("Event subscriber disconnected");
+                    return Ok(());
+                }
+            }
+            // Slow readers miss some level updates, that is fine
+            Err(broadcast::error::RecvError::Lagged(n)) => {
+                debug!("Event subscriber lagged, skipped {} events", n);
+            }
+            Err(broadcast::error::RecvError::Closed) => return Ok(()),
+        }
+    }
+}
+
 /// IPC client for sending commands
 pub struct IpcClient {
     socket_path: PathBuf,
@@ -146,4 +302,210 @@ impl IpcClient {
 
         Ok(response.trim().to_string())
     }
+
+    /// Subscribe to the event stream; yields one JSON event per line
+    pub async fn events(&self) -> Result<Lines<BufReader<UnixStream>>, IpcError> {
+        let mut stream = UnixStream::connect(&self.socket_path)
+            .await
+            .map_err(|_| IpcError::ConnectionRefused)?;
+
+        stream.write_all(EVENTS_REQUEST.as_bytes()).await?;
+        stream.write_all(b"\n").await?;
+
+        Ok(BufReader::new(stream).lines())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn parse(s: &str) -> Option<IpcCommand> {
+        IpcCommand::from_str(s)
+    }
+
+    #[test]
+    fn parses_commands_without_arguments() {
+        for (line, command) in [
+            ("toggle", IpcCommand::Toggle { output: None }),
+            ("start", IpcCommand::Start),
+            ("stop", IpcCommand::Stop { output: None }),
+            ("rollover", IpcCommand::Rollover),
+            ("cancel", IpcCommand::Cancel),
+            ("ptt-down", IpcCommand::PttDown),
+            ("ptt-up", IpcCommand::PttUp),
+            ("status", IpcCommand::Status),
+            ("open-config", IpcCommand::OpenConfig),
+            ("open-history", IpcCommand::OpenHistory),
+            ("shutdown", IpcCommand::Shutdown),
+            ("quit", IpcCommand::Shutdown),
+            ("exit", IpcCommand::Shutdown),
+        ] {
+            assert_eq!(parse(line), Some(command.clone()), "{}", line);
+            // Case, surrounding whitespace and the trailing newline do not matter
+            let padded = format!("  {}\n", line.to_uppercase());
+            assert_eq!(parse(&padded), Some(command), "{:?}", padded);
+            // None of them take an argument
+            assert_eq!(parse(&format!("{} extra", line)), None, "{} extra", line);
+        }
+    }
+
+    #[test]
+    fn parses_output_overrides() {
+        assert_eq!(
+            parse("toggle primary"),
+            Some(IpcCommand::Toggle {
+                output: Some(OutputMode::Primary)
+            })
+        );
+        assert_eq!(
+            parse("stop both"),
+            Some(IpcCommand::Stop {
+                output: Some(OutputMode::Both)
+            })
+        );
+        assert_eq!(
+            parse("stop Clipboard"),
+            Some(IpcCommand::Stop {
+                output: Some(OutputMode::Clipboard)
+            })
+        );
+        assert_eq!(parse("toggle nowhere"), None);
+        assert_eq!(parse("stop primary both"), None);
+    }
+
+    #[test]
+    fn parses_settings() {
+        assert_eq!(
+            parse("log-level debug"),
+            Some(IpcCommand::LogLevel("debug".into()))
+        );
+        assert_eq!(
+            parse("log-level super_whisper_linux=trace,warn"),
+            Some(IpcCommand::LogLevel(
+                "super_whisper_linux=trace,warn".into()
+            ))
+        );
+        assert_eq!(parse("log-level"), None);
+        assert_eq!(parse("log-level debug info"), None);
+
+        assert_eq!(
+            parse("provider groq"),
+            Some(IpcCommand::Provider(ProviderType::Groq))
+        );
+        assert_eq!(
+            parse("provider Whisper-Local"),
+            Some(IpcCommand::Provider(ProviderType::WhisperLocal))
+        );
+        assert_eq!(parse("provider nope"), None);
+        assert_eq!(parse("provider"), None);
+
+        assert_eq!(
+            parse("language EN"),
+            Some(IpcCommand::Language("en".into()))
+        );
+        assert_eq!(
+            parse("language auto"),
+            Some(IpcCommand::Language("auto".into()))
+        );
+        assert_eq!(parse("language"), None);
+        assert_eq!(parse("language en fr"), None);
+
+        assert_eq!(parse("auto-paste on"), Some(IpcCommand::AutoPaste(true)));
+        assert_eq!(parse("auto-paste off"), Some(IpcCommand::AutoPaste(false)));
+        assert_eq!(parse("auto-paste yes"), None);
+        assert_eq!(parse("auto-paste"), None);
+    }
+
+    #[test]
+    fn parses_device_names_with_spaces() {
+        assert_eq!(parse("device default"), Some(IpcCommand::Device(None)));
+        assert_eq!(
+            parse("device USB  Audio Device"),
+            Some(IpcCommand::Device(Some("USB Audio Device".into())))
+        );
+        // Names keep their case
+        assert_eq!(
+            parse("DEVICE Blue Yeti"),
+            Some(IpcCommand::Device(Some("Blue Yeti".into())))
+        );
+        assert_eq!(parse("device"), None);
+    }
+
+    #[test]
+    fn parses_history_indices() {
+        assert_eq!(parse("copy 0"), Some(IpcCommand::Copy(0)));
+        assert_eq!(parse("paste 3"), Some(IpcCommand::Paste(3)));
+        for line in ["copy", "paste", "copy -1", "paste last", "copy 1 2"] {
+            assert_eq!(parse(line), None, "{}", line);
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_input() {
+        for line in ["", "   ", "\n", "record", "toggel", "events now", "status?"] {
+            assert_eq!(parse(line), None, "{:?}", line);
+        }
+    }
+
+    /// Socket path unique to this test process
+    fn socket_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "super-whisper-test-{}-{}.sock",
+            std::process::id(),
+            name
+        ))
+    }
+
+    #[tokio::test]
+    async fn replies_with_the_command_result() {
+        let path = socket_path("reply");
+        let server = IpcServer::new(path.clone());
+        let (tx, mut rx) = mpsc::channel::<CommandRequest>(1);
+        let (events, _) = broadcast::channel(1);
+        server.start(tx, events, String::new).await.unwrap();
+        tokio::spawn(async move {
+            while let Some(mut request) = rx.recv().await {
+                let result = match request.command {
+                    IpcCommand::Paste(_) => Err("No recent transcript #0".to_string()),
+                    _ => Ok("OK".to_string()),
+                };
+                request.respond(result);
+            }
+        });
+
+        let client = IpcClient::new(path);
+        assert_eq!(client.send("language en").await.unwrap(), "OK");
+        assert_eq!(
+            client.send("paste 0").await.unwrap(),
+            "ERROR: No recent transcript #0"
+        );
+        assert_eq!(
+            client.send("bogus").await.unwrap(),
+            "ERROR: Unknown command"
+        );
+    }
+
+    #[tokio::test]
+    async fn answers_status_while_the_command_loop_is_busy() {
+        let path = socket_path("status");
+        let server = IpcServer::new(path.clone());
+        // Nobody drains the commands, like a loop waiting on a transcription
+        let (tx, _rx) = mpsc::channel(1);
+        let (events, _) = broadcast::channel(1);
+        server
+            .start(tx, events, || {
+                "processing (device: mic, provider: test)".into()
+            })
+            .await
+            .unwrap();
+
+        let status =
+            tokio::time::timeout(Duration::from_secs(5), IpcClient::new(path).send("status"))
+                .await
+                .expect("status waited for the command loop")
+                .unwrap();
+        assert_eq!(status, "processing (device: mic, provider: test)");
+    }
 }
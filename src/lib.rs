@@ -3,7 +3,11 @@ pub mod audio;
 pub mod clipboard;
 pub mod config;
 pub mod error;
+pub mod history;
+pub mod hotkey;
 pub mod ipc;
+pub mod logging;
+pub mod notify;
 pub mod stt;
 pub mod tray;
 
@@ -0,0 +1,227 @@
+use std::path::PathBuf;
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{fmt, reload, EnvFilter, Layer, Registry};
+
+use crate::config::{self, LogRotation, LoggingConfig};
+use crate::error::{AppError, Result};
+
+/// Level used by `--debug`; expanded like any other plain level, so
+/// dependencies stay at `warn`
+pub const DEBUG_LEVEL: &str = "debug";
+
+/// Handle to change the log filter at runtime
+#[derive(Clone)]
+pub struct LogHandle {
+    reload: reload::Handle<EnvFilter, Registry>,
+}
+
+impl LogHandle {
+    /// Replace the active filter with a level or directive string
+    pub fn set_level(&self, level: &str) -> Result<()> {
+        let filter = parse_filter(level)?;
+        self.reload
+            .reload(filter)
+            .map_err(|e| AppError::Other(format!("Failed to change log level: {}", e)))
+    }
+}
+
+/// Keeps the logging backend alive; drop it only on exit so buffered file
+/// output is flushed
+pub struct Logging {
+    pub handle: LogHandle,
+    _guard: Option<WorkerGuard>,
+}
+
+/// Initialize logging.
+///
+/// The filter is chosen with this precedence: the command line (`--log-level`
+/// or `--debug`), then `RUST_LOG`, then `logging.level` from the config file.
+/// Only the daemon writes the log file and formats for journald; one-shot
+/// client commands log plainly to stderr.
+pub fn init(config: &LoggingConfig, cli_level: Option<&str>, daemon: bool) -> Result<Logging> {
+    let env = std::env::var(EnvFilter::DEFAULT_ENV).ok();
+    let filter = choose_filter(&config.level, cli_level, env.as_deref())?;
+
+    let (filter, reload) = reload::Layer::new(filter);
+
+    // journald adds its own timestamps and does not render ANSI colors
+    let journald = daemon && std::env::var_os("JOURNAL_STREAM").is_some();
+    let stderr_layer = if journald {
+        fmt::layer()
+            .with_target(false)
+            .with_ansi(false)
+            .without_time()
+            .boxed()
+    } else {
+        fmt::layer().with_target(false).boxed()
+    };
+
+    let (file_layer, guard) = match &config.file {
+        Some(file) if daemon => {
+            let appender = file_appender(file, config)?;
+            let (writer, guard) = tracing_appender::non_blocking(appender);
+            let layer = fmt::layer()
+                .with_target(false)
+                .with_ansi(false)
+                .with_writer(writer);
+            (Some(layer), Some(guard))
+        }
+        _ => (None, None),
+    };
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(stderr_layer)
+        .with(file_layer)
+        .init();
+
+    Ok(Logging {
+        handle: LogHandle { reload },
+        _guard: guard,
+    })
+}
+
+/// Pick the filter from the command line, `RUST_LOG` (`env`) or the config
+fn choose_filter(config: &str, cli: Option<&str>, env: Option<&str>) -> Result<EnvFilter> {
+    if let Some(level) = cli {
+        parse_filter(level)
+    } else if let Some(env) = env {
+        EnvFilter::try_new(env).map_err(|e| AppError::Other(format!("Invalid RUST_LOG: {}", e)))
+    } else {
+        parse_filter(config)
+    }
+}
+
+/// Turn a plain level ("debug") into the app's default directive, or accept
+/// a full `EnvFilter` directive ("super_whisper_linux=trace,warn") as-is
+fn parse_filter(level: &str) -> Result<EnvFilter> {
+    let level = level.trim();
+    let directive = if level.contains('=') || level.contains(',') {
+        level.to_string()
+    } else {
+        match level.to_lowercase().as_str() {
+            "trace" | "debug" | "info" | "warn" | "error" | "off" => {
+                format!("super_whisper_linux={},warn", level.to_lowercase())
+            }
+            _ => {
+                return Err(AppError::Other(format!("Invalid log level: {}", level)));
+            }
+        }
+    };
+
+    EnvFilter::try_new(&directive)
+        .map_err(|e| AppError::Other(format!("Invalid log filter '{}': {}", directive, e)))
+}
+
+/// Build the rotating appender; relative paths live under `data_dir()/logs`
+fn file_appender(file: &std::path::Path, config: &LoggingConfig) -> Result<RollingFileAppender> {
+    let path = if file.is_absolute() {
+        file.to_path_buf()
+    } else {
+        config::data_dir().join("logs").join(file)
+    };
+
+    let dir = path
+        .parent()
+        .map(PathBuf::from)
+        .unwrap_or_else(|| config::data_dir().join("logs"));
+    std::fs::create_dir_all(&dir)?;
+
+    let prefix = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "super-whisper.log".to_string());
+
+    let rotation = match config.rotation {
+        LogRotation::Hourly => Rotation::HOURLY,
+        LogRotation::Daily => Rotation::DAILY,
+        LogRotation::Never => Rotation::NEVER,
+    };
+
+    RollingFileAppender::builder()
+        .rotation(rotation)
+        .filename_prefix(prefix)
+        .max_log_files(config.max_files.max(1))
+        .build(dir)
+        .map_err(|e| AppError::Other(format!("Failed to open log file: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Directives of a filter, for comparison
+    fn directives(filter: Result<EnvFilter>) -> String {
+        filter.unwrap().to_string()
+    }
+
+    fn expected(directive: &str) -> String {
+        EnvFilter::new(directive).to_string()
+    }
+
+    #[test]
+    fn expands_plain_levels() {
+        for level in ["trace", "debug", "info", "warn", "error", "off"] {
+            assert_eq!(
+                directives(parse_filter(level)),
+                expected(&format!("super_whisper_linux={},warn", level))
+            );
+        }
+        assert_eq!(
+            directives(parse_filter(" DEBUG ")),
+            expected("super_whisper_linux=debug,warn")
+        );
+        assert_eq!(
+            directives(parse_filter(DEBUG_LEVEL)),
+            expected("super_whisper_linux=debug,warn")
+        );
+    }
+
+    #[test]
+    fn passes_directives_through() {
+        for directive in [
+            "super_whisper_linux=trace,warn",
+            "super_whisper_linux=debug",
+            "info,zbus=warn",
+        ] {
+            assert_eq!(directives(parse_filter(directive)), expected(directive));
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_filters() {
+        for level in ["", "loud", "debug info", "super_whisper_linux=loud"] {
+            assert!(parse_filter(level).is_err(), "{:?}", level);
+        }
+    }
+
+    #[test]
+    fn prefers_cli_then_rust_log_then_config() {
+        let cli = "super_whisper_linux=trace,warn";
+        let env = "super_whisper_linux=info,warn";
+        assert_eq!(
+            directives(choose_filter("error", Some("trace"), Some(env))),
+            expected(cli)
+        );
+        assert_eq!(
+            directives(choose_filter("error", None, Some(env))),
+            expected(env)
+        );
+        // RUST_LOG is used as written, not expanded like a plain level
+        assert_eq!(
+            directives(choose_filter("error", None, Some("debug"))),
+            expected("debug")
+        );
+        assert_eq!(
+            directives(choose_filter("error", None, None)),
+            expected("super_whisper_linux=error,warn")
+        );
+
+        assert!(choose_filter("error", Some("loud"), None).is_err());
+        assert!(choose_filter("error", None, Some("super_whisper_linux=loud")).is_err());
+        assert!(choose_filter("loud", None, None).is_err());
+    }
+}
@@ -1,11 +1,15 @@
 use clap::{Parser, Subcommand};
-use tracing::{error, info};
-use tracing_subscriber::EnvFilter;
-
-use super_whisper_linux::audio::AudioCapture;
-use super_whisper_linux::config::{self, AppConfig};
-use super_whisper_linux::ipc::{IpcClient, IpcServer};
-use super_whisper_linux::tray::TrayIcon;
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::{error, info, warn};
+
+use super_whisper_linux::audio::{self, AudioCapture, AudioLevel, CaptureConfig, DevicePattern};
+use super_whisper_linux::config::{self, AppConfig, OutputMode, ProviderType};
+use super_whisper_linux::hotkey::HotkeyListener;
+use super_whisper_linux::ipc::{IpcClient, IpcCommand, IpcServer};
+use super_whisper_linux::logging::{self, LogHandle};
+use super_whisper_linux::tray::{MenuState, TrayIcon};
 use super_whisper_linux::{App, AppError};
 
 #[derive(Parser)]
@@ -23,6 +27,14 @@ struct Cli {
     /// Enable debug logging
     #[arg(short, long)]
     debug: bool,
+
+    /// Log level or filter directive (overrides RUST_LOG and the config file)
+    #[arg(long, conflicts_with = "debug")]
+    log_level: Option<String>,
+
+    /// Run without the system tray icon (headless daemon)
+    #[arg(long)]
+    no_tray: bool,
 }
 
 #[derive(Subcommand)]
@@ -34,12 +46,24 @@ enum Commands {
     #[command(subcommand)]
     Trigger(TriggerCommands),
 
-    /// List available audio devices
-    Devices,
+    /// List audio input devices and their supported formats
+    Devices {
+        #[command(subcommand)]
+        action: Option<DeviceCommands>,
+    },
 
     /// Show current status
     Status,
 
+    /// Print state changes, input levels and warnings as JSON lines
+    Events,
+
+    /// Change the log level of the running instance
+    LogLevel {
+        /// Level (trace, debug, info, warn, error) or filter directive
+        level: String,
+    },
+
     /// Generate example configuration file
     InitConfig,
 
@@ -51,42 +75,83 @@ enum Commands {
     },
 }
 
+#[derive(Subcommand)]
+enum DeviceCommands {
+    /// Record from a device and print level statistics
+    Test {
+        /// Device name, substring or /regex/ (default: configured device)
+        name: Option<String>,
+        /// Recording length in seconds
+        #[arg(short, long, default_value_t = 3.0)]
+        seconds: f32,
+        /// Play the recording back on the default output device
+        #[arg(short, long)]
+        play: bool,
+        /// Save the recording as a WAV file
+        #[arg(long)]
+        save: Option<PathBuf>,
+    },
+}
+
 #[derive(Subcommand)]
 enum TriggerCommands {
     /// Toggle recording
-    Toggle,
+    Toggle {
+        /// Where to put the transcript: clipboard, primary, or both
+        #[arg(short, long)]
+        output: Option<OutputMode>,
+    },
     /// Start recording
     Start,
     /// Stop recording
-    Stop,
+    Stop {
+        /// Where to put the transcript: clipboard, primary, or both
+        #[arg(short, long)]
+        output: Option<OutputMode>,
+    },
+    /// Transcribe what was recorded so far and keep recording
+    Rollover,
     /// Cancel current operation
     Cancel,
+    /// Push-to-talk key pressed (start recording)
+    PttDown,
+    /// Push-to-talk key released (stop, or cancel if held too briefly)
+    PttUp,
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let cli = Cli::parse();
 
+    // Load configuration (logging settings come from it)
+    let (config, config_file) = config::load_config()?;
+
     // Initialize logging
-    let filter = if cli.debug {
-        EnvFilter::new("super_whisper_linux=debug,info")
+    let cli_level = if cli.debug {
+        Some(logging::DEBUG_LEVEL)
     } else {
-        EnvFilter::new("super_whisper_linux=info,warn")
+        cli.log_level.as_deref()
     };
+    let command = cli.command.unwrap_or(Commands::Run);
+    let daemon = matches!(command, Commands::Run);
+    let logging = logging::init(&config.logging, cli_level, daemon)?;
+    if daemon {
+        match &config_file {
+            Some(path) => info!("Loaded configuration from {:?}", path),
+            None => info!("No configuration file found, using defaults"),
+        }
+    }
 
-    tracing_subscriber::fmt()
-        .with_env_filter(filter)
-        .with_target(false)
-        .init();
-
-    // Load configuration
-    let config = config::load_config()?;
-
-    match cli.command.unwrap_or(Commands::Run) {
-        Commands::Run => run_app(config).await?,
+    match command {
+        Commands::Run => run_app(config, logging.handle.clone(), cli.no_tray).await?,
         Commands::Trigger(cmd) => run_trigger(config, cmd).await?,
-        Commands::Devices => list_devices()?,
+        Commands::Devices { action: None } => list_devices()?,
+        Commands::Devices {
+            action: Some(DeviceCommands::Test { name, seconds, play, save }),
+        } => test_device(config, name, seconds, play, save).await?,
         Commands::Status => show_status(config).await?,
+        Commands::Events => watch_events(config).await?,
+        Commands::LogLevel { level } => set_log_level(config, &level).await?,
         Commands::InitConfig => init_config()?,
         Commands::DownloadModel { model } => download_model(&model).await?,
     }
@@ -94,14 +159,15 @@ async fn main() -> anyhow::Result<()> {
     Ok(())
 }
 
-async fn run_app(config: AppConfig) -> anyhow::Result<()> {
+async fn run_app(config: AppConfig, log_handle: LogHandle, no_tray: bool) -> anyhow::Result<()> {
     info!("Starting SuperWhisper Linux");
 
     // Initialize directories
     config::init_dirs()?;
 
     // Create application
-    let app = App::new(config.clone()).await?;
+    let mut app = App::new(config.clone()).await?;
+    app.set_log_handle(log_handle);
 
     // Initialize STT provider
     if let Err(e) = app.init_provider().await {
@@ -110,44 +176,152 @@ async fn run_app(config: AppConfig) -> anyhow::Result<()> {
         return Err(e.into());
     }
 
-    // Start IPC server
-    let socket_path = config.socket_path();
-    let ipc_server = IpcServer::new(socket_path.clone());
-    let mut cmd_rx = ipc_server.start().await?;
+    // Start IPC server (the tray sends commands in-process and keeps working
+    // without it)
+    let (cmd_tx, mut cmd_rx) = mpsc::channel(32);
+    app.set_command_sender(cmd_tx.clone());
+    let ipc_server = IpcServer::new(config.socket_path());
+    let status = app.status_reporter();
+    let ipc_result = ipc_server
+        .start(cmd_tx.clone(), app.event_sender(), move || status.status())
+        .await;
 
     // Initialize system tray (keep _tray alive to maintain the tray service)
-    let _tray = TrayIcon::new(socket_path.to_string_lossy().to_string())?;
-    let tray_handle = _tray.handle();
+    let _tray = if config.tray.enabled && !no_tray {
+        match TrayIcon::new(cmd_tx.clone(), &config.tray) {
+            Ok(tray) => Some(tray),
+            Err(e) => {
+                warn!("Failed to start system tray, continuing without it: {}", e);
+                None
+            }
+        }
+    } else {
+        info!("System tray disabled, running headless");
+        None
+    };
+
+    // Start the built-in hotkey listener (if configured)
+    let hotkeys = match HotkeyListener::new(&config.hotkey, cmd_tx) {
+        Ok(listener) => listener,
+        Err(e) => {
+            warn!("Failed to start hotkey listener: {}", e);
+            None
+        }
+    };
+
+    // Without IPC or a tray, losing the hotkeys leaves no way to control
+    // the app
+    let hotkeys_only = ipc_result.is_err() && _tray.is_none();
+    if let Err(e) = ipc_result {
+        if hotkeys_only && hotkeys.is_none() {
+            return Err(e.into());
+        }
+        error!("Failed to start IPC server on {:?}: {}", config.socket_path(), e);
+        warn!("Only the tray and built-in hotkeys can control the app");
+    }
+    let mut hotkeys_running = hotkeys.is_some();
 
     // Spawn task to sync app state with tray
-    let mut state_rx = app.state_receiver();
-    tokio::spawn(async move {
-        loop {
-            if state_rx.changed().await.is_err() {
-                break;
+    if let Some(tray_handle) = _tray.as_ref().map(TrayIcon::handle) {
+        let mut state_rx = app.state_receiver();
+        tokio::spawn(async move {
+            loop {
+                if state_rx.changed().await.is_err() {
+                    break;
+                }
+                let state = *state_rx.borrow();
+                tray_handle.set_state(state.to_tray_state());
             }
-            let state = *state_rx.borrow();
-            tray_handle.set_state(state.to_tray_state());
-        }
-    });
+        });
+    }
+
+    // Spawn task to show the input level in the tray while recording
+    if let Some(tray_handle) = _tray.as_ref().map(TrayIcon::handle) {
+        let mut level_rx = app.level_receiver();
+        tokio::spawn(async move {
+            while level_rx.changed().await.is_ok() {
+                let level = *level_rx.borrow();
+                tray_handle.set_level(level.meter());
+            }
+        });
+    }
+
+    // Spawn task to keep the tray menu in sync with settings, history and
+    // the input devices present
+    if let Some(tray_handle) = _tray.as_ref().map(TrayIcon::handle) {
+        let mut settings_rx = app.settings_receiver();
+        let mut recent_rx = app.recent_receiver();
+        let mut devices_rx = app.devices_receiver();
+        let configured_languages = config.tray.languages.clone();
+        tokio::spawn(async move {
+            loop {
+                let settings = settings_rx.borrow_and_update().clone();
+                let recent = recent_rx
+                    .borrow_and_update()
+                    .iter()
+                    .map(|entry| entry.text.clone())
+                    .collect();
+
+                let mut languages = configured_languages.clone();
+                if !languages.contains(&settings.language) {
+                    languages.push(settings.language.clone());
+                }
+
+                let devices = devices_rx.borrow_and_update().clone();
+
+                tray_handle.set_menu(MenuState {
+                    recent,
+                    providers: ProviderType::ALL.iter().map(|p| p.to_string()).collect(),
+                    provider: settings.provider.to_string(),
+                    languages,
+                    language: settings.language,
+                    devices,
+                    device: settings.input_device,
+                    auto_paste: settings.auto_paste,
+                });
+
+                tokio::select! {
+                    r = settings_rx.changed() => if r.is_err() { break },
+                    r = recent_rx.changed() => if r.is_err() { break },
+                    r = devices_rx.changed() => if r.is_err() { break },
+                }
+            }
+        });
+    }
 
     info!("Ready! Send commands via: echo 'toggle' | nc -U {:?}", config.socket_path());
 
     // Main event loop
     loop {
         tokio::select! {
-            Some(cmd) = cmd_rx.recv() => {
-                match app.handle_command(cmd).await {
-                    Ok(_) => {}
+            Some(mut request) = cmd_rx.recv() => {
+                match app.handle_command(request.command.clone()).await {
+                    Ok(_) if request.command == IpcCommand::Status => {
+                        request.respond(Ok(app.status()));
+                    }
+                    Ok(_) => request.respond(Ok("OK".to_string())),
                     Err(AppError::Other(msg)) if msg == "Shutdown" => {
+                        request.respond(Ok("OK".to_string()));
                         info!("Shutting down");
                         break;
                     }
                     Err(e) => {
                         error!("Command error: {}", e);
+                        app.notify_error(command_error_summary(&request.command), e.to_string());
+                        request.respond(Err(e.to_string()));
                     }
                 }
             }
+            reason = hotkeys_failed(hotkeys.as_ref()), if hotkeys_running => {
+                hotkeys_running = false;
+                if hotkeys_only {
+                    anyhow::bail!(
+                        "Hotkeys stopped working and nothing else can control the app: {}",
+                        reason
+                    );
+                }
+                app.notify_error("Hotkeys unavailable", reason);
+            }
             _ = tokio::signal::ctrl_c() => {
                 info!("Received Ctrl+C, shutting down");
                 break;
@@ -158,17 +332,50 @@ async fn run_app(config: AppConfig) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Resolves when the hotkey backend fails after startup
+async fn hotkeys_failed(hotkeys: Option<&HotkeyListener>) -> String {
+    match hotkeys {
+        Some(hotkeys) => hotkeys.failed().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Notification summary for a failed command
+fn command_error_summary(command: &IpcCommand) -> &'static str {
+    match command {
+        IpcCommand::Toggle { .. }
+        | IpcCommand::Start
+        | IpcCommand::Stop { .. }
+        | IpcCommand::Rollover
+        | IpcCommand::PttDown
+        | IpcCommand::PttUp => "Recording failed",
+        IpcCommand::Provider(_) => "Provider switch failed",
+        IpcCommand::Copy(_) | IpcCommand::Paste(_) => "Copy failed",
+        IpcCommand::OpenConfig | IpcCommand::OpenHistory => "Could not open file",
+        _ => "Command failed",
+    }
+}
+
 async fn run_trigger(config: AppConfig, cmd: TriggerCommands) -> anyhow::Result<()> {
     let client = IpcClient::new(config.socket_path());
 
     let command = match cmd {
-        TriggerCommands::Toggle => "toggle",
-        TriggerCommands::Start => "start",
-        TriggerCommands::Stop => "stop",
-        TriggerCommands::Cancel => "cancel",
+        TriggerCommands::Toggle { output: None } => "toggle".to_string(),
+        TriggerCommands::Toggle { output: Some(output) } => format!("toggle {}", output),
+        TriggerCommands::Start => "start".to_string(),
+        TriggerCommands::Stop { output: None } => "stop".to_string(),
+        TriggerCommands::Stop { output: Some(output) } => format!("stop {}", output),
+        TriggerCommands::Rollover => "rollover".to_string(),
+        TriggerCommands::Cancel => "cancel".to_string(),
+        TriggerCommands::PttDown => "ptt-down".to_string(),
+        TriggerCommands::PttUp => "ptt-up".to_string(),
     };
 
-    match client.send(command).await {
+    match client.send(&command).await {
+        Ok(response) if response.starts_with("ERROR") => {
+            eprintln!("{}", response);
+            std::process::exit(1);
+        }
         Ok(response) => {
             println!("{}", response);
         }
@@ -183,9 +390,121 @@ async fn run_trigger(config: AppConfig, cmd: TriggerCommands) -> anyhow::Result<
 
 fn list_devices() -> anyhow::Result<()> {
     println!("Available audio input devices:");
-    for device in AudioCapture::list_devices()? {
-        println!("  - {}", device);
+    for device in audio::describe_input_devices()? {
+        let marker = if device.is_default { " (default)" } else { "" };
+        println!("  - {}{}", device.name, marker);
+
+        if let Some((channels, rate, format)) = &device.default_config {
+            println!("      default: {} ch, {} Hz, {}", channels, rate, format);
+        }
+        for range in &device.configs {
+            let rates = if range.min_sample_rate == range.max_sample_rate {
+                format!("{} Hz", range.min_sample_rate)
+            } else {
+                format!("{}-{} Hz", range.min_sample_rate, range.max_sample_rate)
+            };
+            println!(
+                "      supports: {} ch, {}, {}",
+                range.channels, rates, range.sample_format
+            );
+        }
+    }
+    Ok(())
+}
+
+async fn test_device(
+    config: AppConfig,
+    name: Option<String>,
+    seconds: f32,
+    play: bool,
+    save: Option<PathBuf>,
+) -> anyhow::Result<()> {
+    let sample_rate = config.audio.sample_rate;
+    let capture_config = match &name {
+        // Exact name first, then the name as a pattern
+        Some(name) => CaptureConfig {
+            sample_rate,
+            device_name: Some(name.clone()),
+            preferences: vec![name.clone()],
+            downmix: config.audio.downmix,
+            downmix_channel: config.audio.downmix_channel,
+            buffer_size: config.audio.buffer_size,
+        },
+        None => CaptureConfig {
+            sample_rate,
+            device_name: config.audio.input_device.clone(),
+            preferences: config.audio.input_devices.clone(),
+            downmix: config.audio.downmix,
+            downmix_channel: config.audio.downmix_channel,
+            buffer_size: config.audio.buffer_size,
+        },
+    };
+
+    let capture = AudioCapture::new(capture_config)?;
+    if let Some(name) = &name {
+        let device = capture.device_name();
+        if device != name && !DevicePattern::parse(name)?.matches(device) {
+            anyhow::bail!("No input device matches '{}'", name);
+        }
+    }
+
+    println!("Recording {:.1}s from {}...", seconds, capture.device_name());
+    let (stream, mut rx) = capture.start()?;
+    let mut samples = Vec::new();
+    let mut chunks = Vec::new();
+    let deadline = tokio::time::sleep(Duration::from_secs_f32(seconds.max(0.1)));
+    tokio::pin!(deadline);
+    loop {
+        tokio::select! {
+            _ = &mut deadline => break,
+            chunk = rx.recv() => match chunk {
+                Some(chunk) => {
+                    chunks.push(AudioLevel::measure(&chunk));
+                    samples.extend(chunk);
+                }
+                None => break,
+            },
+        }
+    }
+    capture.stop();
+    drop(stream);
+
+    if samples.is_empty() {
+        anyhow::bail!("No audio received from {}", capture.device_name());
+    }
+
+    let level = AudioLevel::measure(&samples);
+    let threshold = config.audio.silence_threshold;
+    let silent = chunks.iter().filter(|l| l.peak < threshold).count();
+    let clipped = samples.iter().filter(|s| s.abs() >= 0.999).count();
+    println!(
+        "Captured {:.2}s at {} Hz",
+        samples.len() as f32 / sample_rate as f32,
+        sample_rate
+    );
+    println!("  peak:    {:.1} dBFS", level.peak_db());
+    println!("  rms:     {:.1} dBFS", level.rms_db());
+    println!(
+        "  silent:  {:.0}% of chunks below threshold {}",
+        silent as f32 * 100.0 / chunks.len() as f32,
+        threshold
+    );
+    println!("  clipped: {} samples", clipped);
+    println!("  dropped: {} samples", rx.dropped());
+    if level.peak < threshold {
+        println!("Warning: the input never rose above the silence threshold; check the microphone and its gain");
     }
+
+    if let Some(path) = save {
+        std::fs::write(&path, audio::encode_wav(&samples, sample_rate)?)?;
+        println!("Saved to {:?}", path);
+    }
+
+    if play {
+        println!("Playing back...");
+        tokio::task::spawn_blocking(move || audio::play_samples(&samples, sample_rate)).await??;
+    }
+
     Ok(())
 }
 
@@ -205,8 +524,46 @@ async fn show_status(config: AppConfig) -> anyhow::Result<()> {
     Ok(())
 }
 
+async fn watch_events(config: AppConfig) -> anyhow::Result<()> {
+    let client = IpcClient::new(config.socket_path());
+
+    let mut lines = match client.events().await {
+        Ok(lines) => lines,
+        Err(e) => {
+            eprintln!("Error: {}. Is the app running?", e);
+            std::process::exit(1);
+        }
+    };
+
+    while let Some(line) = lines.next_line().await? {
+        println!("{}", line);
+    }
+
+    Ok(())
+}
+
+async fn set_log_level(config: AppConfig, level: &str) -> anyhow::Result<()> {
+    let client = IpcClient::new(config.socket_path());
+
+    match client.send(&format!("log-level {}", level)).await {
+        Ok(response) if response.starts_with("ERROR") => {
+            eprintln!("{}", response);
+            std::process::exit(1);
+        }
+        Ok(response) => {
+            println!("{}", response);
+        }
+        Err(e) => {
+            eprintln!("Error: {}. Is the app running?", e);
+            std::process::exit(1);
+        }
+    }
+
+    Ok(())
+}
+
 fn init_config() -> anyhow::Result<()> {
-    let config_path = config::config_dir().join("config.toml");
+    let config_path = config::config_path();
 
     if config_path.exists() {
         eprintln!("Configuration file already exists at {:?}", config_path);
@@ -0,0 +1,674 @@
+use dbus::arg::PropMap;
+use dbus::blocking::Connection;
+use dbus::message::MatchRule;
+use std::collections::HashMap;
+use std::sync::mpsc;
+use std::time::Duration;
+use tracing::{debug, error, info, warn};
+
+use crate::clipboard;
+use crate::config::NotificationsConfig;
+use crate::error::NotificationError;
+
+const NOTIFICATIONS_BUS: &str = "org.freedesktop.Notifications";
+const NOTIFICATIONS_PATH: &str = "/org/freedesktop/Notifications";
+const NOTIFICATIONS_IFACE: &str = "org.freedesktop.Notifications";
+
+const APP_NAME: &str = "SuperWhisper";
+const COPY_ACTION: &str = "copy";
+
+/// A notification to show
+#[derive(Debug, Clone)]
+enum Notification {
+    /// A failure, with a summary naming what failed
+    Error(&'static str, String),
+    Warning(String),
+    Transcript(String),
+}
+
+impl Notification {
+    fn summary(&self) -> &'static str {
+        match self {
+            Self::Error(summary, _) => summary,
+            Self::Warning(_) => "Recording warning",
+            Self::Transcript(_) => "Transcription",
+        }
+    }
+
+    fn icon(&self) -> &'static str {
+        match self {
+            Self::Error(..) => "dialog-error",
+            Self::Warning(_) => "dialog-warning",
+            Self::Transcript(_) => "audio-input-microphone",
+        }
+    }
+
+    fn text(&self) -> &str {
+        match self {
+            Self::Error(_, text) | Self::Warning(text) | Self::Transcript(text) => text,
+        }
+    }
+
+    /// Body as sent to a server that does (or does not) read it as markup
+    fn body(&self, markup: bool) -> String {
+        if markup {
+            escape_markup(self.text())
+        } else {
+            self.text().to_string()
+        }
+    }
+
+    fn actions(&self) -> Vec<&'static str> {
+        match self {
+            Self::Transcript(_) => vec![COPY_ACTION, "Copy"],
+            _ => vec![],
+        }
+    }
+}
+
+/// Signals from the notification server
+enum ServerEvent {
+    ActionInvoked(u32, String),
+    Closed(u32),
+}
+
+/// What the running notification server supports
+struct ServerInfo {
+    /// Bodies are read as markup, so `&`, `<` and `>` must be escaped
+    body_markup: bool,
+}
+
+/// Desktop notifications via org.freedesktop.Notifications
+///
+/// The D-Bus connection lives on its own thread (like the tray service) so
+/// action signals can be processed without blocking the app. A notification
+/// server that is missing at startup (or restarts) is looked for again when
+/// the next notification is due.
+#[derive(Clone)]
+pub struct Notifier {
+    config: NotificationsConfig,
+    tx: mpsc::Sender<Notification>,
+}
+
+impl Notifier {
+    /// Connect to the session bus and start the notification thread.
+    /// Must be called from within a tokio runtime (actions copy via wl-copy).
+    pub fn new(config: &NotificationsConfig) -> Result<Self, NotificationError> {
+        let runtime = tokio::runtime::Handle::current();
+        Self::start(config, connect_session, move |text| {
+            runtime.spawn(async move {
+                if let Err(e) = clipboard::set_clipboard(&text).await {
+                    error!("Failed to copy transcript: {}", e);
+                }
+            });
+        })
+    }
+
+    /// Start the notification thread on the bus `connect` opens; `copy` runs
+    /// when a transcript's "Copy" action is invoked
+    fn start(
+        config: &NotificationsConfig,
+        connect: impl FnOnce() -> Result<Connection, NotificationError> + Send + 'static,
+        copy: impl Fn(String) + Send + 'static,
+    ) -> Result<Self, NotificationError> {
+        let (tx, rx) = mpsc::channel::<Notification>();
+        let (ready_tx, ready_rx) = mpsc::channel::<Result<(), NotificationError>>();
+        let timeout = config.timeout_ms;
+
+        std::thread::spawn(move || {
+            let conn = match connect() {
+                Ok(conn) => {
+                    let _ = ready_tx.send(Ok(()));
+                    conn
+                }
+                Err(e) => {
+                    let _ = ready_tx.send(Err(e));
+                    return;
+                }
+            };
+            let server = match check_server(&conn) {
+                Ok(server) => Some(server),
+                Err(e) => {
+                    info!("{}, will look again when notifying", e);
+                    None
+                }
+            };
+            run(conn, server, rx, copy, timeout);
+        });
+
+        ready_rx
+            .recv()
+            .map_err(|e| NotificationError::DbusError(e.to_string()))??;
+
+        info!("Desktop notifications initialized");
+        Ok(Self {
+            config: config.clone(),
+            tx,
+        })
+    }
+
+    /// Notify about a failure; `summary` says what failed (e.g.
+    /// "Transcription failed")
+    pub fn error(&self, summary: &'static str, message: impl Into<String>) {
+        if self.config.errors {
+            let _ = self.tx.send(Notification::Error(summary, message.into()));
+        }
+    }
+
+    /// Notify about a recording problem that did not stop it from working
+    pub fn warning(&self, message: impl Into<String>) {
+        if self.config.warnings {
+            let _ = self.tx.send(Notification::Warning(message.into()));
+        }
+    }
+
+    /// Notify about a finished transcript (with a "Copy" action)
+    pub fn transcript(&self, text: impl Into<String>) {
+        if self.config.transcripts {
+            let _ = self.tx.send(Notification::Transcript(text.into()));
+        }
+    }
+}
+
+fn connect_session() -> Result<Connection, NotificationError> {
+    Connection::new_session().map_err(|e| NotificationError::DbusError(e.to_string()))
+}
+
+/// Make sure a notification server is actually running and ask what it
+/// supports
+fn check_server(conn: &Connection) -> Result<ServerInfo, NotificationError> {
+    let has_owner: (bool,) = conn
+        .with_proxy(
+            "org.freedesktop.DBus",
+            "/org/freedesktop/DBus",
+            Duration::from_secs(2),
+        )
+        .method_call("org.freedesktop.DBus", "NameHasOwner", (NOTIFICATIONS_BUS,))
+        .map_err(|e| NotificationError::DbusError(e.to_string()))?;
+
+    if !has_owner.0 {
+        return Err(NotificationError::ServerUnavailable);
+    }
+
+    let (capabilities,): (Vec<String>,) = conn
+        .with_proxy(
+            NOTIFICATIONS_BUS,
+            NOTIFICATIONS_PATH,
+            Duration::from_secs(2),
+        )
+        .method_call(NOTIFICATIONS_IFACE, "GetCapabilities", ())
+        .map_err(|e| NotificationError::DbusError(e.to_string()))?;
+    debug!("Notification server capabilities: {:?}", capabilities);
+
+    Ok(ServerInfo {
+        body_markup: capabilities.iter().any(|c| c == "body-markup"),
+    })
+}
+
+/// Show a notification, looking for the server first if it was missing or
+/// the last `Notify` failed; a failure here is retried once in case the
+/// server was replaced
+fn notify(
+    conn: &Connection,
+    server: &mut Option<ServerInfo>,
+    notification: &Notification,
+    timeout: i32,
+) -> Result<u32, NotificationError> {
+    let mut retried = server.is_none();
+    loop {
+        let info = match server {
+            Some(info) => info,
+            None => server.insert(check_server(conn)?),
+        };
+        match send(conn, notification, info, timeout) {
+            Ok(id) => return Ok(id),
+            Err(e) if retried => {
+                *server = None;
+                return Err(e);
+            }
+            Err(e) => {
+                debug!("Notify failed ({}), looking for the server again", e);
+                *server = None;
+                retried = true;
+            }
+        }
+    }
+}
+
+fn run(
+    conn: Connection,
+    mut server: Option<ServerInfo>,
+    rx: mpsc::Receiver<Notification>,
+    copy: impl Fn(String),
+    timeout: i32,
+) {
+    let (event_tx, event_rx) = mpsc::channel::<ServerEvent>();
+
+    let action_tx = event_tx.clone();
+    let action_rule = MatchRule::new_signal(NOTIFICATIONS_IFACE, "ActionInvoked");
+    if let Err(e) = conn.add_match(action_rule, move |(id, action): (u32, String), _, _| {
+        let _ = action_tx.send(ServerEvent::ActionInvoked(id, action));
+        true
+    }) {
+        warn!("Failed to subscribe to notification actions: {}", e);
+    }
+
+    let closed_rule = MatchRule::new_signal(NOTIFICATIONS_IFACE, "NotificationClosed");
+    if let Err(e) = conn.add_match(closed_rule, move |(id, _reason): (u32, u32), _, _| {
+        let _ = event_tx.send(ServerEvent::Closed(id));
+        true
+    }) {
+        warn!("Failed to subscribe to notification close events: {}", e);
+    }
+
+    // Transcripts of notifications that still have a live "Copy" action
+    let mut pending: HashMap<u32, String> = HashMap::new();
+
+    loop {
+        match rx.recv_timeout(Duration::from_millis(100)) {
+            Ok(notification) => match notify(&conn, &mut server, &notification, timeout) {
+                Ok(id) => {
+                    if let Notification::Transcript(text) = notification {
+                        pending.insert(id, text);
+                    }
+                }
+                Err(NotificationError::ServerUnavailable) => {
+                    debug!("No notification server, dropped: {}", notification.text());
+                }
+                Err(e) => warn!("Failed to show notification: {}", e),
+            },
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+            Err(mpsc::RecvTimeoutError::Disconnected) => break,
+        }
+
+        if let Err(e) = conn.process(Duration::ZERO) {
+            error!("Notification D-Bus error: {}", e);
+        }
+
+        while let Ok(event) = event_rx.try_recv() {
+            match event {
+                ServerEvent::ActionInvoked(id, action) if action == COPY_ACTION => {
+                    if let Some(text) = pending.remove(&id) {
+                        debug!("Copy action invoked on notification {}", id);
+                        copy(text);
+                    }
+                }
+                ServerEvent::ActionInvoked(..) => {}
+                ServerEvent::Closed(id) => {
+                    pending.remove(&id);
+                }
+            }
+        }
+    }
+
+    debug!("Notification thread finished");
+}
+
+fn send(
+    conn: &Connection,
+    notification: &Notification,
+    server: &ServerInfo,
+    timeout: i32,
+) -> Result<u32, NotificationError> {
+    let summary = notification.summary();
+    let proxy = conn.with_proxy(NOTIFICATIONS_BUS, NOTIFICATIONS_PATH, Duration::from_secs(2));
+    let (id,): (u32,) = proxy
+        .method_call(
+            NOTIFICATIONS_IFACE,
+            "Notify",
+            (
+                APP_NAME,
+                0u32,
+                notification.icon(),
+                summary,
+                notification.body(server.body_markup),
+                notification.actions(),
+                PropMap::new(),
+                timeout,
+            ),
+        )
+        .map_err(|e| NotificationError::DbusError(e.to_string()))?;
+
+    debug!("Notification {} shown: {}", id, summary);
+    Ok(id)
+}
+
+/// Escape the characters a markup body would interpret
+fn escape_markup(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dbus::channel::{Channel, MatchingReceiver, Sender as _};
+    use dbus::message::Message;
+    use std::io::{BufRead, BufReader};
+    use std::process::{Child, Command, Stdio};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    const WAIT: Duration = Duration::from_secs(5);
+
+    /// A private session bus
+    struct Bus {
+        daemon: Child,
+        address: String,
+    }
+
+    impl Bus {
+        fn start() -> Option<Self> {
+            let mut daemon = Command::new("dbus-daemon")
+                .args(["--session", "--nofork", "--print-address=1"])
+                .stdout(Stdio::piped())
+                .stderr(Stdio::null())
+                .spawn()
+                .ok()?;
+            let mut address = String::new();
+            BufReader::new(daemon.stdout.take()?)
+                .read_line(&mut address)
+                .ok()?;
+            Some(Self {
+                daemon,
+                address: address.trim().to_string(),
+            })
+        }
+
+        fn connect(address: &str) -> Result<Connection, NotificationError> {
+            let mut channel = Channel::open_private(address)
+                .map_err(|e| NotificationError::DbusError(e.to_string()))?;
+            channel
+                .register()
+                .map_err(|e| NotificationError::DbusError(e.to_string()))?;
+            Ok(Connection::from(channel))
+        }
+    }
+
+    impl Drop for Bus {
+        fn drop(&mut self) {
+            let _ = self.daemon.kill();
+            let _ = self.daemon.wait();
+        }
+    }
+
+    /// Arguments of a `Notify` call
+    #[derive(Debug, PartialEq)]
+    struct NotifyCall {
+        app_name: String,
+        icon: String,
+        summary: String,
+        body: String,
+        actions: Vec<String>,
+        timeout: i32,
+    }
+
+    /// Mock notification server: owns the name, reports `capabilities` and
+    /// records `Notify` calls, answering each with the next id
+    struct Server {
+        stop: Arc<AtomicBool>,
+        thread: Option<std::thread::JoinHandle<()>>,
+    }
+
+    impl Server {
+        fn start(
+            address: String,
+            capabilities: &[&str],
+            calls: mpsc::Sender<(u32, NotifyCall)>,
+        ) -> Self {
+            let capabilities: Vec<String> = capabilities.iter().map(|c| c.to_string()).collect();
+            let stop = Arc::new(AtomicBool::new(false));
+            let (ready_tx, ready_rx) = mpsc::channel();
+            let thread_stop = stop.clone();
+            let thread = std::thread::spawn(move || {
+                let conn = Bus::connect(&address).unwrap();
+                conn.request_name(NOTIFICATIONS_BUS, false, true, true)
+                    .unwrap();
+
+                let mut next_id = 0u32;
+                conn.start_receive(
+                    MatchRule::new_method_call(),
+                    Box::new(move |msg: Message, conn: &Connection| {
+                        match msg.member().as_deref() {
+                            Some("Notify") => {}
+                            Some("GetCapabilities") => {
+                                let _ = conn.send(msg.method_return().append1(&capabilities));
+                                return true;
+                            }
+                            _ => return true,
+                        }
+                        type Args = (
+                            String,
+                            u32,
+                            String,
+                            String,
+                            String,
+                            Vec<String>,
+                            PropMap,
+                            i32,
+                        );
+                        let (app_name, _, icon, summary, body, actions, _, timeout): Args =
+                            msg.read_all().unwrap();
+                        next_id += 1;
+                        let _ = calls.send((
+                            next_id,
+                            NotifyCall {
+                                app_name,
+                                icon,
+                                summary,
+                                body,
+                                actions,
+                                timeout,
+                            },
+                        ));
+                        let _ = conn.send(msg.method_return().append1(next_id));
+                        true
+                    }),
+                );
+
+                let _ = ready_tx.send(());
+                while !thread_stop.load(Ordering::Relaxed) {
+                    let _ = conn.process(Duration::from_millis(50));
+                }
+            });
+            ready_rx.recv_timeout(WAIT).unwrap();
+
+            Self {
+                stop,
+                thread: Some(thread),
+            }
+        }
+    }
+
+    impl Drop for Server {
+        fn drop(&mut self) {
+            self.stop.store(true, Ordering::Relaxed);
+            if let Some(thread) = self.thread.take() {
+                let _ = thread.join();
+            }
+        }
+    }
+
+    /// Emit `ActionInvoked` as the server would when an action is clicked
+    fn invoke_action(address: &str, id: u32, action: &str) {
+        let conn = Bus::connect(address).unwrap();
+        let signal = Message::new_signal(NOTIFICATIONS_PATH, NOTIFICATIONS_IFACE, "ActionInvoked")
+            .unwrap()
+            .append2(id, action);
+        conn.send(signal).unwrap();
+        conn.channel().flush();
+    }
+
+    #[test]
+    fn notifies_and_copies_transcripts() {
+        let Some(bus) = Bus::start() else {
+            eprintln!("dbus-daemon not available, skipping");
+            return;
+        };
+        let (calls_tx, calls_rx) = mpsc::channel();
+        let _server = Server::start(bus.address.clone(), &[], calls_tx);
+
+        let config = NotificationsConfig {
+            transcripts: true,
+            timeout_ms: 5000,
+            ..Default::default()
+        };
+        let (copy_tx, copy_rx) = mpsc::channel();
+        let address = bus.address.clone();
+        let notifier = Notifier::start(
+            &config,
+            move || Bus::connect(&address),
+            move |text| {
+                let _ = copy_tx.send(text);
+            },
+        )
+        .unwrap();
+
+        notifier.error("Transcription failed", "Model file not found");
+        let (_, call) = calls_rx.recv_timeout(WAIT).unwrap();
+        assert_eq!(
+            call,
+            NotifyCall {
+                app_name: APP_NAME.into(),
+                icon: "dialog-error".into(),
+                summary: "Transcription failed".into(),
+                body: "Model file not found".into(),
+                actions: vec![],
+                timeout: 5000,
+            }
+        );
+
+        notifier.warning("Audio dropped (20 ms lost)");
+        let (_, call) = calls_rx.recv_timeout(WAIT).unwrap();
+        assert_eq!(
+            call,
+            NotifyCall {
+                app_name: APP_NAME.into(),
+                icon: "dialog-warning".into(),
+                summary: "Recording warning".into(),
+                body: "Audio dropped (20 ms lost)".into(),
+                actions: vec![],
+                timeout: 5000,
+            }
+        );
+
+        notifier.transcript("hello world");
+        let (id, call) = calls_rx.recv_timeout(WAIT).unwrap();
+        assert_eq!(
+            call,
+            NotifyCall {
+                app_name: APP_NAME.into(),
+                icon: "audio-input-microphone".into(),
+                summary: "Transcription".into(),
+                body: "hello world".into(),
+                actions: vec![COPY_ACTION.into(), "Copy".into()],
+                timeout: 5000,
+            }
+        );
+
+        // Other notifications' actions are ignored
+        invoke_action(&bus.address, id + 1, COPY_ACTION);
+        assert!(copy_rx.recv_timeout(Duration::from_millis(300)).is_err());
+
+        invoke_action(&bus.address, id, COPY_ACTION);
+        assert_eq!(copy_rx.recv_timeout(WAIT).unwrap(), "hello world");
+
+        // The action is used up
+        invoke_action(&bus.address, id, COPY_ACTION);
+        assert!(copy_rx.recv_timeout(Duration::from_millis(300)).is_err());
+    }
+
+    #[test]
+    fn respects_disabled_kinds() {
+        let Some(bus) = Bus::start() else {
+            eprintln!("dbus-daemon not available, skipping");
+            return;
+        };
+        let (calls_tx, calls_rx) = mpsc::channel();
+        let _server = Server::start(bus.address.clone(), &[], calls_tx);
+
+        let config = NotificationsConfig {
+            errors: false,
+            warnings: false,
+            transcripts: false,
+            ..Default::default()
+        };
+        let address = bus.address.clone();
+        let notifier = Notifier::start(&config, move || Bus::connect(&address), |_| {}).unwrap();
+
+        notifier.error("Command failed", "ignored");
+        notifier.warning("ignored");
+        notifier.transcript("ignored");
+        assert!(calls_rx.recv_timeout(Duration::from_millis(300)).is_err());
+    }
+
+    #[test]
+    fn finds_a_server_that_starts_late() {
+        let Some(bus) = Bus::start() else {
+            eprintln!("dbus-daemon not available, skipping");
+            return;
+        };
+        let address = bus.address.clone();
+        let notifier = Notifier::start(
+            &NotificationsConfig::default(),
+            move || Bus::connect(&address),
+            |_| {},
+        )
+        .unwrap();
+
+        // Nobody to show it, so it is dropped rather than queued
+        notifier.warning("too early");
+
+        let (calls_tx, calls_rx) = mpsc::channel();
+        let server = Server::start(bus.address.clone(), &[], calls_tx);
+        notifier.warning("on time");
+        let (_, call) = calls_rx.recv_timeout(WAIT).unwrap();
+        assert_eq!(call.body, "on time");
+        assert!(calls_rx.recv_timeout(Duration::from_millis(300)).is_err());
+
+        // A replacement server is picked up, along with its capabilities
+        drop(server);
+        notifier.warning("while restarting");
+        let (calls_tx, calls_rx) = mpsc::channel();
+        let _server = Server::start(bus.address.clone(), &["body-markup"], calls_tx);
+        notifier.warning("a < b");
+        let (_, call) = calls_rx.recv_timeout(WAIT).unwrap();
+        assert_eq!(call.body, "a &lt; b");
+        assert!(calls_rx.recv_timeout(Duration::from_millis(300)).is_err());
+    }
+
+    #[test]
+    fn escapes_bodies_only_for_markup_servers() {
+        let Some(bus) = Bus::start() else {
+            eprintln!("dbus-daemon not available, skipping");
+            return;
+        };
+        let config = NotificationsConfig {
+            transcripts: true,
+            ..Default::default()
+        };
+
+        for (capabilities, expected) in [
+            (vec!["body", "body-markup"], "a &lt; b &amp;&amp; c &gt; d"),
+            (vec!["body"], "a < b && c > d"),
+        ] {
+            let (calls_tx, calls_rx) = mpsc::channel();
+            let _server = Server::start(bus.address.clone(), &capabilities, calls_tx);
+            let address = bus.address.clone();
+            let notifier =
+                Notifier::start(&config, move || Bus::connect(&address), |_| {}).unwrap();
+
+            notifier.transcript("a < b && c > d");
+            let (_, call) = calls_rx.recv_timeout(WAIT).unwrap();
+            assert_eq!(call.body, expected, "capabilities {:?}", capabilities);
+        }
+    }
+}
@@ -0,0 +1,3 @@
+mod freedesktop;
+
+pub use freedesktop::Notifier;
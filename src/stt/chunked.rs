@@ -0,0 +1,438 @@
+use async_trait::async_trait;
+use futures::{StreamExt, TryStreamExt};
+use std::time::Instant;
+use tracing::debug;
+
+use crate::audio::AudioLevel;
+use crate::config::AppConfig;
+use crate::stt::{AudioData, SttProvider, SttResult, TranscriptionResult};
+
+/// Length of the frames compared when looking for a quiet cut point
+const FRAME_MS: u32 = 20;
+
+/// Audio past the chunk limit that is kept in the last chunk rather than sent
+/// on its own
+const MIN_TAIL_SECS: u32 = 1;
+
+/// Longest run of words removed when stitching overlapping chunks
+const MAX_OVERLAP_WORDS: usize = 12;
+
+/// A chunk of the recording, as sample offsets
+#[derive(Debug, Clone, Copy)]
+struct Chunk {
+    start: usize,
+    end: usize,
+    /// Starts inside the previous chunk (no silence was found to cut at)
+    overlaps: bool,
+}
+
+/// Wraps a provider so long recordings are split at quiet points into
+/// bounded chunks, transcribed separately and stitched back together.
+///
+/// Cloud providers transcribe chunks concurrently; local ones one at a time.
+pub struct ChunkedProvider {
+    inner: Box<dyn SttProvider>,
+    max_chunk: u32,
+    overlap_ms: u32,
+    silence_threshold: f32,
+    parallel_requests: usize,
+}
+
+impl ChunkedProvider {
+    pub fn new(inner: Box<dyn SttProvider>, config: &AppConfig) -> Self {
+        Self {
+            inner,
+            max_chunk: config.chunking.max_chunk_secs.max(2),
+            overlap_ms: config.chunking.overlap_ms,
+            silence_threshold: config.audio.silence_threshold,
+            parallel_requests: config.chunking.parallel_requests.max(1),
+        }
+    }
+
+    /// Plan the chunks for `audio`
+    fn split(&self, audio: &AudioData) -> Vec<Chunk> {
+        let rate = audio.sample_rate as usize;
+        let max = self.max_chunk as usize * rate;
+        let frame = (rate * FRAME_MS as usize / 1000).max(1);
+        // Never overlap by more than a quarter chunk, so every step advances
+        let overlap = (self.overlap_ms as usize * rate / 1000).min(max / 4);
+        let len = audio.samples.len();
+
+        let mut chunks = Vec::new();
+        let mut start = 0;
+        let mut overlaps = false;
+
+        while len - start > max + MIN_TAIL_SECS as usize * rate {
+            // Quietest frame in the second half of the window
+            let search = start + max / 2;
+            let (offset, level) = audio.samples[search..start + max]
+                .chunks(frame)
+                .enumerate()
+                .map(|(i, f)| (i * frame + f.len() / 2, AudioLevel::measure(f).rms))
+                .fold((0, f32::MAX), |best, candidate| {
+                    if candidate.1 < best.1 {
+                        candidate
+                    } else {
+                        best
+                    }
+                });
+
+            if level <= self.silence_threshold {
+                let cut = search + offset;
+                chunks.push(Chunk {
+                    start,
+                    end: cut,
+                    overlaps,
+                });
+                start = cut;
+                overlaps = false;
+            } else {
+                // Continuous speech: cut at the limit and repeat a little
+                let cut = start + max;
+                chunks.push(Chunk {
+                    start,
+                    end: cut,
+                    overlaps,
+                });
+                start = cut - overlap;
+                overlaps = overlap > 0;
+            }
+        }
+
+        chunks.push(Chunk {
+            start,
+            end: len,
+            overlaps,
+        });
+        chunks
+    }
+}
+
+#[async_trait]
+impl SttProvider for ChunkedProvider {
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+
+    fn is_local(&self) -> bool {
+        self.inner.is_local()
+    }
+
+    fn cost_per_minute(&self) -> Option<f64> {
+        self.inner.cost_per_minute()
+    }
+
+    async fn transcribe(
+        &self,
+        audio: &AudioData,
+        language: Option<&str>,
+    ) -> SttResult<TranscriptionResult> {
+        let chunks = self.split(audio);
+        if chunks.len() == 1 {
+            return self.inner.transcribe(audio, language).await;
+        }
+
+        let start = Instant::now();
+        let parallel = if self.inner.is_local() {
+            1
+        } else {
+            self.parallel_requests
+        };
+        debug!(
+            "Transcribing {:.1}s of audio in {} chunks ({} at a time)",
+            audio.duration().as_secs_f32(),
+            chunks.len(),
+            parallel
+        );
+
+        let pieces: Vec<AudioData> = chunks
+            .iter()
+            .map(|chunk| {
+                AudioData::new(
+                    audio.samples[chunk.start..chunk.end].to_vec(),
+                    audio.sample_rate,
+                )
+                .with_ends_recording(audio.ends_recording && chunk.end == audio.samples.len())
+            })
+            .collect();
+        let requests: Vec<_> = pieces
+            .iter()
+            .map(|piece| self.inner.transcribe(piece, language))
+            .collect();
+        let results: Vec<TranscriptionResult> = futures::stream::iter(requests)
+            .buffered(parallel)
+            .try_collect()
+            .await?;
+
+        let mut text = String::new();
+        for (chunk, result) in chunks.iter().zip(&results) {
+            if chunk.overlaps {
+                append_deduplicated(&mut text, &result.text);
+            } else {
+                append(&mut text, &result.text);
+            }
+        }
+
+        let mut combined = TranscriptionResult::new(text)
+            .with_processing_time(start.elapsed())
+            .with_suspect_segments(results.iter().map(|r| r.suspect_segments).sum());
+        if let Some(language) = results.iter().find_map(|r| r.language.clone()) {
+            combined = combined.with_language(language);
+        }
+        let confidences: Vec<f32> = results.iter().filter_map(|r| r.confidence).collect();
+        if !confidences.is_empty() {
+            combined = combined
+                .with_confidence(confidences.iter().sum::<f32>() / confidences.len() as f32);
+        }
+
+        Ok(combined)
+    }
+
+    async fn health_check(&self) -> SttResult<()> {
+        self.inner.health_check().await
+    }
+}
+
+/// Append `next` to `text`, separated by a space
+fn append(text: &mut String, next: &str) {
+    let next = next.trim();
+    if next.is_empty() {
+        return;
+    }
+    if !text.is_empty() {
+        text.push(' ');
+    }
+    text.push_str(next);
+}
+
+/// Append `next`, dropping its leading words if they repeat the end of `text`
+/// (the audio both chunks share)
+fn append_deduplicated(text: &mut String, next: &str) {
+    let previous: Vec<&str> = text.split_whitespace().collect();
+    let words: Vec<&str> = next.split_whitespace().collect();
+
+    let longest = MAX_OVERLAP_WORDS.min(previous.len()).min(words.len());
+    let repeated = (1..=longest)
+        .rev()
+        .find(|&n| {
+            previous[previous.len() - n..]
+                .iter()
+                .zip(&words[..n])
+                .all(|(a, b)| normalize(a) == normalize(b))
+        })
+        .unwrap_or(0);
+
+    if repeated > 0 {
+        debug!("Removed {} repeated word(s) between chunks", repeated);
+    }
+    append(text, &words[repeated..].join(" "));
+}
+
+/// Lowercase a word and strip punctuation for comparison
+fn normalize(word: &str) -> String {
+    word.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+    use std::sync::Mutex;
+
+    // 20 ms frames of 20 samples
+    const RATE: u32 = 1000;
+
+    /// Local provider answering with a fixed transcript per call, in order
+    struct Scripted(Mutex<VecDeque<&'static str>>);
+
+    #[async_trait]
+    impl SttProvider for Scripted {
+        fn name(&self) -> &'static str {
+            "scripted"
+        }
+
+        fn is_local(&self) -> bool {
+            true
+        }
+
+        async fn transcribe(
+            &self,
+            _audio: &AudioData,
+            _language: Option<&str>,
+        ) -> SttResult<TranscriptionResult> {
+            let text = self.0.lock().unwrap().pop_front().unwrap_or_default();
+            Ok(TranscriptionResult::new(text.to_string()))
+        }
+
+        async fn health_check(&self) -> SttResult<()> {
+            Ok(())
+        }
+    }
+
+    /// Drops "Thank you." where it ends the recording, like whisper's
+    /// trailing-segment blocklist rule
+    struct TrailingFilter(Scripted);
+
+    #[async_trait]
+    impl SttProvider for TrailingFilter {
+        fn name(&self) -> &'static str {
+            "trailing-filter"
+        }
+
+        fn is_local(&self) -> bool {
+            true
+        }
+
+        async fn transcribe(
+            &self,
+            audio: &AudioData,
+            language: Option<&str>,
+        ) -> SttResult<TranscriptionResult> {
+            let mut result = self.0.transcribe(audio, language).await?;
+            if audio.ends_recording {
+                if let Some(text) = result.text.strip_suffix("Thank you.") {
+                    result.text = text.trim_end().to_string();
+                }
+            }
+            Ok(result)
+        }
+
+        async fn health_check(&self) -> SttResult<()> {
+            Ok(())
+        }
+    }
+
+    fn scripted(texts: &[&'static str]) -> Scripted {
+        Scripted(Mutex::new(texts.iter().copied().collect()))
+    }
+
+    fn chunked(max_chunk: u32, overlap_ms: u32, texts: &[&'static str]) -> ChunkedProvider {
+        ChunkedProvider {
+            inner: Box::new(scripted(texts)),
+            max_chunk,
+            overlap_ms,
+            silence_threshold: 0.01,
+            parallel_requests: 1,
+        }
+    }
+
+    /// Continuous speech with no pause to cut at
+    fn speech(len: usize) -> AudioData {
+        AudioData::new(vec![0.5; len], RATE)
+    }
+
+    fn bounds(chunks: &[Chunk]) -> Vec<(usize, usize, bool)> {
+        chunks
+            .iter()
+            .map(|c| (c.start, c.end, c.overlaps))
+            .collect()
+    }
+
+    #[test]
+    fn keeps_audio_up_to_the_limit_whole() {
+        let provider = chunked(10, 1000, &[]);
+
+        assert_eq!(bounds(&provider.split(&speech(10000))), [(0, 10000, false)]);
+        // The short tail stays in the last chunk
+        assert_eq!(bounds(&provider.split(&speech(11000))), [(0, 11000, false)]);
+        assert_eq!(
+            bounds(&provider.split(&speech(11001))),
+            [(0, 10000, false), (9000, 11001, true)]
+        );
+    }
+
+    #[test]
+    fn overlaps_continuous_speech() {
+        let provider = chunked(10, 1000, &[]);
+        assert_eq!(
+            bounds(&provider.split(&speech(25000))),
+            [(0, 10000, false), (9000, 19000, true), (18000, 25000, true)]
+        );
+
+        // At most a quarter chunk, so every chunk moves forward
+        let provider = chunked(10, 5000, &[]);
+        assert_eq!(
+            bounds(&provider.split(&speech(12000))),
+            [(0, 10000, false), (7500, 12000, true)]
+        );
+
+        let provider = chunked(10, 0, &[]);
+        assert_eq!(
+            bounds(&provider.split(&speech(12000))),
+            [(0, 10000, false), (10000, 12000, false)]
+        );
+    }
+
+    #[test]
+    fn cuts_at_a_pause_without_overlap() {
+        let mut audio = speech(12000);
+        audio.samples[7000..7100].fill(0.0);
+        let provider = chunked(10, 1000, &[]);
+
+        // In the middle of the first silent frame
+        assert_eq!(
+            bounds(&provider.split(&audio)),
+            [(0, 7010, false), (7010, 12000, false)]
+        );
+    }
+
+    #[test]
+    fn removes_words_repeated_at_the_seam() {
+        let mut text = String::from("the quick brown fox");
+        append_deduplicated(&mut text, "Brown fox, jumps over");
+        assert_eq!(text, "the quick brown fox jumps over");
+
+        let mut text = String::from("hello world");
+        append_deduplicated(&mut text, "again");
+        assert_eq!(text, "hello world again");
+
+        // Only the words both chunks heard are dropped
+        let mut text = String::from("I said no");
+        append_deduplicated(&mut text, "no no more");
+        assert_eq!(text, "I said no no more");
+
+        let mut text = String::new();
+        append_deduplicated(&mut text, "hello");
+        assert_eq!(text, "hello");
+    }
+
+    #[tokio::test]
+    async fn stitches_overlapping_chunks() {
+        let provider = chunked(10, 1000, &["one two three four", "three four five"]);
+        let result = provider.transcribe(&speech(11001), None).await.unwrap();
+        assert_eq!(result.text, "one two three four five");
+    }
+
+    #[tokio::test]
+    async fn keeps_repeats_across_a_pause() {
+        let mut audio = speech(12000);
+        audio.samples[7000..7100].fill(0.0);
+        let provider = chunked(10, 1000, &["yes yes", "yes"]);
+
+        let result = provider.transcribe(&audio, None).await.unwrap();
+        assert_eq!(result.text, "yes yes yes");
+    }
+
+    #[tokio::test]
+    async fn only_the_last_chunk_ends_the_recording() {
+        let mut audio = speech(12000);
+        audio.samples[7000..7100].fill(0.0);
+        let mut provider = chunked(10, 1000, &[]);
+        provider.inner = Box::new(TrailingFilter(scripted(&[
+            "Let me know. Thank you.",
+            "Next point. Thank you.",
+        ])));
+
+        // The pause after the first "Thank you." is a chunk cut, not the end
+        let result = provider.transcribe(&audio, None).await.unwrap();
+        assert_eq!(result.text, "Let me know. Thank you. Next point.");
+
+        // Unsplit audio is the whole recording
+        provider.inner = Box::new(TrailingFilter(scripted(&["Short. Thank you."])));
+        let result = provider.transcribe(&speech(5000), None).await.unwrap();
+        assert_eq!(result.text, "Short.");
+    }
+}
@@ -1,9 +1,11 @@
 use async_trait::async_trait;
 use reqwest::Client;
 use serde::Deserialize;
+use std::sync::Arc;
 use std::time::Instant;
 use tracing::debug;
 
+use crate::audio::AudioEncoder;
 use crate::config::AppConfig;
 use crate::error::SttError;
 use crate::stt::{AudioData, SttProvider, SttResult, TranscriptionResult};
@@ -34,6 +36,7 @@ pub struct DeepgramProvider {
     api_key: String,
     model: String,
     features: Vec<String>,
+    encoder: Arc<dyn AudioEncoder>,
 }
 
 impl DeepgramProvider {
@@ -43,12 +46,16 @@ impl DeepgramProvider {
         })?;
 
         let client = Client::new();
+        let encoder = crate::audio::encoder(config.providers.deepgram.upload_format)
+            .map_err(|e| SttError::ProviderUnavailable(e.to_string()))?
+            .into();
 
         Ok(Self {
             client,
             api_key,
             model: config.providers.deepgram.model.clone(),
             features: config.providers.deepgram.features.clone(),
+            encoder,
         })
     }
 
@@ -97,11 +104,21 @@ impl SttProvider for DeepgramProvider {
 
         let start = Instant::now();
 
-        // Encode audio as WAV
-        let wav_data = crate::audio::encode_wav(&audio.samples, audio.sample_rate)
+        // Encode audio in the configured upload format (FLAC and Opus are
+        // CPU-heavy, so keep it off the async workers)
+        let encoder = self.encoder.clone();
+        let samples = audio.samples.clone();
+        let sample_rate = audio.sample_rate;
+        let data = tokio::task::spawn_blocking(move || encoder.encode(&samples, sample_rate))
+            .await
+            .map_err(|e| SttError::TranscriptionError(format!("Encoding failed: {}", e)))?
             .map_err(|e| SttError::InvalidAudio(e.to_string()))?;
 
-        debug!("Sending {} bytes to Deepgram API", wav_data.len());
+        debug!(
+            "Sending {} bytes ({}) to Deepgram API",
+            data.len(),
+            self.encoder.mime_type()
+        );
 
         let url = self.build_url(language);
 
@@ -109,8 +126,8 @@ impl SttProvider for DeepgramProvider {
             .client
             .post(&url)
             .header("Authorization", format!("Token {}", self.api_key))
-            .header("Content-Type", "audio/wav")
-            .body(wav_data)
+            .header("Content-Type", self.encoder.mime_type())
+            .body(data)
             .send()
             .await?;
 
@@ -2,9 +2,11 @@ use async_trait::async_trait;
 use reqwest::multipart::{Form, Part};
 use reqwest::Client;
 use serde::Deserialize;
+use std::sync::Arc;
 use std::time::Instant;
 use tracing::debug;
 
+use crate::audio::AudioEncoder;
 use crate::config::AppConfig;
 use crate::error::SttError;
 use crate::stt::{AudioData, SttProvider, SttResult, TranscriptionResult};
@@ -19,6 +21,7 @@ pub struct OpenAIProvider {
     api_key: String,
     model: String,
     endpoint: String,
+    encoder: Arc<dyn AudioEncoder>,
 }
 
 impl OpenAIProvider {
@@ -28,12 +31,16 @@ impl OpenAIProvider {
             .ok_or_else(|| SttError::ProviderUnavailable("OpenAI API key not configured".into()))?;
 
         let client = Client::new();
+        let encoder = crate::audio::encoder(config.providers.openai.upload_format)
+            .map_err(|e| SttError::ProviderUnavailable(e.to_string()))?
+            .into();
 
         Ok(Self {
             client,
             api_key,
             model: config.providers.openai.model.clone(),
             endpoint: config.providers.openai.endpoint.clone(),
+            encoder,
         })
     }
 }
@@ -63,15 +70,25 @@ impl SttProvider for OpenAIProvider {
 
         let start = Instant::now();
 
-        // Encode audio as WAV
-        let wav_data = crate::audio::encode_wav(&audio.samples, audio.sample_rate)
+        // Encode audio in the configured upload format (FLAC and Opus are
+        // CPU-heavy, so keep it off the async workers)
+        let encoder = self.encoder.clone();
+        let samples = audio.samples.clone();
+        let sample_rate = audio.sample_rate;
+        let data = tokio::task::spawn_blocking(move || encoder.encode(&samples, sample_rate))
+            .await
+            .map_err(|e| SttError::TranscriptionError(format!("Encoding failed: {}", e)))?
             .map_err(|e| SttError::InvalidAudio(e.to_string()))?;
 
-        debug!("Sending {} bytes to OpenAI API", wav_data.len());
+        debug!(
+            "Sending {} bytes ({}) to OpenAI API",
+            data.len(),
+            self.encoder.mime_type()
+        );
 
-        let file_part = Part::bytes(wav_data)
-            .file_name("audio.wav")
-            .mime_str("audio/wav")
+        let file_part = Part::bytes(data)
+            .file_name(self.encoder.file_name())
+            .mime_str(self.encoder.mime_type())
             .map_err(|e| SttError::ApiError(format!("Failed to create multipart: {}", e)))?;
 
         let mut form = Form::new()
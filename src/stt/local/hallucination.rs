@@ -0,0 +1,305 @@
+//! Post-transcription guard against whisper hallucinations: phantom phrases
+//! on silence ("Thanks for watching!") and sentences repeated in a loop.
+
+use std::fmt;
+
+use crate::config::{HallucinationAction, HallucinationFilterConfig};
+
+/// Shortest repeat counted as a back-reference when estimating compression
+const MIN_MATCH: usize = 3;
+
+/// Approximate size of a back-reference, in bytes
+const MATCH_COST: usize = 3;
+
+/// Why a segment was caught
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Hallucination {
+    /// Whisper rated the segment as probably not speech and was unsure of
+    /// its words (no-speech probability, average token log probability)
+    NoSpeech(f32, f32),
+    /// The segment repeats itself (compression ratio)
+    Repetitive(f32),
+    /// The segment repeats the one before it word for word
+    Duplicate,
+    /// The segment is a known hallucination
+    Blocklisted,
+}
+
+impl fmt::Display for Hallucination {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoSpeech(prob, logprob) => write!(
+                f,
+                "no-speech probability {:.2}, average log probability {:.2}",
+                prob, logprob
+            ),
+            Self::Repetitive(ratio) => write!(f, "compression ratio {:.1}", ratio),
+            Self::Duplicate => write!(f, "repeats an earlier segment"),
+            Self::Blocklisted => write!(f, "blocklisted"),
+        }
+    }
+}
+
+pub struct HallucinationFilter {
+    action: HallucinationAction,
+    max_no_speech_prob: f32,
+    min_avg_logprob: f32,
+    max_compression_ratio: f32,
+    blocklist: Vec<String>,
+}
+
+impl HallucinationFilter {
+    /// `None` when the filter is disabled
+    pub fn from_config(config: &HallucinationFilterConfig) -> Option<Self> {
+        config.enabled.then(|| Self {
+            action: config.action,
+            max_no_speech_prob: config.max_no_speech_prob,
+            min_avg_logprob: config.min_avg_logprob,
+            max_compression_ratio: config.max_compression_ratio,
+            blocklist: config
+                .blocklist
+                .iter()
+                .map(|entry| normalize(entry))
+                .filter(|entry| !entry.is_empty())
+                .collect(),
+        })
+    }
+
+    pub fn action(&self) -> HallucinationAction {
+        self.action
+    }
+
+    /// Check a segment; `previous` are the segments already accepted and
+    /// `last` marks the final segment of the recording
+    pub fn check(
+        &self,
+        text: &str,
+        no_speech_prob: f32,
+        avg_logprob: f32,
+        previous: &[String],
+        last: bool,
+    ) -> Option<Hallucination> {
+        let normalized = normalize(text);
+        if normalized.is_empty() {
+            return None;
+        }
+
+        // Like whisper's own silence check: a confident decode is kept even
+        // when the no-speech token scored high
+        if no_speech_prob > self.max_no_speech_prob && avg_logprob < self.min_avg_logprob {
+            return Some(Hallucination::NoSpeech(no_speech_prob, avg_logprob));
+        }
+        // Blocklisted phrases are also things people say: only drop them
+        // where whisper tends to invent them, on uncertain segments or the
+        // silence trailing a recording
+        let doubtful =
+            no_speech_prob > self.max_no_speech_prob || avg_logprob < self.min_avg_logprob || last;
+        if doubtful && self.blocklist.contains(&normalized) {
+            return Some(Hallucination::Blocklisted);
+        }
+        // Only back to back: a phrase may legitimately come up again later
+        if previous.last().is_some_and(|p| normalize(p) == normalized) {
+            return Some(Hallucination::Duplicate);
+        }
+        let ratio = compression_ratio(&normalized);
+        if ratio > self.max_compression_ratio {
+            return Some(Hallucination::Repetitive(ratio));
+        }
+        None
+    }
+}
+
+/// Lowercase words without punctuation, separated by single spaces
+fn normalize(text: &str) -> String {
+    text.split_whitespace()
+        .map(|word| {
+            word.chars()
+                .filter(|c| c.is_alphanumeric())
+                .flat_map(char::to_lowercase)
+                .collect::<String>()
+        })
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Estimate of how well `text` compresses, from a greedy LZ77 parse.
+/// Ordinary sentences stay close to 1; text looping on itself scores high,
+/// like the gzip ratio whisper uses for its own fallback.
+fn compression_ratio(text: &str) -> f32 {
+    let bytes = text.as_bytes();
+    if bytes.is_empty() {
+        return 1.0;
+    }
+
+    let mut cost = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        let longest = (0..i)
+            .map(|j| {
+                bytes[i..]
+                    .iter()
+                    .zip(&bytes[j..])
+                    .take_while(|(a, b)| a == b)
+                    .count()
+            })
+            .max()
+            .unwrap_or(0);
+
+        if longest >= MIN_MATCH {
+            cost += MATCH_COST;
+            i += longest;
+        } else {
+            cost += 1;
+            i += 1;
+        }
+    }
+
+    bytes.len() as f32 / cost as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter() -> HallucinationFilter {
+        HallucinationFilter::from_config(&HallucinationFilterConfig::default()).unwrap()
+    }
+
+    /// Check a confidently decoded segment in the middle of a recording
+    fn check(text: &str, previous: &[&str]) -> Option<Hallucination> {
+        let previous: Vec<String> = previous.iter().map(|p| p.to_string()).collect();
+        filter().check(text, 0.0, -0.2, &previous, false)
+    }
+
+    #[test]
+    fn disabled_filter_is_none() {
+        let config = HallucinationFilterConfig {
+            enabled: false,
+            ..Default::default()
+        };
+        assert!(HallucinationFilter::from_config(&config).is_none());
+    }
+
+    #[test]
+    fn keeps_ordinary_speech() {
+        assert_eq!(check(" Let's meet at three tomorrow.", &[]), None);
+        assert_eq!(check("", &[]), None);
+        assert_eq!(check(" ...", &[]), None);
+    }
+
+    #[test]
+    fn no_speech_needs_low_confidence() {
+        let filter = filter();
+        let text = " I'll see you then.";
+
+        assert_eq!(
+            filter.check(text, 0.9, -1.5, &[], false),
+            Some(Hallucination::NoSpeech(0.9, -1.5))
+        );
+        // Confidently decoded words are kept despite the no-speech score
+        assert_eq!(filter.check(text, 0.9, -0.3, &[], false), None);
+        // An unsure decode alone is not enough
+        assert_eq!(filter.check(text, 0.2, -1.5, &[], false), None);
+        // Both bounds are exclusive
+        assert_eq!(filter.check(text, 0.6, -1.5, &[], false), None);
+        assert_eq!(filter.check(text, 0.9, -1.0, &[], false), None);
+    }
+
+    #[test]
+    fn blocklist_ignores_case_and_punctuation() {
+        let filter = filter();
+        let last = |text: &str| filter.check(text, 0.0, -0.2, &[], true);
+
+        assert_eq!(last(" thank you"), Some(Hallucination::Blocklisted));
+        assert_eq!(
+            last(" THANKS FOR WATCHING"),
+            Some(Hallucination::Blocklisted)
+        );
+        // Whole segments only
+        assert_eq!(last(" Thank you for the coffee."), None);
+        // A lone "you" is often real speech
+        assert_eq!(last(" You?"), None);
+    }
+
+    #[test]
+    fn blocklist_only_on_doubtful_segments() {
+        let filter = filter();
+        let text = " Thank you.";
+
+        // A confident "Thank you." mid-recording is real speech
+        assert_eq!(check(text, &[]), None);
+        // Unsure of no-speech, unsure of its words, or trailing the recording
+        for (prob, logprob, last) in [(0.9, -0.3, false), (0.2, -1.5, false), (0.0, -0.2, true)] {
+            assert_eq!(
+                filter.check(text, prob, logprob, &[], last),
+                Some(Hallucination::Blocklisted)
+            );
+        }
+    }
+
+    #[test]
+    fn blocklist_skips_empty_entries() {
+        let config = HallucinationFilterConfig {
+            blocklist: vec!["...".into(), " ".into()],
+            ..Default::default()
+        };
+        let filter = HallucinationFilter::from_config(&config).unwrap();
+        assert!(filter.blocklist.is_empty());
+    }
+
+    #[test]
+    fn duplicate_only_when_consecutive() {
+        assert_eq!(
+            check(" The meeting is at noon.", &[" the meeting is at noon"]),
+            Some(Hallucination::Duplicate)
+        );
+        assert_eq!(
+            check(
+                " The meeting is at noon.",
+                &[" The meeting is at noon.", " Don't be late."]
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn repetitive_above_compression_ratio() {
+        let looped = " I'm going to go to the store. ".repeat(6);
+        assert!(matches!(
+            check(&looped, &[]),
+            Some(Hallucination::Repetitive(ratio)) if ratio > 2.4
+        ));
+
+        let config = HallucinationFilterConfig {
+            max_compression_ratio: 10.0,
+            ..Default::default()
+        };
+        let lenient = HallucinationFilter::from_config(&config).unwrap();
+        assert_eq!(lenient.check(&looped, 0.0, -0.2, &[], false), None);
+    }
+
+    #[test]
+    fn compression_ratio_of_plain_and_looping_text() {
+        assert_eq!(compression_ratio(""), 1.0);
+        // Nothing to back-reference
+        assert_eq!(compression_ratio("abcdefg"), 1.0);
+        // Repeats shorter than MIN_MATCH are literals
+        assert_eq!(compression_ratio("abab"), 1.0);
+        // "abc" + one 9-byte match: 12 bytes for 3 + MATCH_COST
+        assert_eq!(compression_ratio("abcabcabcabc"), 2.0);
+        // A long loop approaches length / (period + MATCH_COST)
+        let looped = "hello ".repeat(50);
+        assert_eq!(compression_ratio(&looped), 300.0 / 9.0);
+
+        let sentence = normalize("The quick brown fox jumps over the lazy dog.");
+        assert!(compression_ratio(&sentence) < 1.2);
+    }
+
+    #[test]
+    fn normalizes_case_punctuation_and_spacing() {
+        assert_eq!(normalize("  Hello,   World! "), "hello world");
+        assert_eq!(normalize("It's 5 o'clock."), "its 5 oclock");
+        assert_eq!(normalize("- ... -"), "");
+    }
+}
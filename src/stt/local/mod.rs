@@ -1,3 +1,4 @@
+mod hallucination;
 mod whisper;
 
 pub use whisper::WhisperProvider;
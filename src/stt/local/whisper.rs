@@ -2,15 +2,20 @@ use async_trait::async_trait;
 use std::sync::Arc;
 use std::time::Instant;
 use tokio::sync::Mutex;
-use tracing::{debug, info};
-use whisper_rs::{FullParams, SamplingStrategy, WhisperContext, WhisperContextParameters};
+use tracing::{debug, info, warn};
+use whisper_rs::{
+    FullParams, SamplingStrategy, WhisperContext, WhisperContextParameters, WhisperSegment,
+};
 
-use crate::config::AppConfig;
+use crate::config::{AppConfig, HallucinationAction, WhisperLocalConfig};
 use crate::error::SttError;
+use crate::stt::local::hallucination::HallucinationFilter;
 use crate::stt::{AudioData, SttProvider, SttResult, TranscriptionResult};
 
 pub struct WhisperProvider {
     ctx: Arc<Mutex<WhisperContext>>,
+    config: Arc<WhisperLocalConfig>,
+    filter: Option<Arc<HallucinationFilter>>,
     #[allow(dead_code)]
     model_name: String,
 }
@@ -44,11 +49,50 @@ impl WhisperProvider {
 
         Ok(Self {
             ctx: Arc::new(Mutex::new(ctx)),
+            config: Arc::new(config.providers.whisper_local.clone()),
+            filter: HallucinationFilter::from_config(
+                &config.providers.whisper_local.hallucination_filter,
+            )
+            .map(Arc::new),
             model_name,
         })
     }
 }
 
+/// Sampling strategy for the configured beam width
+fn strategy(config: &WhisperLocalConfig) -> SamplingStrategy {
+    if config.beam_size > 1 {
+        SamplingStrategy::BeamSearch {
+            beam_size: config.beam_size as i32,
+            // whisper.cpp default (no length penalty)
+            patience: -1.0,
+        }
+    } else {
+        SamplingStrategy::Greedy { best_of: 1 }
+    }
+}
+
+/// Apply the configured decoding parameters; `config` has been validated,
+/// so every value fits whisper.cpp's types
+fn apply_decoding(config: &WhisperLocalConfig, params: &mut FullParams) {
+    // 0 keeps whisper-rs' default (up to 4 threads)
+    if config.threads > 0 {
+        params.set_n_threads(config.threads as i32);
+    }
+    params.set_temperature(config.temperature);
+    params.set_temperature_inc(config.temperature_inc);
+    params.set_no_speech_thold(config.no_speech_threshold);
+    params.set_suppress_blank(config.suppress_blank);
+    params.set_suppress_nst(config.suppress_non_speech);
+    if config.max_segment_len > 0 {
+        // Segment length limits are applied using token timestamps
+        params.set_token_timestamps(true);
+        params.set_split_on_word(true);
+        params.set_max_len(config.max_segment_len as i32);
+    }
+    params.set_single_segment(config.single_segment);
+}
+
 #[async_trait]
 impl SttProvider for WhisperProvider {
     fn name(&self) -> &'static str {
@@ -70,8 +114,11 @@ impl SttProvider for WhisperProvider {
 
         let start = Instant::now();
         let samples = audio.samples.clone();
+        let ends_recording = audio.ends_recording;
         let lang = language.map(|s| s.to_string());
         let ctx = self.ctx.clone();
+        let filter = self.filter.clone();
+        let config = self.config.clone();
 
         debug!(
             "Transcribing {} samples ({:.2}s of audio)",
@@ -79,13 +126,14 @@ impl SttProvider for WhisperProvider {
             audio.duration().as_secs_f32()
         );
 
-        let text = tokio::task::spawn_blocking(move || {
+        let (text, suspect) = tokio::task::spawn_blocking(move || {
             let ctx = ctx.blocking_lock();
             let mut state = ctx.create_state().map_err(|e| {
                 SttError::TranscriptionError(format!("Failed to create state: {}", e))
             })?;
 
-            let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
+            let mut params = FullParams::new(strategy(&config));
+            apply_decoding(&config, &mut params);
 
             // Set language if specified
             if let Some(ref lang) = lang {
@@ -110,18 +158,54 @@ impl SttProvider for WhisperProvider {
             // Get number of segments (returns i32 directly)
             let num_segments = state.full_n_segments();
 
-            let mut text = String::new();
+            let mut segments: Vec<String> = Vec::new();
+            let mut suspect = 0;
             for i in 0..num_segments {
                 // Use get_segment which returns Option<WhisperSegment>
-                if let Some(segment) = state.get_segment(i) {
-                    match segment.to_str_lossy() {
-                        Ok(segment_text) => text.push_str(&segment_text),
-                        Err(e) => debug!("Failed to get segment text {}: {}", i, e),
+                let Some(segment) = state.get_segment(i) else {
+                    continue;
+                };
+                let segment_text = match segment.to_str_lossy() {
+                    Ok(segment_text) => segment_text.into_owned(),
+                    Err(e) => {
+                        debug!("Failed to get segment text {}: {}", i, e);
+                        continue;
+                    }
+                };
+
+                let caught = filter.as_ref().and_then(|filter| {
+                    filter
+                        .check(
+                            &segment_text,
+                            segment.no_speech_probability(),
+                            avg_logprob(&segment),
+                            &segments,
+                            ends_recording && i + 1 == num_segments,
+                        )
+                        .map(|reason| (filter.action(), reason))
+                });
+                match caught {
+                    Some((HallucinationAction::Drop, reason)) => {
+                        warn!(
+                            "Dropped hallucinated segment \"{}\" ({})",
+                            segment_text.trim(),
+                            reason
+                        );
+                    }
+                    Some((HallucinationAction::Flag, reason)) => {
+                        warn!(
+                            "Possible hallucination \"{}\" ({})",
+                            segment_text.trim(),
+                            reason
+                        );
+                        suspect += 1;
+                        segments.push(segment_text);
                     }
+                    None => segments.push(segment_text),
                 }
             }
 
-            Ok::<String, SttError>(text.trim().to_string())
+            Ok::<_, SttError>((segments.concat().trim().to_string(), suspect))
         })
         .await
         .map_err(|e| SttError::TranscriptionError(format!("Task failed: {}", e)))??;
@@ -131,7 +215,8 @@ impl SttProvider for WhisperProvider {
 
         Ok(TranscriptionResult::new(text)
             .with_language(language.unwrap_or("auto"))
-            .with_processing_time(processing_time))
+            .with_processing_time(processing_time)
+            .with_suspect_segments(suspect))
     }
 
     async fn health_check(&self) -> SttResult<()> {
@@ -140,3 +225,15 @@ impl SttProvider for WhisperProvider {
         Ok(())
     }
 }
+
+/// Mean log probability of a segment's tokens (0 without tokens)
+fn avg_logprob(segment: &WhisperSegment) -> f32 {
+    let logprobs: Vec<f32> = (0..segment.n_tokens())
+        .filter_map(|i| segment.get_token(i))
+        .map(|token| token.token_data().plog)
+        .collect();
+    if logprobs.is_empty() {
+        return 0.0;
+    }
+    logprobs.iter().sum::<f32>() / logprobs.len() as f32
+}
@@ -1,15 +1,30 @@
 pub mod provider;
 pub mod local;
 pub mod cloud;
+mod chunked;
 
+pub use chunked::ChunkedProvider;
 pub use provider::{AudioData, SttProvider, SttResult, TranscriptionResult};
 
 use crate::config::{AppConfig, ProviderType};
 
-/// Create a provider based on the configuration
+/// Create a provider based on the configuration; long recordings are split
+/// into chunks unless chunking is disabled
 pub async fn create_provider(
     provider_type: ProviderType,
     config: &AppConfig,
+) -> SttResult<Box<dyn SttProvider>> {
+    let provider = create_base_provider(provider_type, config).await?;
+    if config.chunking.enabled {
+        Ok(Box::new(ChunkedProvider::new(provider, config)))
+    } else {
+        Ok(provider)
+    }
+}
+
+async fn create_base_provider(
+    provider_type: ProviderType,
+    config: &AppConfig,
 ) -> SttResult<Box<dyn SttProvider>> {
     match provider_type {
         ProviderType::WhisperLocal => {
@@ -12,6 +12,9 @@ pub struct AudioData {
     pub samples: Vec<f32>,
     /// Sample rate (should be 16000 for whisper)
     pub sample_rate: u32,
+    /// The audio runs to the end of the recording (false for all but the
+    /// last chunk of a split recording)
+    pub ends_recording: bool,
 }
 
 impl AudioData {
@@ -19,9 +22,15 @@ impl AudioData {
         Self {
             samples,
             sample_rate,
+            ends_recording: true,
         }
     }
 
+    pub fn with_ends_recording(mut self, ends_recording: bool) -> Self {
+        self.ends_recording = ends_recording;
+        self
+    }
+
     /// Duration of the audio in seconds
     pub fn duration(&self) -> Duration {
         Duration::from_secs_f32(self.samples.len() as f32 / self.sample_rate as f32)
@@ -44,6 +53,10 @@ pub struct TranscriptionResult {
     pub confidence: Option<f32>,
     /// Processing time
     pub processing_time: Duration,
+    /// Samples lost to capture overflow; non-zero means the audio had gaps
+    pub dropped_samples: u64,
+    /// Segments kept despite looking like hallucinations
+    pub suspect_segments: usize,
 }
 
 impl TranscriptionResult {
@@ -53,6 +66,8 @@ impl TranscriptionResult {
             language: None,
             confidence: None,
             processing_time: Duration::ZERO,
+            dropped_samples: 0,
+            suspect_segments: 0,
         }
     }
 
@@ -70,6 +85,26 @@ impl TranscriptionResult {
         self.processing_time = duration;
         self
     }
+
+    pub fn with_dropped_samples(mut self, dropped: u64) -> Self {
+        self.dropped_samples = dropped;
+        self
+    }
+
+    pub fn with_suspect_segments(mut self, count: usize) -> Self {
+        self.suspect_segments = count;
+        self
+    }
+
+    /// Whether the transcribed audio was missing samples
+    pub fn has_gaps(&self) -> bool {
+        self.dropped_samples > 0
+    }
+
+    /// Whether part of the transcript may be hallucinated
+    pub fn is_suspect(&self) -> bool {
+        self.suspect_segments > 0
+    }
 }
 
 /// Speech-to-text provider trait
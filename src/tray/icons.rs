@@ -0,0 +1,419 @@
+use std::path::{Path, PathBuf};
+use tracing::{debug, warn};
+
+use crate::error::TrayError;
+use crate::tray::TrayState;
+
+/// Pixmap sizes generated for embedded icons (hosts pick the closest)
+const EMBEDDED_SIZES: [i32; 2] = [32, 64];
+
+/// Number of distinct input level steps drawn in the recording icon
+pub const LEVEL_STEPS: u8 = 8;
+
+/// Quantize an input level (0.0 - 1.0) to the step drawn by the icon
+pub fn level_step(level: f32) -> u8 {
+    (level.clamp(0.0, 1.0) * LEVEL_STEPS as f32).round() as u8
+}
+
+/// Where tray icons come from (`tray.icon_theme`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IconTheme {
+    /// ARGB pixmaps drawn by the app
+    Embedded,
+    /// Freedesktop icon names from the user's icon theme
+    System,
+    /// A directory with idle/recording/processing/error .png or .svg files
+    Path(PathBuf),
+}
+
+impl IconTheme {
+    pub fn from_config(value: &str) -> Self {
+        match value.trim() {
+            "" | "embedded" => IconTheme::Embedded,
+            "system" => IconTheme::System,
+            path => IconTheme::Path(PathBuf::from(path)),
+        }
+    }
+}
+
+/// Icon source for one state when using a custom directory
+#[derive(Debug, Clone)]
+enum CustomIcon {
+    /// Decoded PNG, served as a pixmap
+    Pixmap(ksni::Icon),
+    /// SVG (or other) file the host loads through IconThemePath
+    Named(String),
+    /// Nothing usable found, fall back to the embedded icon
+    Missing,
+}
+
+/// Resolved icons for every tray state
+pub struct Icons {
+    theme: IconTheme,
+    custom: Vec<CustomIcon>,
+}
+
+impl Icons {
+    pub fn new(theme: IconTheme) -> Self {
+        let custom = match &theme {
+            IconTheme::Path(dir) => TrayState::ALL
+                .iter()
+                .map(|state| load_custom(dir, *state))
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        Self { theme, custom }
+    }
+
+    fn custom(&self, state: TrayState) -> Option<&CustomIcon> {
+        self.custom.get(state as usize)
+    }
+
+    /// Icon name for the StatusNotifierItem (empty when a pixmap is used)
+    pub fn icon_name(&self, state: TrayState) -> String {
+        match (&self.theme, self.custom(state)) {
+            (IconTheme::System, _) => state.icon_name().into(),
+            (IconTheme::Path(_), Some(CustomIcon::Named(name))) => name.clone(),
+            _ => String::new(),
+        }
+    }
+
+    /// Extra icon search path (only for custom directories)
+    pub fn theme_path(&self) -> String {
+        match &self.theme {
+            IconTheme::Path(dir) => dir.to_string_lossy().to_string(),
+            _ => String::new(),
+        }
+    }
+
+    /// Pixmaps for the state; `level` (0..=LEVEL_STEPS) animates the recording icon
+    pub fn pixmap(&self, state: TrayState, level: u8) -> Vec<ksni::Icon> {
+        match (&self.theme, self.custom(state)) {
+            (IconTheme::System, _) => Vec::new(),
+            (IconTheme::Path(_), Some(CustomIcon::Pixmap(icon))) => vec![icon.clone()],
+            (IconTheme::Path(_), Some(CustomIcon::Named(_))) => Vec::new(),
+            _ => EMBEDDED_SIZES
+                .iter()
+                .map(|&size| draw_embedded(state, level, size))
+                .collect(),
+        }
+    }
+}
+
+/// Look for `<state>.png` or `<state>.svg` in the icon directory
+fn load_custom(dir: &Path, state: TrayState) -> CustomIcon {
+    let name = state.file_stem();
+
+    let png = dir.join(format!("{}.png", name));
+    if png.exists() {
+        match load_png(&png) {
+            Ok(icon) => return CustomIcon::Pixmap(icon),
+            Err(e) => warn!("Failed to load tray icon {:?}: {}", png, e),
+        }
+    }
+
+    if dir.join(format!("{}.svg", name)).exists() {
+        return CustomIcon::Named(name.to_string());
+    }
+
+    warn!(
+        "No {}.png or {}.svg in {:?}, using embedded icon",
+        name, name, dir
+    );
+    CustomIcon::Missing
+}
+
+/// Decode a PNG into an ARGB32 (network byte order) pixmap
+fn load_png(path: &Path) -> Result<ksni::Icon, TrayError> {
+    let file = std::fs::File::open(path).map_err(|e| TrayError::IconNotFound(e.to_string()))?;
+    let mut decoder = png::Decoder::new(file);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder
+        .read_info()
+        .map_err(|e| TrayError::IconNotFound(e.to_string()))?;
+
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader
+        .next_frame(&mut buf)
+        .map_err(|e| TrayError::IconNotFound(e.to_string()))?;
+    let pixels = &buf[..info.buffer_size()];
+
+    let data: Vec<u8> = match info.color_type {
+        png::ColorType::Rgba => pixels
+            .chunks_exact(4)
+            .flat_map(|p| [p[3], p[0], p[1], p[2]])
+            .collect(),
+        png::ColorType::Rgb => pixels
+            .chunks_exact(3)
+            .flat_map(|p| [255, p[0], p[1], p[2]])
+            .collect(),
+        png::ColorType::GrayscaleAlpha => pixels
+            .chunks_exact(2)
+            .flat_map(|p| [p[1], p[0], p[0], p[0]])
+            .collect(),
+        png::ColorType::Grayscale => pixels.iter().flat_map(|&g| [255, g, g, g]).collect(),
+        png::ColorType::Indexed => {
+            return Err(TrayError::IconNotFound(
+                "Indexed PNG was not expanded".into(),
+            ))
+        }
+    };
+
+    debug!("Loaded tray icon {:?} ({}x{})", path, info.width, info.height);
+
+    Ok(ksni::Icon {
+        width: info.width as i32,
+        height: info.height as i32,
+        data,
+    })
+}
+
+/// Draw the embedded microphone icon.
+///
+/// Shapes are described in unit coordinates and rasterized with a one pixel
+/// anti-aliased edge. While recording, the capsule fills from the bottom
+/// according to the input level.
+fn draw_embedded(state: TrayState, level: u8, size: i32) -> ksni::Icon {
+    let (r, g, b) = state.color();
+    let px = 1.0 / size as f32;
+    let fill_to = 0.58 - 0.46 * (level.min(LEVEL_STEPS) as f32 / LEVEL_STEPS as f32);
+
+    let mut data = Vec::with_capacity((size * size * 4) as usize);
+    for y in 0..size {
+        for x in 0..size {
+            let (u, v) = ((x as f32 + 0.5) * px, (y as f32 + 0.5) * px);
+
+            // Capsule (mic head)
+            let capsule = coverage(sd_capsule(u, v, 0.5, 0.26, 0.44, 0.14), px);
+            // U-shaped holder below the capsule
+            let holder = if v > 0.42 {
+                coverage(sd_ring(u, v, 0.5, 0.42, 0.24, 0.035), px)
+            } else {
+                0.0
+            };
+            // Stem and base
+            let stem = coverage(sd_rect(u, v, 0.5, 0.74, 0.035, 0.08), px);
+            let base = coverage(sd_rect(u, v, 0.5, 0.84, 0.16, 0.035), px);
+
+            let mut alpha = capsule.max(holder).max(stem).max(base);
+            let (mut pr, mut pg, mut pb) = (r, g, b);
+
+            // Level meter inside the capsule
+            if state == TrayState::Recording && capsule > 0.0 && v >= fill_to {
+                (pr, pg, pb) = (255, 255, 255);
+            }
+
+            // Error slash
+            if state == TrayState::Error {
+                let slash = coverage(sd_line(u, v, 0.2, 0.15, 0.8, 0.85, 0.04), px);
+                alpha = alpha.max(slash);
+            }
+
+            data.extend_from_slice(&[(alpha * 255.0) as u8, pr, pg, pb]);
+        }
+    }
+
+    ksni::Icon {
+        width: size,
+        height: size,
+        data,
+    }
+}
+
+/// Anti-aliased coverage from a signed distance
+fn coverage(distance: f32, px: f32) -> f32 {
+    (0.5 - distance / px).clamp(0.0, 1.0)
+}
+
+/// Vertical capsule centered on `cx` from `top` to `bottom` (centers of the caps)
+fn sd_capsule(u: f32, v: f32, cx: f32, top: f32, bottom: f32, radius: f32) -> f32 {
+    let cy = v.clamp(top, bottom);
+    ((u - cx).powi(2) + (v - cy).powi(2)).sqrt() - radius
+}
+
+/// Ring of `radius` and half `thickness` around (cx, cy)
+fn sd_ring(u: f32, v: f32, cx: f32, cy: f32, radius: f32, thickness: f32) -> f32 {
+    (((u - cx).powi(2) + (v - cy).powi(2)).sqrt() - radius).abs() - thickness
+}
+
+/// Axis-aligned rectangle with half extents (hw, hh)
+fn sd_rect(u: f32, v: f32, cx: f32, cy: f32, hw: f32, hh: f32) -> f32 {
+    let dx = (u - cx).abs() - hw;
+    let dy = (v - cy).abs() - hh;
+    let outside = (dx.max(0.0).powi(2) + dy.max(0.0).powi(2)).sqrt();
+    outside + dx.max(dy).min(0.0)
+}
+
+/// Line segment with half `width`
+fn sd_line(u: f32, v: f32, x0: f32, y0: f32, x1: f32, y1: f32, width: f32) -> f32 {
+    let (dx, dy) = (x1 - x0, y1 - y0);
+    let t = (((u - x0) * dx + (v - y0) * dy) / (dx * dx + dy * dy)).clamp(0.0, 1.0);
+    ((u - x0 - t * dx).powi(2) + (v - y0 - t * dy).powi(2)).sqrt() - width
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Icon directory unique to this test run
+    fn temp_icon_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "super-whisper-icons-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Write a 2x1 RGBA PNG: one opaque red pixel, one transparent
+    fn write_png(path: &Path) {
+        let file = std::fs::File::create(path).unwrap();
+        let mut encoder = png::Encoder::new(file, 2, 1);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().unwrap();
+        writer
+            .write_image_data(&[255, 0, 0, 255, 0, 0, 0, 0])
+            .unwrap();
+    }
+
+    /// Opaque pixels of the level meter's white fill
+    fn meter_pixels(icon: &ksni::Icon) -> usize {
+        icon.data
+            .chunks_exact(4)
+            .filter(|p| p[0] > 0 && p[1..] == [255, 255, 255])
+            .count()
+    }
+
+    #[test]
+    fn parses_the_theme_setting() {
+        assert_eq!(IconTheme::from_config(""), IconTheme::Embedded);
+        assert_eq!(IconTheme::from_config("embedded"), IconTheme::Embedded);
+        assert_eq!(IconTheme::from_config(" system "), IconTheme::System);
+        assert_eq!(
+            IconTheme::from_config("/usr/share/my-icons"),
+            IconTheme::Path(PathBuf::from("/usr/share/my-icons"))
+        );
+    }
+
+    #[test]
+    fn names_system_icons_per_state() {
+        let icons = Icons::new(IconTheme::System);
+        let names: Vec<String> = TrayState::ALL
+            .iter()
+            .map(|state| icons.icon_name(*state))
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "audio-input-microphone",
+                "media-record",
+                "system-run",
+                "dialog-error"
+            ]
+        );
+        for state in TrayState::ALL {
+            assert!(icons.pixmap(state, LEVEL_STEPS).is_empty());
+        }
+        assert_eq!(icons.theme_path(), "");
+    }
+
+    #[test]
+    fn draws_embedded_icons_per_state() {
+        let icons = Icons::new(IconTheme::Embedded);
+        assert_eq!(icons.theme_path(), "");
+        for state in TrayState::ALL {
+            assert_eq!(icons.icon_name(state), "");
+            let pixmaps = icons.pixmap(state, 0);
+            let sizes: Vec<i32> = pixmaps.iter().map(|icon| icon.width).collect();
+            assert_eq!(sizes, EMBEDDED_SIZES);
+            for icon in &pixmaps {
+                assert_eq!(icon.data.len(), (icon.width * icon.height * 4) as usize);
+            }
+        }
+
+        let colors: Vec<[u8; 3]> = TrayState::ALL
+            .iter()
+            .map(|state| {
+                let icon = &icons.pixmap(*state, 0)[0];
+                let pixel = icon.data.chunks_exact(4).find(|p| p[0] == 255).unwrap();
+                [pixel[1], pixel[2], pixel[3]]
+            })
+            .collect();
+        for (i, color) in colors.iter().enumerate() {
+            assert!(!colors[i + 1..].contains(color), "{:?}", colors);
+        }
+    }
+
+    #[test]
+    fn quantizes_levels_into_bands() {
+        assert_eq!(level_step(-0.5), 0);
+        assert_eq!(level_step(0.0), 0);
+        assert_eq!(level_step(0.06), 0);
+        assert_eq!(level_step(0.07), 1);
+        assert_eq!(level_step(0.5), LEVEL_STEPS / 2);
+        assert_eq!(level_step(0.99), LEVEL_STEPS);
+        assert_eq!(level_step(1.0), LEVEL_STEPS);
+        assert_eq!(level_step(3.0), LEVEL_STEPS);
+    }
+
+    #[test]
+    fn fills_the_recording_icon_with_the_level() {
+        let icons = Icons::new(IconTheme::Embedded);
+        let fills: Vec<usize> = (0..=LEVEL_STEPS + 1)
+            .map(|step| meter_pixels(&icons.pixmap(TrayState::Recording, step)[0]))
+            .collect();
+        assert!(
+            fills[..=LEVEL_STEPS as usize]
+                .windows(2)
+                .all(|pair| pair[0] < pair[1]),
+            "{:?}",
+            fills
+        );
+        // Out of range levels are drawn full
+        assert_eq!(fills[LEVEL_STEPS as usize + 1], fills[LEVEL_STEPS as usize]);
+
+        // Only the recording icon shows the level
+        for state in [TrayState::Idle, TrayState::Processing, TrayState::Error] {
+            assert_eq!(
+                icons.pixmap(state, 0)[0].data,
+                icons.pixmap(state, LEVEL_STEPS)[0].data
+            );
+        }
+    }
+
+    #[test]
+    fn resolves_a_custom_icon_directory() {
+        let dir = temp_icon_dir("custom");
+        write_png(&dir.join("idle.png"));
+        std::fs::write(dir.join("recording.svg"), "<svg/>").unwrap();
+        // A broken PNG falls back to the SVG next to it
+        std::fs::write(dir.join("processing.png"), "not a png").unwrap();
+        std::fs::write(dir.join("processing.svg"), "<svg/>").unwrap();
+        // Nothing for error
+
+        let icons = Icons::new(IconTheme::Path(dir.clone()));
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(icons.theme_path(), dir.to_string_lossy());
+
+        assert_eq!(icons.icon_name(TrayState::Idle), "");
+        let idle = icons.pixmap(TrayState::Idle, 0);
+        assert_eq!(idle.len(), 1);
+        assert_eq!((idle[0].width, idle[0].height), (2, 1));
+        assert_eq!(idle[0].data, [255, 255, 0, 0, 0, 0, 0, 0]);
+
+        assert_eq!(icons.icon_name(TrayState::Recording), "recording");
+        assert!(icons.pixmap(TrayState::Recording, LEVEL_STEPS).is_empty());
+        assert_eq!(icons.icon_name(TrayState::Processing), "processing");
+        assert!(icons.pixmap(TrayState::Processing, 0).is_empty());
+
+        assert_eq!(icons.icon_name(TrayState::Error), "");
+        assert_eq!(
+            icons.pixmap(TrayState::Error, 0)[0].data,
+            Icons::new(IconTheme::Embedded).pixmap(TrayState::Error, 0)[0].data
+        );
+    }
+}
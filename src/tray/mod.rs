@@ -1,3 +1,5 @@
+mod icons;
 mod sni;
 
-pub use sni::{TrayHandle, TrayIcon, TrayState};
+pub use icons::IconTheme;
+pub use sni::{MenuState, TrayHandle, TrayIcon, TrayState};
@@ -1,8 +1,12 @@
 use std::sync::atomic::{AtomicU8, Ordering};
-use std::sync::Arc;
-use tracing::{debug, error, info};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::{debug, error, info, warn};
 
+use crate::config::{ProviderType, TrayConfig};
 use crate::error::TrayError;
+use crate::ipc::{CommandRequest, CommandSender, IpcCommand};
+use crate::tray::icons::{level_step, IconTheme, Icons, LEVEL_STEPS};
 
 /// Tray icon states
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -26,7 +30,14 @@ impl From<u8> for TrayState {
 }
 
 impl TrayState {
-    fn icon_name(&self) -> &'static str {
+    pub(crate) const ALL: [TrayState; 4] = [
+        TrayState::Idle,
+        TrayState::Recording,
+        TrayState::Processing,
+        TrayState::Error,
+    ];
+
+    pub(crate) fn icon_name(&self) -> &'static str {
         match self {
             TrayState::Idle => "audio-input-microphone",
             TrayState::Recording => "media-record",
@@ -35,6 +46,26 @@ impl TrayState {
         }
     }
 
+    /// File name (without extension) looked up in a custom icon directory
+    pub(crate) fn file_stem(&self) -> &'static str {
+        match self {
+            TrayState::Idle => "idle",
+            TrayState::Recording => "recording",
+            TrayState::Processing => "processing",
+            TrayState::Error => "error",
+        }
+    }
+
+    /// Color of the embedded icon
+    pub(crate) fn color(&self) -> (u8, u8, u8) {
+        match self {
+            TrayState::Idle => (0xE0, 0xE0, 0xE0),
+            TrayState::Recording => (0xE5, 0x39, 0x35),
+            TrayState::Processing => (0xFF, 0xB3, 0x00),
+            TrayState::Error => (0xD3, 0x2F, 0x2F),
+        }
+    }
+
     fn tooltip(&self) -> &'static str {
         match self {
             TrayState::Idle => "SuperWhisper - Ready",
@@ -54,11 +85,38 @@ impl TrayState {
     }
 }
 
+/// Dynamic menu contents pushed from the app
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MenuState {
+    /// Recent transcripts, newest first
+    pub recent: Vec<String>,
+    /// Selectable providers and the active one
+    pub providers: Vec<String>,
+    pub provider: String,
+    /// Selectable languages and the active one
+    pub languages: Vec<String>,
+    pub language: String,
+    /// Available input devices and the selected one (None = default)
+    pub devices: Vec<String>,
+    pub device: Option<String>,
+    pub auto_paste: bool,
+}
+
+/// Initial and maximum delay before restarting a failed tray service
+const RESTART_DELAY: Duration = Duration::from_secs(2);
+const MAX_RESTART_DELAY: Duration = Duration::from_secs(60);
+
 /// Handle to control the tray from outside
 #[derive(Clone)]
 pub struct TrayHandle {
     state: Arc<AtomicU8>,
-    handle: ksni::Handle<SuperWhisperTray>,
+    // Quantized input level (0..=LEVEL_STEPS) for the animated recording icon
+    level: Arc<AtomicU8>,
+    menu: Arc<Mutex<MenuState>>,
+    // Last failed tray action, shown in the tooltip until the next success
+    error: Arc<Mutex<Option<String>>>,
+    // Replaced whenever the tray service is restarted; None while it is down
+    handle: Arc<Mutex<Option<ksni::Handle<SuperWhisperTray>>>>,
 }
 
 impl TrayHandle {
@@ -67,7 +125,44 @@ impl TrayHandle {
         let old = self.state.swap(state as u8, Ordering::SeqCst);
         if old != state as u8 {
             debug!("Tray state: {:?} -> {:?}", TrayState::from(old), state);
-            self.handle.update(|_| {});
+            self.refresh();
+        }
+    }
+
+    /// Update the input level (0.0 - 1.0) shown by the recording icon
+    pub fn set_level(&self, level: f32) {
+        let step = level_step(level);
+        let old = self.level.swap(step, Ordering::Relaxed);
+        // Only push a new pixmap when the drawn step changes
+        if old != step && self.state() == TrayState::Recording {
+            self.refresh();
+        }
+    }
+
+    /// Replace the dynamic menu contents
+    pub fn set_menu(&self, menu: MenuState) {
+        let mut current = self.menu.lock().unwrap();
+        if *current != menu {
+            *current = menu;
+            drop(current);
+            self.refresh();
+        }
+    }
+
+    /// Show (or clear) the error of the last tray action
+    fn set_error(&self, error: Option<String>) {
+        let mut current = self.error.lock().unwrap();
+        if *current != error {
+            *current = error;
+            drop(current);
+            self.refresh();
+        }
+    }
+
+    /// Ask the tray service to re-read its properties and menu
+    fn refresh(&self) {
+        if let Some(handle) = self.handle.lock().unwrap().as_ref() {
+            handle.update(|_| {});
         }
     }
 
@@ -77,10 +172,59 @@ impl TrayHandle {
     }
 }
 
+/// Sends tray actions to the daemon's main loop and reports failures
+#[derive(Clone)]
+struct Commands {
+    tx: CommandSender,
+    runtime: tokio::runtime::Handle,
+    tray: TrayHandle,
+}
+
+impl Commands {
+    fn send(&self, command: IpcCommand) {
+        debug!("Tray command: {:?}", command);
+        let (request, reply) = CommandRequest::with_reply(command);
+
+        // Menu callbacks run on the D-Bus thread, never block it
+        if let Err(e) = self.tx.try_send(request) {
+            error!("Failed to send tray command: {}", e);
+            self.tray.set_error(Some("Daemon is not responding".into()));
+            return;
+        }
+
+        let tray = self.tray.clone();
+        self.runtime.spawn(async move {
+            match reply.await {
+                Ok(Ok(_)) => tray.set_error(None),
+                Ok(Err(e)) => {
+                    warn!("Tray command failed: {}", e);
+                    tray.set_error(Some(e));
+                }
+                Err(_) => debug!("Tray command dropped without a result"),
+            }
+        });
+    }
+}
+
 /// The actual tray implementation
 struct SuperWhisperTray {
     state: Arc<AtomicU8>,
-    socket_path: String,
+    level: Arc<AtomicU8>,
+    menu: Arc<Mutex<MenuState>>,
+    error: Arc<Mutex<Option<String>>>,
+    icons: Arc<Icons>,
+    commands: Commands,
+}
+
+impl SuperWhisperTray {
+    fn state(&self) -> TrayState {
+        TrayState::from(self.state.load(Ordering::SeqCst))
+    }
+
+    fn pixmap(&self) -> Vec<ksni::Icon> {
+        self.icons
+            .pixmap(self.state(), self.level.load(Ordering::Relaxed))
+    }
 }
 
 impl ksni::Tray for SuperWhisperTray {
@@ -93,29 +237,47 @@ impl ksni::Tray for SuperWhisperTray {
     }
 
     fn icon_name(&self) -> String {
-        let state = TrayState::from(self.state.load(Ordering::SeqCst));
-        state.icon_name().into()
+        self.icons.icon_name(self.state())
+    }
+
+    fn icon_theme_path(&self) -> String {
+        self.icons.theme_path()
+    }
+
+    fn icon_pixmap(&self) -> Vec<ksni::Icon> {
+        self.pixmap()
+    }
+
+    fn attention_icon_pixmap(&self) -> Vec<ksni::Icon> {
+        self.pixmap()
     }
 
     fn tool_tip(&self) -> ksni::ToolTip {
-        let state = TrayState::from(self.state.load(Ordering::SeqCst));
+        let state = self.state();
         ksni::ToolTip {
             title: state.tooltip().into(),
-            description: String::new(),
-            icon_name: state.icon_name().into(),
-            icon_pixmap: Vec::new(),
+            description: match self.error.lock().unwrap().clone() {
+                Some(error) => error,
+                None if state == TrayState::Recording => {
+                    level_meter(self.level.load(Ordering::Relaxed))
+                }
+                None => String::new(),
+            },
+            icon_name: self.icons.icon_name(state),
+            icon_pixmap: self.pixmap(),
         }
     }
 
     fn status(&self) -> ksni::Status {
-        let state = TrayState::from(self.state.load(Ordering::SeqCst));
+        let state = self.state();
         state.status()
     }
 
     fn menu(&self) -> Vec<ksni::MenuItem<Self>> {
         use ksni::menu::*;
 
-        let state = TrayState::from(self.state.load(Ordering::SeqCst));
+        let state = self.state();
+        let menu = self.menu.lock().unwrap().clone();
 
         vec![
             StandardItem {
@@ -130,7 +292,7 @@ impl ksni::Tray for SuperWhisperTray {
                     _ => "media-record".into(),
                 },
                 activate: Box::new(|this: &mut Self| {
-                    send_command(&this.socket_path, "toggle");
+                    this.commands.send(IpcCommand::Toggle { output: None });
                 }),
                 ..Default::default()
             }
@@ -141,7 +303,50 @@ impl ksni::Tray for SuperWhisperTray {
                 icon_name: "process-stop".into(),
                 enabled: state == TrayState::Recording || state == TrayState::Processing,
                 activate: Box::new(|this: &mut Self| {
-                    send_command(&this.socket_path, "cancel");
+                    this.commands.send(IpcCommand::Cancel);
+                }),
+                ..Default::default()
+            }
+            .into(),
+            MenuItem::Separator,
+            recent_menu(&menu.recent),
+            radio_menu(
+                "Provider",
+                &menu.providers,
+                &menu.provider,
+                |provider| provider.parse::<ProviderType>().ok().map(IpcCommand::Provider),
+            ),
+            radio_menu(
+                "Language",
+                &menu.languages,
+                &menu.language,
+                |language| Some(IpcCommand::Language(language.to_string())),
+            ),
+            device_menu(&menu.devices, menu.device.as_deref()),
+            CheckmarkItem {
+                label: "Auto-paste".into(),
+                checked: menu.auto_paste,
+                activate: Box::new(move |this: &mut Self| {
+                    this.commands.send(IpcCommand::AutoPaste(!menu.auto_paste));
+                }),
+                ..Default::default()
+            }
+            .into(),
+            MenuItem::Separator,
+            StandardItem {
+                label: "Open Config".into(),
+                icon_name: "preferences-system".into(),
+                activate: Box::new(|this: &mut Self| {
+                    this.commands.send(IpcCommand::OpenConfig);
+                }),
+                ..Default::default()
+            }
+            .into(),
+            StandardItem {
+                label: "Open History".into(),
+                icon_name: "document-open-recent".into(),
+                activate: Box::new(|this: &mut Self| {
+                    this.commands.send(IpcCommand::OpenHistory);
                 }),
                 ..Default::default()
             }
@@ -151,7 +356,7 @@ impl ksni::Tray for SuperWhisperTray {
                 label: "Quit".into(),
                 icon_name: "application-exit".into(),
                 activate: Box::new(|this: &mut Self| {
-                    send_command(&this.socket_path, "shutdown");
+                    this.commands.send(IpcCommand::Shutdown);
                 }),
                 ..Default::default()
             }
@@ -161,22 +366,198 @@ impl ksni::Tray for SuperWhisperTray {
 
     fn activate(&mut self, _x: i32, _y: i32) {
         // Left click toggles recording
-        send_command(&self.socket_path, "toggle");
+        self.commands.send(IpcCommand::Toggle { output: None });
+    }
+
+    fn watcher_online(&self) {
+        info!("StatusNotifier host available, tray registered");
+    }
+
+    fn watcher_offine(&self) -> bool {
+        // Keep the service running; ksni re-registers once a host appears
+        warn!("No StatusNotifier host on D-Bus, tray will appear when one starts");
+        true
     }
 }
 
-fn send_command(socket_path: &str, command: &str) {
-    use std::io::Write;
-    use std::os::unix::net::UnixStream;
+/// Longest transcript preview shown in the menu
+const RECENT_LABEL_CHARS: usize = 40;
+
+/// "Recent" submenu: each transcript can be copied or pasted again
+fn recent_menu(recent: &[String]) -> ksni::MenuItem<SuperWhisperTray> {
+    use ksni::menu::*;
 
-    match UnixStream::connect(socket_path) {
-        Ok(mut stream) => {
-            if let Err(e) = stream.write_all(format!("{}\n", command).as_bytes()) {
-                error!("Failed to send tray command: {}", e);
+    let submenu = recent
+        .iter()
+        .enumerate()
+        .map(|(index, text)| {
+            SubMenu {
+                label: preview(text),
+                submenu: vec![
+                    StandardItem {
+                        label: "Copy".into(),
+                        icon_name: "edit-copy".into(),
+                        activate: Box::new(move |this: &mut SuperWhisperTray| {
+                            this.commands.send(IpcCommand::Copy(index));
+                        }),
+                        ..Default::default()
+                    }
+                    .into(),
+                    StandardItem {
+                        label: "Paste".into(),
+                        icon_name: "edit-paste".into(),
+                        activate: Box::new(move |this: &mut SuperWhisperTray| {
+                            this.commands.send(IpcCommand::Paste(index));
+                        }),
+                        ..Default::default()
+                    }
+                    .into(),
+                ],
+                ..Default::default()
             }
+            .into()
+        })
+        .collect();
+
+    SubMenu {
+        label: "Recent".into(),
+        icon_name: "document-open-recent".into(),
+        enabled: !recent.is_empty(),
+        submenu,
+        ..Default::default()
+    }
+    .into()
+}
+
+/// Submenu with a radio group; selecting an option sends `command(option)`
+fn radio_menu(
+    label: &str,
+    options: &[String],
+    selected: &str,
+    command: fn(&str) -> Option<IpcCommand>,
+) -> ksni::MenuItem<SuperWhisperTray> {
+    use ksni::menu::*;
+
+    let values = options.to_vec();
+    SubMenu {
+        label: label.into(),
+        submenu: vec![RadioGroup {
+            selected: options.iter().position(|o| o == selected).unwrap_or(0),
+            select: Box::new(move |this: &mut SuperWhisperTray, index| {
+                if let Some(command) = values.get(index).and_then(|v| command(v)) {
+                    this.commands.send(command);
+                }
+            }),
+            options: options
+                .iter()
+                .map(|o| RadioItem {
+                    label: o.clone(),
+                    ..Default::default()
+                })
+                .collect(),
+        }
+        .into()],
+        ..Default::default()
+    }
+    .into()
+}
+
+/// "Input Device" submenu; the first option restores the default device
+fn device_menu(devices: &[String], selected: Option<&str>) -> ksni::MenuItem<SuperWhisperTray> {
+    use ksni::menu::*;
+
+    let values = devices.to_vec();
+    let mut options = vec![RadioItem {
+        label: "Default".into(),
+        ..Default::default()
+    }];
+    options.extend(devices.iter().map(|d| RadioItem {
+        label: d.clone(),
+        ..Default::default()
+    }));
+
+    SubMenu {
+        label: "Input Device".into(),
+        icon_name: "audio-input-microphone".into(),
+        submenu: vec![RadioGroup {
+            selected: selected
+                .and_then(|s| devices.iter().position(|d| d == s))
+                .map(|i| i + 1)
+                .unwrap_or(0),
+            select: Box::new(move |this: &mut SuperWhisperTray, index| {
+                let device = match index {
+                    0 => None,
+                    i => match values.get(i - 1) {
+                        Some(device) => Some(device.clone()),
+                        None => return,
+                    },
+                };
+                this.commands.send(IpcCommand::Device(device));
+            }),
+            options,
         }
-        Err(e) => {
-            error!("Failed to connect to socket for tray command: {}", e);
+        .into()],
+        ..Default::default()
+    }
+    .into()
+}
+
+/// Text meter for the tooltip, e.g. "Input level ▮▮▮▯▯▯▯▯"
+fn level_meter(step: u8) -> String {
+    let step = step.min(LEVEL_STEPS) as usize;
+    format!(
+        "Input level {}{}",
+        "▮".repeat(step),
+        "▯".repeat(LEVEL_STEPS as usize - step)
+    )
+}
+
+/// Single-line, shortened transcript for menu labels
+fn preview(text: &str) -> String {
+    let line = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    let line = if line.chars().count() > RECENT_LABEL_CHARS {
+        let short: String = line.chars().take(RECENT_LABEL_CHARS - 1).collect();
+        format!("{}…", short)
+    } else {
+        line
+    };
+    // A single underscore marks a mnemonic in dbusmenu labels
+    line.replace('_', "__")
+}
+
+/// Run the tray service until it is shut down, restarting it on D-Bus errors
+fn run_service(handle: TrayHandle, icons: Arc<Icons>, commands: Commands) {
+    let mut delay = RESTART_DELAY;
+
+    loop {
+        let tray = SuperWhisperTray {
+            state: handle.state.clone(),
+            level: handle.level.clone(),
+            menu: handle.menu.clone(),
+            error: handle.error.clone(),
+            icons: icons.clone(),
+            commands: commands.clone(),
+        };
+
+        let service = ksni::TrayService::new(tray);
+        *handle.handle.lock().unwrap() = Some(service.handle());
+
+        let started = std::time::Instant::now();
+        match service.run() {
+            Ok(()) => {
+                debug!("Tray service stopped");
+                break;
+            }
+            Err(e) => {
+                *handle.handle.lock().unwrap() = None;
+                // A service that ran for a while gets a fresh backoff
+                if started.elapsed() > MAX_RESTART_DELAY {
+                    delay = RESTART_DELAY;
+                }
+                error!("Tray service error: {}, restarting in {:?}", e, delay);
+                std::thread::sleep(delay);
+                delay = (delay * 2).min(MAX_RESTART_DELAY);
+            }
         }
     }
 }
@@ -187,33 +568,40 @@ pub struct TrayIcon {
 }
 
 impl TrayIcon {
-    /// Create and start a new tray icon
-    pub fn new(socket_path: String) -> Result<Self, TrayError> {
-        let state = Arc::new(AtomicU8::new(TrayState::Idle as u8));
+    /// Create and start a new tray icon.
+    ///
+    /// The tray service runs on its own thread (D-Bus needs its own event loop)
+    /// and is restarted with backoff if its D-Bus connection fails. Menu
+    /// actions are sent to the daemon through `commands`; must be called from
+    /// within the tokio runtime.
+    pub fn new(commands: CommandSender, config: &TrayConfig) -> Result<Self, TrayError> {
+        let handle = TrayHandle {
+            state: Arc::new(AtomicU8::new(TrayState::Idle as u8)),
+            level: Arc::new(AtomicU8::new(0)),
+            menu: Arc::new(Mutex::new(MenuState::default())),
+            error: Arc::new(Mutex::new(None)),
+            handle: Arc::new(Mutex::new(None)),
+        };
 
-        let tray = SuperWhisperTray {
-            state: state.clone(),
-            socket_path,
+        let runtime = tokio::runtime::Handle::try_current()
+            .map_err(|e| TrayError::CreateError(e.to_string()))?;
+        let commands = Commands {
+            tx: commands,
+            runtime,
+            tray: handle.clone(),
         };
 
-        let service = ksni::TrayService::new(tray);
-        let handle = service.handle();
+        let icons = Arc::new(Icons::new(IconTheme::from_config(&config.icon_theme)));
 
-        // Spawn the tray service in a separate thread (D-Bus needs its own event loop)
-        std::thread::spawn(move || {
-            if let Err(e) = service.run() {
-                error!("Tray service error: {}", e);
-            }
-        });
+        let supervisor = handle.clone();
+        std::thread::Builder::new()
+            .name("tray".into())
+            .spawn(move || run_service(supervisor, icons, commands))
+            .map_err(|e| TrayError::CreateError(e.to_string()))?;
 
         info!("System tray initialized");
 
-        Ok(Self {
-            handle: TrayHandle {
-                state,
-                handle,
-            },
-        })
+        Ok(Self { handle })
     }
 
     /// Get a handle to control the tray